@@ -234,3 +234,199 @@ fn bench_quick_xml_one_cdata_event_trimmed(b: &mut Bencher) {
         assert_eq!(nbtxt, 518);
     })
 }
+
+#[bench]
+fn bench_quick_xml_many_attributes(b: &mut Bencher) {
+    let mut src = b"<hello".to_vec();
+    for i in 0..500 {
+        src.extend_from_slice(format!(r#" attribute{}="value{}""#, i, i).as_bytes());
+    }
+    src.extend_from_slice(b">");
+    let mut buf = Vec::with_capacity(src.len());
+    b.iter(|| {
+        let mut r = Reader::from_reader(src.as_ref());
+        let mut count = test::black_box(0);
+        r.trim_text(true);
+        match r.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) => {
+                for attr in e.attributes() {
+                    let _attr = attr.unwrap();
+                    count += 1;
+                }
+            }
+            something_else => panic!("Did not expect {:?}", something_else),
+        };
+
+        buf.clear();
+
+        assert_eq!(count, 500);
+    })
+}
+
+#[bench]
+fn bench_quick_xml_indented_attributes(b: &mut Bencher) {
+    let mut src = b"<hello".to_vec();
+    for i in 0..100 {
+        src.extend_from_slice(b"\n                ");
+        src.extend_from_slice(format!(r#"attribute{}="{}""#, i, "value".repeat(10)).as_bytes());
+    }
+    src.extend_from_slice(b">");
+    let mut buf = Vec::with_capacity(src.len());
+    b.iter(|| {
+        let mut r = Reader::from_reader(src.as_ref());
+        let mut count = test::black_box(0);
+        r.trim_text(true);
+        match r.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) => {
+                for attr in e.attributes() {
+                    let _attr = attr.unwrap();
+                    count += 1;
+                }
+            }
+            something_else => panic!("Did not expect {:?}", something_else),
+        };
+
+        buf.clear();
+
+        assert_eq!(count, 100);
+    })
+}
+
+#[bench]
+fn bench_quick_xml_100_attributes_checked(b: &mut Bencher) {
+    let mut src = b"<path".to_vec();
+    for i in 0..100 {
+        src.extend_from_slice(format!(r#" a{}="{}""#, i, i).as_bytes());
+    }
+    src.extend_from_slice(b"/>");
+    let mut buf = Vec::with_capacity(src.len());
+    b.iter(|| {
+        let mut r = Reader::from_reader(src.as_ref());
+        let mut count = test::black_box(0);
+        r.trim_text(true);
+        match r.read_event(&mut buf) {
+            Ok(Event::Empty(ref e)) => {
+                for attr in e.attributes() {
+                    let _attr = attr.unwrap();
+                    count += 1;
+                }
+            }
+            something_else => panic!("Did not expect {:?}", something_else),
+        };
+
+        buf.clear();
+
+        assert_eq!(count, 100);
+    })
+}
+
+#[bench]
+fn bench_quick_xml_100_attributes_unchecked(b: &mut Bencher) {
+    let mut src = b"<path".to_vec();
+    for i in 0..100 {
+        src.extend_from_slice(format!(r#" a{}="{}""#, i, i).as_bytes());
+    }
+    src.extend_from_slice(b"/>");
+    let mut buf = Vec::with_capacity(src.len());
+    b.iter(|| {
+        let mut r = Reader::from_reader(src.as_ref());
+        let mut count = test::black_box(0);
+        r.trim_text(true);
+        match r.read_event(&mut buf) {
+            Ok(Event::Empty(ref e)) => {
+                for attr in e.attributes().with_checks(false) {
+                    let _attr = attr.unwrap();
+                    count += 1;
+                }
+            }
+            something_else => panic!("Did not expect {:?}", something_else),
+        };
+
+        buf.clear();
+
+        assert_eq!(count, 100);
+    })
+}
+
+#[bench]
+fn bench_quick_xml_100_attributes_unescape_and_decode(b: &mut Bencher) {
+    let mut src = b"<path".to_vec();
+    for i in 0..100 {
+        src.extend_from_slice(format!(r#" a{}="value{}""#, i, i).as_bytes());
+    }
+    src.extend_from_slice(b"/>");
+    let mut buf = Vec::with_capacity(src.len());
+    b.iter(|| {
+        let mut r = Reader::from_reader(src.as_ref());
+        let mut len = test::black_box(0);
+        r.trim_text(true);
+        match r.read_event(&mut buf) {
+            Ok(Event::Empty(ref e)) => {
+                for attr in e.attributes() {
+                    len += attr.unwrap().unescape_and_decode_value(&r).unwrap().len();
+                }
+            }
+            something_else => panic!("Did not expect {:?}", something_else),
+        };
+
+        buf.clear();
+
+        assert_eq!(len, 690);
+    })
+}
+
+#[bench]
+fn bench_quick_xml_100_attributes_decode_and_unescape(b: &mut Bencher) {
+    let mut src = b"<path".to_vec();
+    for i in 0..100 {
+        src.extend_from_slice(format!(r#" a{}="value{}""#, i, i).as_bytes());
+    }
+    src.extend_from_slice(b"/>");
+    let mut buf = Vec::with_capacity(src.len());
+    b.iter(|| {
+        let mut r = Reader::from_reader(src.as_ref());
+        let mut len = test::black_box(0);
+        r.trim_text(true);
+        match r.read_event(&mut buf) {
+            Ok(Event::Empty(ref e)) => {
+                for attr in e.attributes() {
+                    len += attr.unwrap().decode_and_unescape_value(&r).unwrap().len();
+                }
+            }
+            something_else => panic!("Did not expect {:?}", something_else),
+        };
+
+        buf.clear();
+
+        assert_eq!(len, 690);
+    })
+}
+
+#[bench]
+fn bench_quick_xml_few_attributes(b: &mut Bencher) {
+    let mut src = b"<root>".to_vec();
+    for i in 0..1000 {
+        src.extend_from_slice(format!(r#"<item id="{}" name="n" kind="k"/>"#, i).as_bytes());
+    }
+    src.extend_from_slice(b"</root>");
+    let mut buf = Vec::with_capacity(src.len());
+    b.iter(|| {
+        let mut r = Reader::from_reader(src.as_ref());
+        let mut count = test::black_box(0);
+        r.trim_text(true);
+        loop {
+            match r.read_event(&mut buf) {
+                Ok(Event::Empty(ref e)) => {
+                    for attr in e.attributes() {
+                        let _attr = attr.unwrap();
+                        count += 1;
+                    }
+                }
+                Ok(Event::Eof) => break,
+                _ => (),
+            }
+            buf.clear();
+        }
+        assert_eq!(count, 3000);
+    })
+}
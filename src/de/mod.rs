@@ -106,6 +106,29 @@
 //!     Ok(html)
 //! }
 //! ```
+//!
+//! # Attributes and text content
+//!
+//! Struct fields are read from the attributes of an element, or from its child elements with
+//! the same name. The text content of an element with attributes is read into the field named
+//! `$value`, which can be any type deserializable from a string:
+//!
+//! ```
+//! # extern crate serde;
+//! # extern crate quick_xml;
+//! use serde::Deserialize;
+//! use quick_xml::de::from_str;
+//!
+//! #[derive(Debug, Deserialize, PartialEq)]
+//! struct Price {
+//!     currency: String,
+//!     #[serde(rename = "$value")]
+//!     amount: f64,
+//! }
+//!
+//! let price: Price = from_str(r#"<price currency="USD">9.99</price>"#).unwrap();
+//! assert_eq!(price, Price { currency: "USD".to_string(), amount: 9.99 });
+//! ```
 
 mod escape;
 mod map;
@@ -595,6 +618,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn attributes_and_text() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Price {
+            currency: String,
+            #[serde(rename = "$value")]
+            amount: String,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Item {
+            name: String,
+            price: Price,
+        }
+
+        let item: Item = from_str(
+            r#"<item name="pen"><price currency="USD">9.99 &amp; up</price></item>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            item,
+            Item {
+                name: "pen".to_string(),
+                price: Price {
+                    currency: "USD".to_string(),
+                    amount: "9.99 & up".to_string(),
+                },
+            }
+        );
+    }
+
     #[test]
     fn deserialize_bytes() {
         #[derive(Debug, PartialEq)]
@@ -1,9 +1,14 @@
 //! Error management module
 
+use std::fmt;
+#[cfg(not(feature = "std"))]
+use std::prelude::*;
+
 /// The error type used by this crate.
 #[derive(Debug)]
 pub enum Error {
     /// IO error
+    #[cfg(feature = "std")]
     Io(::std::io::Error),
     /// Utf8 error
     Utf8(::std::str::Utf8Error),
@@ -19,7 +24,12 @@ pub enum Error {
     /// Unexpected token
     UnexpectedToken(String),
     /// Unexpected <!>
+    ///
+    /// Not returned by `Reader` anymore, which returns `Error::UnknownBang` instead.
     UnexpectedBang,
+    /// Element starting with `<!` which is not a comment, CDATA section or document type
+    /// declaration, with its content after the `<!`
+    UnknownBang(String),
     /// Text not found, expected `Event::Text`
     TextNotFound,
     /// `Event::XmlDecl` must start with *version* attribute
@@ -30,12 +40,42 @@ pub enum Error {
     NoEqAfterName(usize),
     /// Attribute value not quoted
     UnquotedValue(usize),
+    /// Attribute value not closed by its opening quote, with the position of that quote
+    UnclosedAttributeValue(usize),
     /// Duplicate attribute
     DuplicatedAttribute(usize, usize),
+    /// Element or attribute name not matching the XML `Name` production, with the position of
+    /// its first invalid byte
+    InvalidName(usize),
+    /// Text written by a `Writer` contains an unescaped `<`, with its position in the text
+    UnescapedText(usize),
+    /// CDATA written by a `Writer` contains `]]>`, with its position in the content
+    UnescapedCData(usize),
+    /// BOM or XML declaration written by a `Writer` after other content
+    MisplacedDeclaration,
+    /// Event rejected by a `Writer` in validation mode, with the reason
+    NotWellFormed(String),
+    /// More elements are opened than allowed by `Reader::max_depth`
+    MaxDepthExceeded(usize),
+    /// An element has more attributes than allowed by `Attributes::max_attributes`
+    TooManyAttributes(usize),
+    /// Character written by a `Writer` that cannot be represented in its encoding, where no
+    /// character reference is allowed
+    UnencodableCharacter(char),
+    /// Bytes rejected by `Reader::decode_strict` as malformed in the named encoding
+    UndecodableBytes(&'static str),
+    /// Text or CDATA outside the root element, with its position, if
+    /// `Reader::check_document_structure`
+    TextOutsideRoot(usize),
+    /// Element after the root element, with its position, if `Reader::check_document_structure`
+    MultipleRoots(usize),
+    /// Input without element, if `Reader::check_document_structure`
+    MissingRoot,
     /// Escape error
     EscapeError(::escape::EscapeError),
 }
 
+#[cfg(feature = "std")]
 impl From<::std::io::Error> for Error {
     /// Creates a new `Error::Io` from the given error
     #[inline]
@@ -57,9 +97,10 @@ impl From<::std::str::Utf8Error> for Error {
 /// [`Error`]: enum.Error.html
 pub type Result<T> = ::std::result::Result<T, Error>;
 
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
+            #[cfg(feature = "std")]
             Error::Io(e) => write!(f, "I/O error: {}", e),
             Error::Utf8(e) => write!(f, "UTF8 error: {}", e),
             Error::UnexpectedEof(e) => write!(f, "Unexpected EOF during reading {}.", e),
@@ -71,6 +112,11 @@ impl std::fmt::Display for Error {
                 f,
                 "Only Comment, CDATA and DOCTYPE nodes can start with a '!'"
             ),
+            Error::UnknownBang(e) => write!(
+                f,
+                "Only Comment, CDATA and DOCTYPE nodes can start with a '!', found '<!{}>'",
+                e
+            ),
             Error::TextNotFound => write!(f, "Cannot read text, expecting Event::Text"),
             Error::XmlDeclWithoutVersion(e) => write!(
                 f,
@@ -95,17 +141,57 @@ impl std::fmt::Display for Error {
                  Attribute value must start with a quote.",
                 e
             ),
+            Error::UnclosedAttributeValue(e) => write!(
+                f,
+                "error while parsing attribute at position {}: \
+                 Attribute value must be closed by the same quote.",
+                e
+            ),
             Error::DuplicatedAttribute(pos1, pos2) => write!(
                 f,
                 "error while parsing attribute at position {0}: \
                  Duplicate attribute at position {1} and {0}",
                 pos1, pos2
             ),
+            Error::InvalidName(e) => write!(f, "Invalid character in name at position {}", e),
+            Error::UnescapedText(e) => write!(
+                f,
+                "error while writing text: unescaped '<' at position {}",
+                e
+            ),
+            Error::UnescapedCData(e) => write!(
+                f,
+                "error while writing CDATA: section end ']]>' at position {}",
+                e
+            ),
+            Error::MisplacedDeclaration => write!(
+                f,
+                "BOM and XML declaration must be written before any other content"
+            ),
+            Error::NotWellFormed(e) => write!(f, "XML would not be well-formed: {}", e),
+            Error::MaxDepthExceeded(e) => {
+                write!(f, "Maximum nesting depth of {} elements exceeded", e)
+            }
+            Error::TooManyAttributes(e) => {
+                write!(f, "Maximum number of {} attributes exceeded", e)
+            }
+            Error::UnencodableCharacter(c) => {
+                write!(f, "Character {:?} cannot be represented in the output encoding", c)
+            }
+            Error::UndecodableBytes(e) => write!(f, "Input is not valid {} text", e),
+            Error::TextOutsideRoot(e) => {
+                write!(f, "Text outside the root element at position {}", e)
+            }
+            Error::MultipleRoots(e) => {
+                write!(f, "Element after the root element at position {}", e)
+            }
+            Error::MissingRoot => write!(f, "Document without root element"),
             Error::EscapeError(e) => write!(f, "{}", e),
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
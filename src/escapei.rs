@@ -2,6 +2,8 @@
 
 use memchr;
 use std::borrow::Cow;
+#[cfg(not(feature = "std"))]
+use std::prelude::*;
 
 #[derive(Debug)]
 pub enum EscapeError {
@@ -24,8 +26,8 @@ pub enum EscapeError {
     InvalidDecimal(char),
 }
 
-impl std::fmt::Display for EscapeError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl ::std::fmt::Display for EscapeError {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
         match self {
             EscapeError::EntityWithNull(e) => write!(
                 f,
@@ -52,6 +54,7 @@ impl std::fmt::Display for EscapeError {
     }
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for EscapeError {}
 
 // UTF-8 ranges and tags for encoding characters
@@ -66,13 +69,33 @@ const MAX_THREE_B: u32 = 0x10000;
 /// Escapes a `&[u8]` and replaces all xml special characters (<, >, &, ', ") with their
 /// corresponding xml escaped value.
 pub fn escape(raw: &[u8]) -> Cow<[u8]> {
-    fn to_escape(b: u8) -> bool {
-        match b {
-            b'<' | b'>' | b'\'' | b'&' | b'"' => true,
-            _ => false,
-        }
-    }
+    _escape(raw, |b| match b {
+        b'<' | b'>' | b'\'' | b'&' | b'"' => true,
+        _ => false,
+    })
+}
+
+/// Escapes a `&[u8]` to be used as an attribute value, replacing all xml special characters like
+/// `escape`, and the tab, line feed and carriage return characters with character references
+/// (`&#9;`, `&#10;` and `&#13;`), which are not normalized into spaces when the value is read.
+pub fn escape_attribute(raw: &[u8]) -> Cow<'_, [u8]> {
+    _escape(raw, |b| {
+        matches!(b, b'<' | b'>' | b'\'' | b'&' | b'"' | b'\t' | b'\n' | b'\r')
+    })
+}
+
+/// Escapes a `&[u8]` to be written between `quote`s, replacing <, >, &, the tab, line feed and
+/// carriage return characters (see `escape_attribute`) and only the `quote` character with
+/// their corresponding xml escaped value.
+pub fn escape_with_quote(raw: &[u8], quote: u8) -> Cow<'_, [u8]> {
+    _escape(raw, |b| match b {
+        b'<' | b'>' | b'&' | b'\t' | b'\n' | b'\r' => true,
+        b => b == quote,
+    })
+}
 
+/// Escapes all bytes of `raw` for which `to_escape` returns `true`
+fn _escape<F: Fn(u8) -> bool>(raw: &[u8], to_escape: F) -> Cow<'_, [u8]> {
     let mut escaped = None;
     let mut bytes = raw.iter();
     let mut pos = 0;
@@ -89,7 +112,10 @@ pub fn escape(raw: &[u8]) -> Cow<[u8]> {
             b'\'' => escaped.extend_from_slice(b"&apos;"),
             b'&' => escaped.extend_from_slice(b"&amp;"),
             b'"' => escaped.extend_from_slice(b"&quot;"),
-            _ => unreachable!("Only '<', '>','\', '&' and '\"' are escaped"),
+            b'\t' => escaped.extend_from_slice(b"&#9;"),
+            b'\n' => escaped.extend_from_slice(b"&#10;"),
+            b'\r' => escaped.extend_from_slice(b"&#13;"),
+            _ => unreachable!("Only '<', '>','\', '&', '\"' and whitespaces are escaped"),
         }
         pos = new_pos + 1;
     }
@@ -107,6 +133,39 @@ pub fn escape(raw: &[u8]) -> Cow<[u8]> {
 /// Unescape a `&[u8]` and replaces all xml escaped characters ('&...;') into their corresponding
 /// value
 pub fn unescape(raw: &[u8]) -> Result<Cow<[u8]>, EscapeError> {
+    _unescape(raw, &|_| None, false)
+}
+
+/// Unescapes a `&[u8]` like [`unescape`], replacing the other entities ('&name;') with the value
+/// returned by `resolve` for their name
+///
+/// The predefined entities and character references are replaced before calling `resolve`. The
+/// returned values are inserted as they are, without being unescaped. Entities for which
+/// `resolve` returns `None` produce an `EscapeError::UnrecognizedSymbol`.
+///
+/// [`unescape`]: fn.unescape.html
+pub fn unescape_with<'r, 'e, F>(raw: &'r [u8], resolve: F) -> Result<Cow<'r, [u8]>, EscapeError>
+where
+    F: Fn(&[u8]) -> Option<&'e [u8]>,
+{
+    _unescape(raw, &resolve, false)
+}
+
+/// Unescapes a `&[u8]` like [`unescape`], but keeps unrecognized entities ('&name;') as they are
+/// instead of returning `EscapeError::UnrecognizedSymbol`
+///
+/// [`unescape`]: fn.unescape.html
+pub fn unescape_lenient(raw: &[u8]) -> Result<Cow<'_, [u8]>, EscapeError> {
+    _unescape(raw, &|_| None, true)
+}
+
+/// Unescapes `raw`, replacing the unrecognized entities with their value from `resolve`, or
+/// keeping them if `lenient`
+fn _unescape<'r, 'e>(
+    raw: &'r [u8],
+    resolve: &dyn Fn(&[u8]) -> Option<&'e [u8]>,
+    lenient: bool,
+) -> Result<Cow<'r, [u8]>, EscapeError> {
     let mut unescaped = None;
     let mut last_end = 0;
     let mut iter = memchr::memchr2_iter(b'&', b';', raw);
@@ -1576,6 +1635,14 @@ pub fn unescape(raw: &[u8]) -> Result<Cow<[u8]>, EscapeError> {
                             parse_hexadecimal(&bytes[2..])
                         } else if bytes.starts_with(b"#") {
                             parse_decimal(&bytes[1..])
+                        } else if let Some(value) = resolve(bytes) {
+                            unescaped.extend_from_slice(value);
+                            last_end = end + 1;
+                            continue;
+                        } else if lenient {
+                            unescaped.extend_from_slice(&raw[start..=end]);
+                            last_end = end + 1;
+                            continue;
                         } else {
                             Err(EscapeError::UnrecognizedSymbol(
                                     start + 1..end,
@@ -1664,6 +1731,57 @@ fn test_unescape() {
     assert_eq!(&*unescape(b"&#48;").unwrap(), b"0");
 }
 
+#[test]
+fn test_escape_with_quote() {
+    assert_eq!(&*escape_with_quote(b"test", b'"'), b"test");
+    assert_eq!(&*escape_with_quote(b"<'a\"&>", b'"'), b"&lt;'a&quot;&amp;&gt;");
+    assert_eq!(&*escape_with_quote(b"<'a\"&>", b'\''), b"&lt;&apos;a\"&amp;&gt;");
+    assert_eq!(&*escape_with_quote(b"a\tb\nc\rd", b'"'), b"a&#9;b&#10;c&#13;d");
+}
+
+#[test]
+fn test_escape_attribute() {
+    assert_eq!(&*escape_attribute(b"a b"), b"a b");
+    assert_eq!(&*escape_attribute(b"a\tb"), b"a&#9;b");
+    assert_eq!(&*escape_attribute(b"a\nb"), b"a&#10;b");
+    assert_eq!(&*escape_attribute(b"a\r\nb"), b"a&#13;&#10;b");
+    assert_eq!(&*escape_attribute(b"<'\"&>"), b"&lt;&apos;&quot;&amp;&gt;");
+    // the text escape keeps whitespace characters
+    assert_eq!(&*escape(b"a\tb\nc\rd"), b"a\tb\nc\rd");
+}
+
+#[test]
+fn test_unescape_with() {
+    let resolve = |entity: &[u8]| match entity {
+        b"me" => Some(&b"Jane &amp; co"[..]),
+        b"lt" => Some(&b"ignored"[..]),
+        _ => None,
+    };
+    assert_eq!(&*unescape_with(b"test", resolve).unwrap(), b"test");
+    assert_eq!(
+        &*unescape_with(b"&lt;&me;&#x41;&#66;&amp;", resolve).unwrap(),
+        b"<Jane &amp; coAB&"
+    );
+    match unescape_with(b"&me;&unknown;", resolve) {
+        Err(EscapeError::UnrecognizedSymbol(range, name)) => {
+            assert_eq!(range, 5..12);
+            assert_eq!(name.unwrap(), "unknown");
+        }
+        e => panic!("expecting UnrecognizedSymbol, found {:?}", e),
+    }
+}
+
+#[test]
+fn test_unescape_lenient() {
+    assert_eq!(&*unescape_lenient(b"test").unwrap(), b"test");
+    assert_eq!(
+        &*unescape_lenient(b"&foo;&lt;&unknown;&#x41;").unwrap(),
+        b"&foo;<&unknown;A"
+    );
+    assert!(unescape_lenient(b"&#xZ;").is_err());
+    assert!(unescape_lenient(b"&lt").is_err());
+}
+
 #[test]
 fn test_escape() {
     assert_eq!(&*escape(b"test"), b"test");
@@ -3,11 +3,21 @@
 //! Provides an iterator over attributes key/value pairs
 
 use errors::{Error, Result};
-use escape::{escape, unescape};
+use escape::{escape_attribute, unescape, unescape_with};
+use memchr;
+use name::{invalid_name_position, QName};
 use reader::{is_whitespace, Reader};
 use std::borrow::Cow;
+#[cfg(not(feature = "std"))]
+use std::collections::btree_map::{BTreeMap as HashMap, Entry};
+#[cfg(feature = "std")]
+use std::collections::hash_map::{Entry, HashMap};
+use std::fmt;
 use std::io::BufRead;
-use std::ops::Range;
+use std::ops::{Deref, Range};
+#[cfg(not(feature = "std"))]
+use std::prelude::*;
+use std::str::from_utf8;
 
 /// Iterator over XML attributes.
 ///
@@ -23,11 +33,21 @@ pub struct Attributes<'a> {
     pub(crate) position: usize,
     /// if true, checks for duplicate names
     with_checks: bool,
+    /// if true, checks that names match the XML `Name` production
+    check_names: bool,
+    /// if true, stops after the first error instead of skipping the malformed attribute
+    stop_on_error: bool,
     /// allows attribute without quote or `=`
     html: bool,
-    /// if `with_checks`, contains the ranges corresponding to the
-    /// attribute names already parsed in this `Element`
-    consumed: Vec<Range<usize>>,
+    /// if `with_checks`, contains the attribute names already parsed in this `Element`, with
+    /// their position
+    consumed: ConsumedKeys<'a>,
+    /// position of `bytes` in the source document, added to the positions reported in errors
+    offset: usize,
+    /// maximum number of attributes to yield before returning an error
+    max_attributes: usize,
+    /// number of attributes yielded
+    count: usize,
 }
 
 impl<'a> Attributes<'a> {
@@ -38,7 +58,12 @@ impl<'a> Attributes<'a> {
             position: pos,
             html: false,
             with_checks: true,
-            consumed: Vec::new(),
+            check_names: false,
+            stop_on_error: true,
+            consumed: ConsumedKeys::default(),
+            offset: 0,
+            max_attributes: usize::MAX,
+            count: 0,
         }
     }
 
@@ -49,20 +74,236 @@ impl<'a> Attributes<'a> {
             position: pos,
             html: true,
             with_checks: true,
-            consumed: Vec::new(),
+            check_names: false,
+            stop_on_error: true,
+            consumed: ConsumedKeys::default(),
+            offset: 0,
+            max_attributes: usize::MAX,
+            count: 0,
         }
     }
 
     /// Changes whether attributes should be checked for uniqueness.
     ///
     /// The XML specification requires attribute keys in the same element to be unique. This check
-    /// can be disabled to improve performance slightly.
+    /// can be disabled to improve performance slightly. Disabling checks also makes the iterator
+    /// stop silently on a value without closing quote instead of returning
+    /// `Error::UnclosedAttributeValue`.
     ///
     /// (`true` by default)
     pub fn with_checks(&mut self, val: bool) -> &mut Attributes<'a> {
         self.with_checks = val;
         self
     }
+
+    /// Changes whether attribute keys should be validated.
+    ///
+    /// When set to `true`, keys are checked against the `Name` production of the XML
+    /// specification, and an invalid one is reported with `Error::InvalidName` and the position
+    /// of its first invalid byte. Names of HTML attributes are not checked. As keys are checked
+    /// as UTF-8, only enable it for documents in UTF-8 (or ASCII-only names). Keys are checked
+    /// even if the other checks are disabled with [`with_checks`].
+    ///
+    /// (`false` by default)
+    ///
+    /// [`with_checks`]: #method.with_checks
+    pub fn check_names(&mut self, val: bool) -> &mut Attributes<'a> {
+        self.check_names = val;
+        self
+    }
+
+    /// Changes whether the iterator stops after the first malformed attribute.
+    ///
+    /// When set to `false`, the iterator returns the error, then skips to the next
+    /// whitespace-delimited token and continues with the following attributes, which allows to
+    /// tolerate errors (e.g. unquoted values in scraped markup) without losing the valid
+    /// attributes. A duplicated attribute is skipped with its value. The iterator still stops on
+    /// a value without closing quote, as the rest of the tag is part of it, and on
+    /// `Error::TooManyAttributes`.
+    ///
+    /// (`true` by default)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use quick_xml::events::attributes::Attributes;
+    ///
+    /// let mut attributes = Attributes::new(b"tag a='1' b=2 c='3'", 3);
+    /// attributes.stop_on_error(false);
+    /// let keys: Vec<_> = attributes.map(|a| a.map(|a| a.key)).collect();
+    /// assert_eq!(keys[0].as_ref().unwrap(), b"a");
+    /// assert!(keys[1].is_err());
+    /// assert_eq!(keys[2].as_ref().unwrap(), b"c");
+    /// ```
+    pub fn stop_on_error(&mut self, val: bool) -> &mut Attributes<'a> {
+        self.stop_on_error = val;
+        self
+    }
+
+    /// Sets the position of the buffer in the source document.
+    ///
+    /// Positions reported in attribute errors are relative to the buffer plus this offset, which
+    /// allows them to point into the whole document rather than into the element alone. The
+    /// iterators returned by [`BytesStart::attributes`] for the events of a `Reader` already
+    /// have the offset of the tag, so their positions are comparable to
+    /// `Reader::buffer_position`.
+    ///
+    /// (`0` by default)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use quick_xml::events::attributes::Attributes;
+    /// use quick_xml::Error;
+    ///
+    /// // a tag starting at position 20 of a document
+    /// let mut attributes = Attributes::new(b"tag a='1' a='2'", 3);
+    /// attributes.with_offset(20);
+    /// attributes.next();
+    /// match attributes.next() {
+    ///     Some(Err(Error::DuplicatedAttribute(30, 24))) => (),
+    ///     e => panic!("expecting DuplicatedAttribute, found {:?}", e),
+    /// }
+    /// ```
+    ///
+    /// [`BytesStart::attributes`]: ../struct.BytesStart.html#method.attributes
+    pub fn with_offset(&mut self, offset: usize) -> &mut Attributes<'a> {
+        self.offset = offset;
+        self
+    }
+
+    /// Changes the maximum number of attributes yielded by this iterator.
+    ///
+    /// Once `max` attributes have been yielded, the iterator returns
+    /// `Error::TooManyAttributes` instead of the next attribute. As the duplicate check keeps the
+    /// previous keys, this bounds the memory spent on elements with a huge number of attributes:
+    /// `256` is a safe value for most documents.
+    ///
+    /// (unlimited by default)
+    pub fn max_attributes(&mut self, max: usize) -> &mut Attributes<'a> {
+        self.max_attributes = max;
+        self
+    }
+
+    /// Converts this iterator into one yielding each attribute with the position of its key and
+    /// value in the buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use quick_xml::events::attributes::Attributes;
+    ///
+    /// let buf = b"tag a='1' b = \"22\"";
+    /// let spans: Vec<_> = Attributes::new(buf, 3)
+    ///     .with_positions()
+    ///     .map(|a| a.unwrap().1)
+    ///     .collect();
+    /// assert_eq!(&buf[spans[1].key.clone()], b"b");
+    /// assert_eq!(&buf[spans[1].value.clone()], b"22");
+    /// ```
+    pub fn with_positions(self) -> AttributesWithPositions<'a> {
+        AttributesWithPositions(self)
+    }
+}
+
+/// The position of the parts of an attribute in the buffer of an [`Attributes`] iterator.
+///
+/// The positions do not include the offset set with [`with_offset`], so they can be used to
+/// slice the element bytes. They are the same whether `with_checks` is set or not.
+///
+/// [`Attributes`]: struct.Attributes.html
+/// [`with_offset`]: struct.Attributes.html#method.with_offset
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AttributeSpans {
+    /// The range of the key
+    pub key: Range<usize>,
+    /// The range of the value, without quotes. It is empty, at the end of the key, for an HTML
+    /// attribute without value.
+    pub value: Range<usize>,
+}
+
+/// Iterator over XML attributes with their positions, created by [`Attributes::with_positions`].
+///
+/// [`Attributes::with_positions`]: struct.Attributes.html#method.with_positions
+#[derive(Clone)]
+pub struct AttributesWithPositions<'a>(Attributes<'a>);
+
+impl<'a> Iterator for AttributesWithPositions<'a> {
+    type Item = Result<(Attribute<'a>, AttributeSpans)>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next_with_spans()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+/// Number of keys checked for duplicates by linear search, before using a `HashMap`
+const INLINE_KEYS: usize = 8;
+
+/// The keys of the attributes already parsed by an `Attributes` iterator, with their position.
+///
+/// Most elements have a few attributes, which are faster to compare than to hash: only the
+/// following ones are stored in a `HashMap`, allocated once needed.
+#[derive(Clone, Default)]
+struct ConsumedKeys<'a> {
+    /// the first `INLINE_KEYS` keys
+    inline: [(&'a [u8], usize); INLINE_KEYS],
+    /// number of keys in `inline`
+    inline_len: usize,
+    /// the keys following the `inline` ones
+    spilled: Option<HashMap<&'a [u8], usize>>,
+}
+
+impl<'a> ConsumedKeys<'a> {
+    /// Adds a key at `position`, or returns the position of the same key if already added
+    fn insert(&mut self, key: &'a [u8], position: usize) -> Option<usize> {
+        let inline = &self.inline[..self.inline_len];
+        if let Some(&(_, start)) = inline.iter().find(|&&(k, _)| k == key) {
+            return Some(start);
+        }
+        if self.inline_len < INLINE_KEYS {
+            self.inline[self.inline_len] = (key, position);
+            self.inline_len += 1;
+            return None;
+        }
+        match self.spilled.get_or_insert_with(HashMap::new).entry(key) {
+            Entry::Occupied(e) => Some(*e.get()),
+            Entry::Vacant(e) => {
+                e.insert(position);
+                None
+            }
+        }
+    }
+}
+
+/// The quote character used to delimit attribute values when writing them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuoteStyle {
+    /// Always use `"`, escaping it as `&quot;` in values
+    Double,
+    /// Always use `'`, escaping it as `&apos;` in values
+    Single,
+    /// Use `'` if the value contains `"` but no `'`, and `"` otherwise
+    Auto,
+}
+
+impl QuoteStyle {
+    /// Returns the quote character to use for `value`
+    pub(crate) fn quote_for(self, value: &[u8]) -> u8 {
+        match self {
+            QuoteStyle::Double => b'"',
+            QuoteStyle::Single => b'\'',
+            QuoteStyle::Auto => {
+                if value.contains(&b'"') && !value.contains(&b'\'') {
+                    b'\''
+                } else {
+                    b'"'
+                }
+            }
+        }
+    }
 }
 
 /// A struct representing a key/value XML attribute.
@@ -86,6 +327,86 @@ pub struct Attribute<'a> {
 }
 
 impl<'a> Attribute<'a> {
+    /// Returns the key as a [`QName`], to be compared with other qualified names.
+    ///
+    /// [`QName`]: ../../name/struct.QName.html
+    #[inline]
+    pub fn qname(&self) -> QName<'a> {
+        QName(self.key)
+    }
+
+    /// Gets the prefix of the key, without the `:`, if any, like [`QName::prefix`].
+    ///
+    /// [`QName::prefix`]: ../../name/struct.QName.html#method.prefix
+    #[inline]
+    pub fn prefix(&self) -> Option<&'a [u8]> {
+        self.qname().prefix()
+    }
+
+    /// Gets the local name of the key, excluding the prefix and the `:`, like
+    /// [`QName::local_name`].
+    ///
+    /// [`QName::local_name`]: ../../name/struct.QName.html#method.local_name
+    #[inline]
+    pub fn local_name(&self) -> &'a [u8] {
+        self.qname().local_name()
+    }
+
+    /// Converts this attribute into an [`OwnedAttribute`], copying the key and the value if
+    /// it is borrowed.
+    ///
+    /// [`OwnedAttribute`]: struct.OwnedAttribute.html
+    pub fn into_owned(self) -> OwnedAttribute {
+        OwnedAttribute {
+            key: self.key.to_vec(),
+            value: self.value.into_owned(),
+        }
+    }
+
+    /// Returns the key as a `&str`, without decoding it with the reader encoding.
+    ///
+    /// Returns `Error::Utf8` if the key is not valid UTF-8.
+    #[inline]
+    pub fn key_str(&self) -> Result<&'a str> {
+        Ok(from_utf8(self.key)?)
+    }
+
+    /// Returns the raw value as a `&str`, without unescaping nor decoding it.
+    ///
+    /// Returns `Error::Utf8` if the value is not valid UTF-8.
+    #[inline]
+    pub fn value_str(&self) -> Result<&str> {
+        Ok(from_utf8(&self.value)?)
+    }
+
+    /// Returns the unescaped value as a string, like [`unescaped_value`] but without the UTF-8
+    /// conversion, and without decoding it with the reader encoding.
+    ///
+    /// Returns `Error::Utf8` if the unescaped value is not valid UTF-8. This will allocate if the
+    /// value contains any escape sequences.
+    ///
+    /// [`unescaped_value`]: #method.unescaped_value
+    pub fn unescaped_value_str(&self) -> Result<Cow<'_, str>> {
+        match self.unescaped_value()? {
+            Cow::Borrowed(value) => Ok(Cow::Borrowed(from_utf8(value)?)),
+            Cow::Owned(value) => String::from_utf8(value)
+                .map(Cow::Owned)
+                .map_err(|e| Error::Utf8(e.utf8_error())),
+        }
+    }
+
+    /// Returns `true` if the raw value contains a `&`, i.e. an escape sequence to be replaced by
+    /// [`unescaped_value`].
+    ///
+    /// When `false`, the unescaped value is the raw value, so unescaping can be skipped. The
+    /// escape sequences are not validated.
+    ///
+    /// [`unescaped_value`]: #method.unescaped_value
+    #[inline]
+    pub fn is_escaped(&self) -> bool {
+        memchr::memchr(b'&', &self.value).is_some()
+    }
+
     /// Returns the unescaped value.
     ///
     /// This is normally the value you are interested in. Escape sequences such as `&gt;` are
@@ -96,6 +417,61 @@ impl<'a> Attribute<'a> {
         unescape(&*self.value).map_err(Error::EscapeError)
     }
 
+    /// Returns the unescaped value like [`unescaped_value`], replacing the other entities with
+    /// the value returned by `resolve` for their name.
+    ///
+    /// The predefined entities and character references are replaced first. The values returned
+    /// by `resolve` are inserted as they are, without being unescaped. An entity for which
+    /// `resolve` returns `None` is an `Error::EscapeError` naming it.
+    ///
+    /// [`unescaped_value`]: #method.unescaped_value
+    pub fn unescaped_value_with<'e, F>(&self, resolve: F) -> Result<Cow<'_, [u8]>>
+    where
+        F: Fn(&[u8]) -> Option<&'e [u8]>,
+    {
+        unescape_with(&self.value, resolve).map_err(Error::EscapeError)
+    }
+
+    /// Returns the unescaped value, normalized as required by the [XML specification].
+    ///
+    /// Each whitespace character (tab, line feed, carriage return or a `\r\n` pair) is replaced by
+    /// a single space, then escape sequences are replaced with their unescaped equivalents.
+    /// Whitespace produced by character references such as `&#xA;` is kept as is.
+    ///
+    /// This is the normalization applied to CDATA attributes. As attribute types are declared in
+    /// the DTD, which is not parsed, the additional normalization of other attribute types
+    /// (trimming and collapsing sequences of spaces) is *not* applied.
+    ///
+    /// This will allocate if the value contains any whitespace other than spaces or any escape
+    /// sequences.
+    ///
+    /// [XML specification]: https://www.w3.org/TR/xml/#AVNormalize
+    pub fn normalized_value(&self) -> Result<Cow<'_, [u8]>> {
+        normalize_and_unescape(&self.value)
+    }
+
+    /// Decodes the value with the reader encoding, then normalizes and unescapes it like
+    /// [`normalized_value`].
+    ///
+    /// [`normalized_value`]: #method.normalized_value
+    #[cfg(feature = "encoding")]
+    pub fn normalize_and_decode_value<B: BufRead>(&self, reader: &Reader<B>) -> Result<String> {
+        let decoded = reader.decode(&self.value);
+        let normalized = normalize_and_unescape(decoded.as_bytes())?;
+        String::from_utf8(normalized.into_owned()).map_err(|e| Error::Utf8(e.utf8_error()))
+    }
+
+    /// Decodes the value with the reader encoding, then normalizes and unescapes it like
+    /// [`normalized_value`].
+    ///
+    /// [`normalized_value`]: #method.normalized_value
+    #[cfg(not(feature = "encoding"))]
+    pub fn normalize_and_decode_value<B: BufRead>(&self, reader: &Reader<B>) -> Result<String> {
+        let decoded = reader.decode(&self.value)?;
+        let normalized = normalize_and_unescape(decoded.as_bytes())?;
+        String::from_utf8(normalized.into_owned()).map_err(|e| Error::Utf8(e.utf8_error()))
+    }
+
     /// Decode then unescapes the value
     ///
     /// This allocates a `String` in all cases. For performance reasons it might be a better idea to
@@ -130,6 +506,80 @@ impl<'a> Attribute<'a> {
         String::from_utf8(unescaped.into_owned()).map_err(|e| Error::Utf8(e.utf8_error()))
     }
 
+    /// Decodes then unescapes the value, like [`unescape_and_decode_value`], but without
+    /// allocating when the value does not need to be changed.
+    ///
+    /// The value is borrowed if it contains no escape sequences and is valid UTF-8 in the reader
+    /// encoding. For input known to be UTF-8, [`unescaped_value_str`] does not need a reader.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::borrow::Cow;
+    /// use quick_xml::events::attributes::Attribute;
+    /// use quick_xml::Reader;
+    ///
+    /// let reader = Reader::from_str("");
+    /// let plain = Attribute::from(("a", "plain"));
+    /// assert!(matches!(plain.decode_and_unescape_value(&reader)?, Cow::Borrowed("plain")));
+    /// let escaped = Attribute::from(("a", "a & b"));
+    /// assert_eq!(escaped.decode_and_unescape_value(&reader)?, "a & b");
+    /// # Ok::<(), quick_xml::Error>(())
+    /// ```
+    ///
+    /// [`unescape_and_decode_value`]: #method.unescape_and_decode_value
+    /// [`unescaped_value_str`]: #method.unescaped_value_str
+    #[cfg(feature = "encoding")]
+    pub fn decode_and_unescape_value<B: BufRead>(
+        &self,
+        reader: &Reader<B>,
+    ) -> Result<Cow<'_, str>> {
+        let decoded = reader.decode(&self.value);
+        if let Cow::Owned(unescaped) = unescape(decoded.as_bytes()).map_err(Error::EscapeError)? {
+            return String::from_utf8(unescaped)
+                .map(Cow::Owned)
+                .map_err(|e| Error::Utf8(e.utf8_error()));
+        }
+        Ok(decoded)
+    }
+
+    /// Decodes then unescapes the value, like [`unescape_and_decode_value`], but without
+    /// allocating when the value does not need to be changed.
+    ///
+    /// The value is borrowed if it contains no escape sequences. For input known to be UTF-8,
+    /// [`unescaped_value_str`] does not need a reader.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::borrow::Cow;
+    /// use quick_xml::events::attributes::Attribute;
+    /// use quick_xml::Reader;
+    ///
+    /// let reader = Reader::from_str("");
+    /// let plain = Attribute::from(("a", "plain"));
+    /// assert!(matches!(plain.decode_and_unescape_value(&reader)?, Cow::Borrowed("plain")));
+    /// let escaped = Attribute::from(("a", "a & b"));
+    /// assert_eq!(escaped.decode_and_unescape_value(&reader)?, "a & b");
+    /// # Ok::<(), quick_xml::Error>(())
+    /// ```
+    ///
+    /// [`unescape_and_decode_value`]: #method.unescape_and_decode_value
+    /// [`unescaped_value_str`]: #method.unescaped_value_str
+    #[cfg(not(feature = "encoding"))]
+    pub fn decode_and_unescape_value<B: BufRead>(
+        &self,
+        reader: &Reader<B>,
+    ) -> Result<Cow<'_, str>> {
+        let decoded = reader.decode(&self.value)?;
+        match unescape(decoded.as_bytes()).map_err(Error::EscapeError)? {
+            Cow::Borrowed(_) => Ok(Cow::Borrowed(decoded)),
+            Cow::Owned(unescaped) => String::from_utf8(unescaped)
+                .map(Cow::Owned)
+                .map_err(|e| Error::Utf8(e.utf8_error())),
+        }
+    }
+
     /// helper method to unescape then decode self using the reader encoding
     /// but without BOM (Byte order mark)
     ///
@@ -165,8 +615,43 @@ impl<'a> Attribute<'a> {
     }
 }
 
-impl<'a> std::fmt::Debug for Attribute<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+/// Normalizes the whitespace characters of an attribute value, then unescapes it.
+fn normalize_and_unescape(raw: &[u8]) -> Result<Cow<'_, [u8]>> {
+    match normalize_whitespace(raw) {
+        Cow::Borrowed(value) => unescape(value).map_err(Error::EscapeError),
+        Cow::Owned(value) => {
+            let unescaped = unescape(&value).map_err(Error::EscapeError)?;
+            Ok(Cow::Owned(unescaped.into_owned()))
+        }
+    }
+}
+
+/// Replaces every whitespace character of an attribute value, or `\r\n` pair, by a space.
+fn normalize_whitespace(raw: &[u8]) -> Cow<'_, [u8]> {
+    let first = match raw.iter().position(|&b| b != b' ' && is_whitespace(b)) {
+        Some(i) => i,
+        None => return Cow::Borrowed(raw),
+    };
+    let mut normalized = Vec::with_capacity(raw.len());
+    normalized.extend_from_slice(&raw[..first]);
+    let mut bytes = raw[first..].iter().peekable();
+    while let Some(&b) = bytes.next() {
+        match b {
+            b'\r' => {
+                if bytes.peek() == Some(&&b'\n') {
+                    bytes.next();
+                }
+                normalized.push(b' ');
+            }
+            b'\n' | b'\t' => normalized.push(b' '),
+            b => normalized.push(b),
+        }
+    }
+    Cow::Owned(normalized)
+}
+
+impl<'a> fmt::Debug for Attribute<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use crate::utils::write_byte_string;
 
         write!(f, "Attribute {{ key: ")?;
@@ -177,6 +662,13 @@ impl<'a> std::fmt::Debug for Attribute<'a> {
     }
 }
 
+impl<'a> From<&'a OwnedAttribute> for Attribute<'a> {
+    /// Borrows an owned attribute, e.g. to push it to a `BytesStart`.
+    fn from(val: &'a OwnedAttribute) -> Attribute<'a> {
+        val.borrow()
+    }
+}
+
 impl<'a> From<(&'a [u8], &'a [u8])> for Attribute<'a> {
     /// Creates new attribute from raw bytes.
     /// Does not apply any transformation to both key and value.
@@ -199,7 +691,8 @@ impl<'a> From<(&'a [u8], &'a [u8])> for Attribute<'a> {
 
 impl<'a> From<(&'a str, &'a str)> for Attribute<'a> {
     /// Creates new attribute from text representation.
-    /// Key is stored as-is, but the value will be escaped.
+    /// Key is stored as-is, but the value will be escaped, including tabs and line breaks (see
+    /// [`escape_attribute`]).
     ///
     /// # Examples
     ///
@@ -208,18 +701,80 @@ impl<'a> From<(&'a str, &'a str)> for Attribute<'a> {
     ///
     /// let features = Attribute::from(("features", "Bells & whistles"));
     /// assert_eq!(features.value, "Bells &amp; whistles".as_bytes());
+    /// let lines = Attribute::from(("lines", "a\nb"));
+    /// assert_eq!(lines.value, "a&#10;b".as_bytes());
     /// ```
+    ///
+    /// [`escape_attribute`]: ../../escape/fn.escape_attribute.html
     fn from(val: (&'a str, &'a str)) -> Attribute<'a> {
         Attribute {
             key: val.0.as_bytes(),
-            value: escape(val.1.as_bytes()),
+            value: escape_attribute(val.1.as_bytes()),
         }
     }
 }
 
-impl<'a> Iterator for Attributes<'a> {
-    type Item = Result<Attribute<'a>>;
-    fn next(&mut self) -> Option<Self::Item> {
+/// An attribute owning its key and value, which can be stored independently of the element it
+/// was read from.
+///
+/// # Examples
+///
+/// ```
+/// use quick_xml::events::BytesStart;
+/// use quick_xml::events::attributes::OwnedAttribute;
+///
+/// let owned: Vec<OwnedAttribute> = BytesStart::borrowed(b"a x='1' y=\"&lt;\"", 1)
+///     .attributes()
+///     .map(|a| a.map(OwnedAttribute::from))
+///     .collect::<Result<_, _>>()?;
+/// assert_eq!(owned[1].value, b"&lt;");
+///
+/// let mut start = BytesStart::borrowed_name(b"b");
+/// start.extend_attributes(&owned);
+/// assert_eq!(&*start, &b"b x=\"1\" y=\"&lt;\""[..]);
+/// # Ok::<(), quick_xml::Error>(())
+/// ```
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct OwnedAttribute {
+    /// The key of the attribute.
+    pub key: Vec<u8>,
+    /// The raw value of the attribute, possibly containing escape sequences.
+    pub value: Vec<u8>,
+}
+
+impl OwnedAttribute {
+    /// Returns an [`Attribute`] borrowing the key and the value of this attribute.
+    ///
+    /// [`Attribute`]: struct.Attribute.html
+    pub fn borrow(&self) -> Attribute<'_> {
+        Attribute {
+            key: &self.key,
+            value: Cow::Borrowed(&self.value),
+        }
+    }
+}
+
+impl<'a> From<Attribute<'a>> for OwnedAttribute {
+    fn from(val: Attribute<'a>) -> OwnedAttribute {
+        val.into_owned()
+    }
+}
+
+impl fmt::Debug for OwnedAttribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use crate::utils::write_byte_string;
+
+        write!(f, "OwnedAttribute {{ key: ")?;
+        write_byte_string(f, &self.key)?;
+        write!(f, ", value: ")?;
+        write_byte_string(f, &self.value)?;
+        write!(f, " }}")
+    }
+}
+
+impl<'a> Attributes<'a> {
+    /// Parses the next attribute, with the position of its key and value
+    fn next_with_spans(&mut self) -> Option<Result<(Attribute<'a>, AttributeSpans)>> {
         let len = self.bytes.len();
 
         macro_rules! err {
@@ -227,104 +782,255 @@ impl<'a> Iterator for Attributes<'a> {
                 self.position = len;
                 return Some(Err($err.into()));
             }};
+            // resumes with the token following the whitespace after `$resume`
+            ($err:expr, $resume:expr) => {{
+                self.position = if self.stop_on_error { len } else { $resume };
+                return Some(Err($err.into()));
+            }};
         }
 
         macro_rules! attr {
             ($key:expr) => {{
                 self.position = len;
                 if self.html {
-                    attr!($key, 0..0)
+                    let key = $key;
+                    attr!(key.clone(), key.end..key.end)
                 } else {
                     return None;
+                }
+            }};
+            ($key:expr, $val:expr) => {{
+                let key = $key;
+                let value = $val;
+                // checked once the value is parsed, to skip it with the attribute
+                let name = &self.bytes[key.clone()];
+                if self.check_names && !self.html {
+                    if let Some(i) = invalid_name_position(name) {
+                        let position = self.position;
+                        err!(Error::InvalidName(self.offset + key.start + i), position);
+                    }
+                }
+                if self.with_checks {
+                    if let Some(start) = self.consumed.insert(name, key.start) {
+                        let position = self.position;
+                        err!(
+                            Error::DuplicatedAttribute(
+                                self.offset + key.start,
+                                self.offset + start
+                            ),
+                            position
+                        );
+                    }
+                }
+                self.count += 1;
+                let attribute = Attribute {
+                    key: &self.bytes[key.clone()],
+                    value: Cow::Borrowed(&self.bytes[value.clone()]),
                 };
+                return Some(Ok((attribute, AttributeSpans { key, value })));
             }};
-            ($key:expr, $val:expr) => {
-                return Some(Ok(Attribute {
-                    key: &self.bytes[$key],
-                    value: Cow::Borrowed(&self.bytes[$val]),
-                }));
-            };
         }
 
         if len <= self.position {
             return None;
         }
 
-        let mut bytes = self.bytes.iter().enumerate().skip(self.position);
-
-        // key starts after the whitespace
-        let start_key = match bytes
-            .by_ref()
-            .skip_while(|&(_, &b)| !is_whitespace(b))
-            .find(|&(_, &b)| !is_whitespace(b))
-        {
-            Some((i, _)) => i,
-            None => attr!(self.position..len),
-        };
-
-        // key ends with either whitespace or =
-        let end_key = match bytes
-            .by_ref()
-            .find(|&(_, &b)| b == b'=' || is_whitespace(b))
-        {
-            Some((i, &b'=')) => i,
-            Some((i, &b'\'')) | Some((i, &b'"')) if self.with_checks => {
-                err!(Error::NameWithQuote(i));
-            }
-            Some((i, _)) => {
+        // the key, `=`, quote and value are found in a single traversal of the bytes
+        let mut state = ParseState::Token;
+        let mut i = self.position;
+        while i < len {
+            let b = self.bytes[i];
+            match state {
+                ParseState::Token => {
+                    if is_whitespace(b) {
+                        // key starts after the whitespace
+                        match find_non_whitespace(&self.bytes[i + 1..]) {
+                            Some(j) => i += 1 + j,
+                            None => attr!(self.position..len),
+                        }
+                        if self.count == self.max_attributes {
+                            err!(Error::TooManyAttributes(self.max_attributes));
+                        }
+                        state = ParseState::Key(i);
+                    }
+                }
+                // key ends with either whitespace or =
+                ParseState::Key(start) => {
+                    if b == b'=' {
+                        state = ParseState::Value(start, i);
+                    } else if is_whitespace(b) {
+                        state = ParseState::Eq(start, i);
+                    }
+                }
                 // consume until `=` or return if html
-                match bytes.by_ref().find(|&(_, &b)| !is_whitespace(b)) {
-                    Some((_, &b'=')) => i,
-                    Some((j, _)) if self.html => {
-                        self.position = j - 1;
-                        attr!(start_key..i, 0..0);
+                ParseState::Eq(start, end) => {
+                    if b == b'=' {
+                        state = ParseState::Value(start, end);
+                    } else if !is_whitespace(b) {
+                        if self.html {
+                            self.position = i - 1;
+                            attr!(start..end, end..end);
+                        }
+                        err!(Error::NoEqAfterName(self.offset + i), i - 1);
                     }
-                    Some((j, _)) => err!(Error::NoEqAfterName(j)),
-                    None if self.html => {
-                        self.position = len;
-                        attr!(start_key..len, 0..0);
+                }
+                // value has quote if not html
+                ParseState::Value(start, end) => match b {
+                    b'\'' | b'"' => match memchr::memchr(b, &self.bytes[i + 1..]) {
+                        Some(j) => {
+                            let j = i + 1 + j;
+                            self.position = j + 1;
+                            attr!(start..end, i + 1..j)
+                        }
+                        None if self.with_checks => {
+                            err!(Error::UnclosedAttributeValue(self.offset + i))
+                        }
+                        None => {
+                            self.position = len;
+                            return None;
+                        }
+                    },
+                    b if is_whitespace(b) => (),
+                    _ if self.html => state = ParseState::UnquotedValue(start, end, i),
+                    _ => err!(Error::UnquotedValue(self.offset + i), i),
+                },
+                ParseState::UnquotedValue(start, end, value) => {
+                    if is_whitespace(b) {
+                        self.position = i;
+                        attr!(start..end, value..i);
                     }
-                    None => err!(Error::NoEqAfterName(len)),
                 }
             }
-            None => attr!(start_key..len),
-        };
+            i += 1;
+        }
 
-        if self.with_checks {
-            if let Some(start) = self
-                .consumed
-                .iter()
-                .filter(|r| r.len() == end_key - start_key)
-                .find(|r| self.bytes[(*r).clone()] == self.bytes[start_key..end_key])
-                .map(|ref r| r.start)
-            {
-                err!(Error::DuplicatedAttribute(start_key, start));
+        // end of the bytes
+        match state {
+            ParseState::Token => attr!(self.position..len),
+            ParseState::Key(start) => attr!(start..len),
+            ParseState::Eq(start, _) if self.html => {
+                self.position = len;
+                attr!(start..len, len..len);
+            }
+            ParseState::Eq(..) => err!(Error::NoEqAfterName(self.offset + len)),
+            ParseState::Value(start, end) => attr!(start..end),
+            ParseState::UnquotedValue(start, end, value) => {
+                self.position = len;
+                attr!(start..end, value..len)
             }
-            self.consumed.push(start_key..end_key);
         }
+    }
+}
 
-        // value has quote if not html
-        match bytes.by_ref().find(|&(_, &b)| !is_whitespace(b)) {
-            Some((i, quote @ &b'\'')) | Some((i, quote @ &b'"')) => {
-                match bytes.by_ref().find(|&(_, &b)| b == *quote) {
-                    Some((j, _)) => {
-                        self.position = j + 1;
-                        attr!(start_key..end_key, i + 1..j)
-                    }
-                    None => err!(Error::UnquotedValue(i)),
-                }
-            }
-            Some((i, _)) if self.html => {
-                let j = bytes
-                    .by_ref()
-                    .find(|&(_, &b)| is_whitespace(b))
-                    .map_or(len, |(j, _)| j);
-                self.position = j;
-                attr!(start_key..end_key, i..j)
+/// What the parser of the next attribute expects, with the positions found so far
+#[derive(Clone, Copy)]
+enum ParseState {
+    /// The end of the element name or of the token skipped after an error, before whitespace
+    Token,
+    /// The end of the key started at the given position
+    Key(usize),
+    /// The `=` following the whitespace after the key, with the key bounds
+    Eq(usize, usize),
+    /// The first byte of the value following the `=`, with the key bounds
+    Value(usize, usize),
+    /// The end of an unquoted html value started at the given position, with the key bounds
+    UnquotedValue(usize, usize, usize),
+}
+
+impl<'a> Iterator for Attributes<'a> {
+    type Item = Result<Attribute<'a>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_spans()
+            .map(|result| result.map(|(attribute, _)| attribute))
+    }
+
+    /// Estimates the number of remaining attributes from the remaining bytes, without parsing
+    /// them: the upper bound is reached when no attribute value contains an `=`.
+    ///
+    /// An exact count would require parsing the attributes twice, so `Attributes` is not an
+    /// `ExactSizeIterator`.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = match self.bytes.get(self.position..) {
+            Some(remaining) if !remaining.is_empty() => remaining,
+            _ => return (0, Some(0)),
+        };
+        // each attribute or skipped token is preceded by a whitespace
+        let mut attributes = remaining.len() / 2;
+        if self.stop_on_error {
+            // each attribute contains an `=` unless in html
+            if !self.html {
+                attributes = attributes.min(remaining.iter().filter(|&&b| b == b'=').count());
             }
-            Some((i, _)) => err!(Error::UnquotedValue(i)),
-            None => attr!(start_key..end_key),
+            attributes = attributes.min(self.max_attributes.saturating_sub(self.count));
         }
+        // the iteration can end with an error
+        (0, Some(attributes + 1))
+    }
+}
+
+/// Returns the position of the first byte of `bytes` which is not whitespace, if any.
+///
+/// Long runs of whitespace (e.g. indentation between attributes) are skipped 8 bytes at a time.
+fn find_non_whitespace(bytes: &[u8]) -> Option<usize> {
+    const ONES: u64 = 0x0101_0101_0101_0101;
+    const HIGH_BITS: u64 = 0x8080_8080_8080_8080;
+    // sets the high bit of each zero byte (and only of them) of `v`
+    fn zero_bytes(v: u64) -> u64 {
+        !(((v & !HIGH_BITS) + !HIGH_BITS) | v | !HIGH_BITS)
+    }
+
+    let mut chunks = bytes.chunks_exact(8);
+    let mut offset = 0;
+    for chunk in chunks.by_ref() {
+        let mut word = [0; 8];
+        word.copy_from_slice(chunk);
+        let v = u64::from_le_bytes(word);
+        let whitespaces = zero_bytes(v ^ (ONES * u64::from(b' ')))
+            | zero_bytes(v ^ (ONES * u64::from(b'\t')))
+            | zero_bytes(v ^ (ONES * u64::from(b'\n')))
+            | zero_bytes(v ^ (ONES * u64::from(b'\r')));
+        if whitespaces != HIGH_BITS {
+            return Some(offset + (!whitespaces & HIGH_BITS).trailing_zeros() as usize / 8);
+        }
+        offset += 8;
+    }
+    chunks
+        .remainder()
+        .iter()
+        .position(|&b| !is_whitespace(b))
+        .map(|i| offset + i)
+}
+
+/// All attributes of a tag, parsed eagerly.
+///
+/// Unlike the lazy [`Attributes`] iterator, this view parses every attribute upfront and stores
+/// it in a `Vec`, which costs one allocation and a full pass over the tag. In exchange, it
+/// dereferences to a slice, allowing indexed access (`view[1]`), `last()` or reverse iteration.
+///
+/// [`Attributes`]: struct.Attributes.html
+#[derive(Clone, Debug)]
+pub struct AttributesView<'a> {
+    attributes: Vec<Attribute<'a>>,
+}
+
+impl<'a> AttributesView<'a> {
+    /// Parses all attributes, failing on the first malformed one
+    pub fn new(attributes: Attributes<'a>) -> Result<AttributesView<'a>> {
+        Ok(AttributesView {
+            attributes: attributes.collect::<Result<_>>()?,
+        })
+    }
+
+    /// Consumes the view, returning the parsed attributes
+    pub fn into_inner(self) -> Vec<Attribute<'a>> {
+        self.attributes
+    }
+}
+
+impl<'a> Deref for AttributesView<'a> {
+    type Target = [Attribute<'a>];
+    fn deref(&self) -> &[Attribute<'a>] {
+        &self.attributes
     }
 }
 
@@ -363,6 +1069,98 @@ mod tests {
         assert!(attributes.next().is_none());
     }
 
+    #[test]
+    fn normalized_value() {
+        let event = b"name a='a\tb\r\nc\rd\ne  f' b='&lt;&#x9;&#xA;&gt;' c='x\n&amp;\ty'";
+        let mut attributes = Attributes::new(event, 0);
+        let a = attributes.next().unwrap().unwrap();
+        assert_eq!(&*a.normalized_value().unwrap(), b"a b c d e  f");
+        let a = attributes.next().unwrap().unwrap();
+        assert_eq!(&*a.normalized_value().unwrap(), b"<\t\n>");
+        let a = attributes.next().unwrap().unwrap();
+        assert_eq!(&*a.normalized_value().unwrap(), b"x & y");
+        assert!(attributes.next().is_none());
+    }
+
+    #[test]
+    fn normalize_and_decode_value() {
+        let reader = Reader::from_str("");
+        let event = b"name a='multi\n    line\r\n  value' b='&#10;&#x9;&#13;'";
+        let mut attributes = Attributes::new(event, 0);
+        let a = attributes.next().unwrap().unwrap();
+        assert_eq!(
+            a.normalize_and_decode_value(&reader).unwrap(),
+            "multi     line   value"
+        );
+        let a = attributes.next().unwrap().unwrap();
+        assert_eq!(a.normalize_and_decode_value(&reader).unwrap(), "\n\t\r");
+    }
+
+    #[test]
+    fn is_escaped() {
+        let event = b"name a='a b' b='a &amp; b' c='&' d=''";
+        let escaped: Vec<_> = Attributes::new(event, 0)
+            .map(|a| a.unwrap().is_escaped())
+            .collect();
+        assert_eq!(escaped, vec![false, true, true, false]);
+        assert!(Attribute::from(("a", "a & b")).is_escaped());
+        assert!(!Attribute::from(("a", "a b")).is_escaped());
+    }
+
+    #[test]
+    fn decode_and_unescape_value() {
+        let reader = Reader::from_str("");
+        let event = b"name a='plain' b='&lt;&#x20;' c='&unknown;'";
+        let mut attributes = Attributes::new(event, 0);
+        let a = attributes.next().unwrap().unwrap();
+        match a.decode_and_unescape_value(&reader).unwrap() {
+            Cow::Borrowed(value) => assert_eq!(value, "plain"),
+            Cow::Owned(value) => panic!("expecting borrowed value, found {:?}", value),
+        }
+        let b = attributes.next().unwrap().unwrap();
+        match b.decode_and_unescape_value(&reader).unwrap() {
+            Cow::Owned(value) => assert_eq!(value, "< "),
+            Cow::Borrowed(value) => panic!("expecting owned value, found {:?}", value),
+        }
+        let c = attributes.next().unwrap().unwrap();
+        assert!(matches!(
+            c.decode_and_unescape_value(&reader),
+            Err(Error::EscapeError(_))
+        ));
+    }
+
+    #[test]
+    fn unescaped_value_with() {
+        let resolve = |entity: &[u8]| match entity {
+            b"custom" => Some(&b"value"[..]),
+            _ => None,
+        };
+        let event = b"name a='&lt;&custom;&#x20;&#33;' b='none' c='&unknown;'";
+        let mut attributes = Attributes::new(event, 0);
+        let a = attributes.next().unwrap().unwrap();
+        assert_eq!(&*a.unescaped_value_with(resolve).unwrap(), b"<value !");
+        let b = attributes.next().unwrap().unwrap();
+        match b.unescaped_value_with(resolve).unwrap() {
+            Cow::Borrowed(value) => assert_eq!(value, b"none"),
+            Cow::Owned(value) => panic!("expecting borrowed value, found {:?}", value),
+        }
+        let c = attributes.next().unwrap().unwrap();
+        match c.unescaped_value_with(resolve) {
+            Err(Error::EscapeError(e)) => assert!(e.to_string().contains("unknown")),
+            e => panic!("expecting EscapeError, found {:?}", e),
+        }
+    }
+
+    #[test]
+    fn normalized_value_borrowed() {
+        let event = b"name a='a b'";
+        let a = Attributes::new(event, 0).next().unwrap().unwrap();
+        match a.normalized_value().unwrap() {
+            Cow::Borrowed(v) => assert_eq!(v, b"a b"),
+            v => panic!("expecting borrowed value, found {:?}", v),
+        }
+    }
+
     #[test]
     fn html_fail() {
         let event = b"name a='a' b=b c";
@@ -399,4 +1197,580 @@ mod tests {
         assert_eq!(&*a.value, b"ee");
         assert!(attributes.next().is_none());
     }
+
+    #[test]
+    fn view() {
+        let event = b"name a='a' b='b' c='c'";
+        let view = AttributesView::new(Attributes::new(event, 0)).unwrap();
+        assert_eq!(view.len(), 3);
+        assert_eq!(view[1].key, b"b");
+        assert_eq!(view.last().unwrap().key, b"c");
+        let keys = view.iter().rev().map(|a| a.key).collect::<Vec<_>>();
+        assert_eq!(keys, vec![&b"c"[..], b"b", b"a"]);
+
+        let event = b"name a='a' a='b'";
+        assert!(AttributesView::new(Attributes::new(event, 0)).is_err());
+    }
+
+    #[test]
+    fn unclosed_value() {
+        let event = b"a b=\"unterminated";
+        let mut attributes = Attributes::new(event, 0);
+        match attributes.next() {
+            Some(Err(Error::UnclosedAttributeValue(4))) => (),
+            e => panic!("expecting UnclosedAttributeValue error, found {:?}", e),
+        }
+        assert!(attributes.next().is_none());
+
+        let mut attributes = Attributes::new(event, 0);
+        attributes.with_checks(false);
+        assert!(attributes.next().is_none());
+    }
+
+    #[test]
+    fn max_attributes() {
+        let event = b"name a='a' b='b' c='c'";
+        let mut attributes = Attributes::new(event, 0);
+        attributes.max_attributes(2);
+        assert_eq!(attributes.next().unwrap().unwrap().key, b"a");
+        assert_eq!(attributes.next().unwrap().unwrap().key, b"b");
+        match attributes.next() {
+            Some(Err(Error::TooManyAttributes(2))) => (),
+            e => panic!("expecting TooManyAttributes error, found {:?}", e),
+        }
+        assert!(attributes.next().is_none());
+
+        let mut attributes = Attributes::new(event, 0);
+        attributes.max_attributes(3);
+        assert_eq!(attributes.count(), 3);
+    }
+
+    #[test]
+    fn size_hint_bounds_remaining_attributes() {
+        let events: &[&[u8]] = &[
+            b"name",
+            b"name ",
+            b"name a='a' b=\"b=c\" c='='",
+            b"name a = 'a'\n\tb='b'",
+            b"name a b c",
+            b"name a=b c='d' e",
+            b"name a='a' a='b'",
+            b"name a='a",
+            b"name a=1 b=2 c=3 d e\"f g",
+        ];
+        for &event in events {
+            for &html in &[false, true] {
+                for &(max, stop_on_error) in &[(1, true), (usize::MAX, true), (1, false)] {
+                    let mut attributes = if html {
+                        Attributes::html(event, 4)
+                    } else {
+                        Attributes::new(event, 4)
+                    };
+                    attributes.max_attributes(max).stop_on_error(stop_on_error);
+                    loop {
+                        let (lower, upper) = attributes.size_hint();
+                        let remaining = attributes.clone().count();
+                        assert!(lower <= remaining && remaining <= upper.unwrap());
+                        if attributes.next().is_none() {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        assert_eq!(Attributes::new(b"name", 4).size_hint(), (0, Some(0)));
+        assert_eq!(Attributes::new(b"name a='a'", 4).size_hint(), (0, Some(2)));
+    }
+
+    #[test]
+    fn owned_attribute() {
+        let owned = {
+            let event = b"name a='x&amp;y'".to_vec();
+            let mut attributes = Attributes::new(&event, 4);
+            attributes.next().unwrap().unwrap().into_owned()
+        };
+        assert_eq!(owned.key, b"a");
+        assert_eq!(owned.value, b"x&amp;y");
+        assert_eq!(
+            format!("{:?}", owned),
+            r#"OwnedAttribute { key: "a", value: "x&amp;y" }"#
+        );
+        let attribute = Attribute::from(&owned);
+        assert_eq!(&*attribute.unescaped_value().unwrap(), b"x&y");
+        assert_eq!(OwnedAttribute::from(attribute), owned);
+    }
+
+    #[test]
+    fn stop_on_error() {
+        fn keys(event: &[u8]) -> Vec<String> {
+            let mut attributes = Attributes::new(event, 4);
+            attributes.stop_on_error(false);
+            attributes
+                .map(|a| match a {
+                    Ok(a) => String::from_utf8(a.key.to_vec()).unwrap(),
+                    Err(e) => format!("{:?}", e),
+                })
+                .collect()
+        }
+
+        assert_eq!(
+            keys(b"name a='1' b=2 c='3'"),
+            vec!["a", "UnquotedValue(13)", "c"]
+        );
+        assert_eq!(
+            keys(b"name a='1' b c='3'"),
+            vec!["a", "NoEqAfterName(13)", "c"]
+        );
+        assert_eq!(
+            keys(b"name a='1' b\"x c='3'"),
+            vec!["a", "NoEqAfterName(15)", "c"]
+        );
+        assert_eq!(
+            keys(b"name a='1' a='x y' b='2' b='3'"),
+            vec![
+                "a",
+                "DuplicatedAttribute(11, 5)",
+                "b",
+                "DuplicatedAttribute(25, 19)"
+            ]
+        );
+        // a skipped attribute is not a duplicate of the following one
+        assert_eq!(keys(b"name a=1 a='2'"), vec!["UnquotedValue(7)", "a"]);
+        assert_eq!(
+            keys(b"name a='1' b='2"),
+            vec!["a", "UnclosedAttributeValue(13)"]
+        );
+
+        let mut attributes = Attributes::new(b"name a='1' b=2 c='3'", 4);
+        assert_eq!(attributes.next().unwrap().unwrap().key, b"a");
+        assert!(attributes.next().unwrap().is_err());
+        assert!(attributes.next().is_none());
+    }
+
+    #[test]
+    fn html_duplicated_attribute() {
+        let mut attributes = Attributes::html(b"name disabled a=1 disabled", 4);
+        assert_eq!(attributes.next().unwrap().unwrap().key, b"disabled");
+        assert_eq!(attributes.next().unwrap().unwrap().key, b"a");
+        match attributes.next() {
+            Some(Err(Error::DuplicatedAttribute(18, 5))) => (),
+            e => panic!("expecting DuplicatedAttribute error, found {:?}", e),
+        }
+        assert!(attributes.next().is_none());
+    }
+
+    #[test]
+    fn with_positions() {
+        let event: &[u8] = b"name a='a' b = \"bb\" c='' xmlns:d=\"d\"";
+        for &checks in &[true, false] {
+            let mut attributes = Attributes::new(event, 4);
+            attributes.with_checks(checks);
+            let mut count = 0;
+            for a in attributes.with_positions() {
+                let (a, spans) = a.unwrap();
+                assert_eq!(&event[spans.key], a.key);
+                assert_eq!(&event[spans.value.clone()], &*a.value);
+                assert!(
+                    event[spans.value.start - 1] == b'\'' || event[spans.value.start - 1] == b'"'
+                );
+                count += 1;
+            }
+            assert_eq!(count, 4);
+        }
+
+        let event: &[u8] = b"input disabled value=x checked";
+        let spans: Vec<_> = Attributes::html(event, 5)
+            .with_positions()
+            .map(|a| a.unwrap().1)
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                AttributeSpans {
+                    key: 6..14,
+                    value: 14..14
+                },
+                AttributeSpans {
+                    key: 15..20,
+                    value: 21..22
+                },
+                AttributeSpans {
+                    key: 23..30,
+                    value: 30..30
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_name() {
+        let mut attributes = Attributes::new(b"tag a-1='1' 1a='2'", 3);
+        attributes.with_offset(10).check_names(true);
+        assert_eq!(attributes.next().unwrap().unwrap().key, b"a-1");
+        match attributes.next() {
+            Some(Err(Error::InvalidName(22))) => (),
+            e => panic!("expecting InvalidName(22), found {:?}", e),
+        }
+
+        let mut attributes = Attributes::new("tag \u{e9}='1' a\u{d7}='2'".as_bytes(), 3);
+        attributes.check_names(true);
+        assert_eq!(attributes.next().unwrap().unwrap().value, &b"1"[..]);
+        match attributes.next() {
+            Some(Err(Error::InvalidName(12))) => (),
+            e => panic!("expecting InvalidName(12), found {:?}", e),
+        }
+
+        // not checked by default, e.g. for documents in other encodings
+        let mut attributes = Attributes::new(b"tag \xE8\xEC\xFF='1' 1a='2'", 3);
+        assert_eq!(attributes.next().unwrap().unwrap().key, b"\xE8\xEC\xFF");
+        assert_eq!(attributes.next().unwrap().unwrap().key, b"1a");
+        let mut attributes = Attributes::html(b"tag @click=f", 3);
+        attributes.check_names(true);
+        assert_eq!(attributes.next().unwrap().unwrap().key, b"@click");
+
+        // independent of the other checks
+        let mut attributes = Attributes::new(b"tag a='1' a='2' 1a='3'", 3);
+        attributes.with_checks(false).check_names(true);
+        assert_eq!(attributes.next().unwrap().unwrap().value, &b"1"[..]);
+        assert_eq!(attributes.next().unwrap().unwrap().value, &b"2"[..]);
+        match attributes.next() {
+            Some(Err(Error::InvalidName(16))) => (),
+            e => panic!("expecting InvalidName(16), found {:?}", e),
+        }
+    }
+
+    #[test]
+    fn duplicated_attribute() {
+        let event = b"name a='a' ab='b' b='c' a='d' b='e'";
+        let mut attributes = Attributes::new(event, 0);
+        attributes.with_offset(10);
+        assert_eq!(attributes.next().unwrap().unwrap().key, b"a");
+        assert_eq!(attributes.next().unwrap().unwrap().key, b"ab");
+        assert_eq!(attributes.next().unwrap().unwrap().key, b"b");
+        match attributes.next() {
+            Some(Err(Error::DuplicatedAttribute(34, 15))) => (),
+            e => panic!("expecting DuplicatedAttribute error, found {:?}", e),
+        }
+
+        // the check restarts with each iterator
+        let mut attributes = Attributes::new(event, 17);
+        assert_eq!(attributes.next().unwrap().unwrap().key, b"b");
+        assert_eq!(attributes.next().unwrap().unwrap().key, b"a");
+        match attributes.next() {
+            Some(Err(Error::DuplicatedAttribute(30, 18))) => (),
+            e => panic!("expecting DuplicatedAttribute error, found {:?}", e),
+        }
+    }
+
+    #[test]
+    fn duplicated_attribute_among_many() {
+        let mut event = b"name".to_vec();
+        for i in 0..100 {
+            event.extend_from_slice(format!(" a{}='{}'", i, i).as_bytes());
+        }
+        let duplicate = event.len() + 1;
+        event.extend_from_slice(b" a0='x'");
+        let mut attributes = Attributes::new(&event, 4);
+        for _ in 0..100 {
+            attributes.next().unwrap().unwrap();
+        }
+        match attributes.next() {
+            Some(Err(Error::DuplicatedAttribute(pos, 5))) if pos == duplicate => (),
+            e => panic!("expecting DuplicatedAttribute error, found {:?}", e),
+        }
+        assert!(attributes.next().is_none());
+    }
+
+    #[test]
+    fn parsed_bounds_and_error_offsets() {
+        let cases: &[(&[u8], &[&str], &[&str])] = &[
+            (
+                b"name a='a' b = \"b\" c",
+                &["a=a", "b=b"],
+                &["a=a", "b=b", "c="],
+            ),
+            (b"name a ='a'", &["a=a"], &["a=a"]),
+            (b"name a= 'a'", &["a=a"], &["a=a"]),
+            (b"name a b='c'", &["NoEqAfterName(17)"], &["a=", "b=c"]),
+            (b"name a=b", &["UnquotedValue(17)"], &["a=b"]),
+            (
+                b"name a='b",
+                &["UnclosedAttributeValue(17)"],
+                &["UnclosedAttributeValue(17)"],
+            ),
+            (
+                b"name a='a' a='b'",
+                &["a=a", "DuplicatedAttribute(21, 15)"],
+                &["a=a", "DuplicatedAttribute(21, 15)"],
+            ),
+            (b"name a", &[], &["a="]),
+            (b"name a =", &[], &["a="]),
+            (b"name a=  ", &[], &["a="]),
+        ];
+        let parse = |mut attributes: Attributes| {
+            attributes
+                .with_offset(10)
+                .map(|a| match a {
+                    Ok(a) => format!(
+                        "{}={}",
+                        ::std::str::from_utf8(a.key).unwrap(),
+                        ::std::str::from_utf8(&a.value).unwrap()
+                    ),
+                    Err(e) => format!("{:?}", e),
+                })
+                .collect::<Vec<_>>()
+        };
+        for &(event, xml, html) in cases {
+            assert_eq!(parse(Attributes::new(event, 0)), xml, "{:?}", event);
+            assert_eq!(parse(Attributes::html(event, 0)), html, "{:?}", event);
+        }
+    }
+
+    /// `Attributes::next_with_spans` as it was before parsing in a single pass, which scanned
+    /// the key, `=` and value with separate searches
+    fn old_next_with_spans<'a>(
+        attributes: &mut Attributes<'a>,
+    ) -> Option<Result<(Attribute<'a>, AttributeSpans)>> {
+        let len = attributes.bytes.len();
+
+        macro_rules! err {
+            ($err:expr) => {{
+                attributes.position = len;
+                return Some(Err($err.into()));
+            }};
+            // resumes with the token following the whitespace after `$resume`
+            ($err:expr, $resume:expr) => {{
+                attributes.position = if attributes.stop_on_error {
+                    len
+                } else {
+                    $resume
+                };
+                return Some(Err($err.into()));
+            }};
+        }
+
+        macro_rules! attr {
+            ($key:expr) => {{
+                attributes.position = len;
+                if attributes.html {
+                    let key = $key;
+                    attr!(key.clone(), key.end..key.end)
+                } else {
+                    return None;
+                }
+            }};
+            ($key:expr, $val:expr) => {{
+                let key = $key;
+                let value = $val;
+                // checked once the value is parsed, to skip it with the attribute
+                let name = &attributes.bytes[key.clone()];
+                if attributes.check_names && !attributes.html {
+                    if let Some(i) = invalid_name_position(name) {
+                        let position = attributes.position;
+                        err!(
+                            Error::InvalidName(attributes.offset + key.start + i),
+                            position
+                        );
+                    }
+                }
+                if attributes.with_checks {
+                    if let Some(start) = attributes.consumed.insert(name, key.start) {
+                        let position = attributes.position;
+                        err!(
+                            Error::DuplicatedAttribute(
+                                attributes.offset + key.start,
+                                attributes.offset + start
+                            ),
+                            position
+                        );
+                    }
+                }
+                attributes.count += 1;
+                let attribute = Attribute {
+                    key: &attributes.bytes[key.clone()],
+                    value: Cow::Borrowed(&attributes.bytes[value.clone()]),
+                };
+                return Some(Ok((attribute, AttributeSpans { key, value })));
+            }};
+        }
+
+        if len <= attributes.position {
+            return None;
+        }
+
+        // key starts after the whitespace
+        let start_key = match attributes.bytes[attributes.position..]
+            .iter()
+            .position(|&b| is_whitespace(b))
+            .and_then(|i| {
+                let start = attributes.position + i + 1;
+                find_non_whitespace(&attributes.bytes[start..]).map(|j| start + j)
+            }) {
+            Some(i) => i,
+            None => attr!(attributes.position..len),
+        };
+        let mut bytes = attributes.bytes.iter().enumerate().skip(start_key + 1);
+
+        if attributes.count == attributes.max_attributes {
+            err!(Error::TooManyAttributes(attributes.max_attributes));
+        }
+
+        // key ends with either whitespace or =
+        let end_key = match bytes
+            .by_ref()
+            .find(|&(_, &b)| b == b'=' || is_whitespace(b))
+        {
+            Some((i, &b'=')) => i,
+            Some((i, &b'\'')) | Some((i, &b'"')) if attributes.with_checks => {
+                err!(Error::NameWithQuote(attributes.offset + i), i);
+            }
+            Some((i, _)) => {
+                // consume until `=` or return if html
+                match bytes.by_ref().find(|&(_, &b)| !is_whitespace(b)) {
+                    Some((_, &b'=')) => i,
+                    Some((j, _)) if attributes.html => {
+                        attributes.position = j - 1;
+                        attr!(start_key..i, i..i);
+                    }
+                    Some((j, _)) => err!(Error::NoEqAfterName(attributes.offset + j), j - 1),
+                    None if attributes.html => {
+                        attributes.position = len;
+                        attr!(start_key..len, len..len);
+                    }
+                    None => err!(Error::NoEqAfterName(attributes.offset + len)),
+                }
+            }
+            None => attr!(start_key..len),
+        };
+
+        // value has quote if not html
+        match bytes.by_ref().find(|&(_, &b)| !is_whitespace(b)) {
+            Some((i, quote @ &b'\'')) | Some((i, quote @ &b'"')) => {
+                match memchr::memchr(*quote, &attributes.bytes[i + 1..]) {
+                    Some(j) => {
+                        let j = i + 1 + j;
+                        attributes.position = j + 1;
+                        attr!(start_key..end_key, i + 1..j)
+                    }
+                    None if attributes.with_checks => {
+                        err!(Error::UnclosedAttributeValue(attributes.offset + i))
+                    }
+                    None => {
+                        attributes.position = len;
+                        None
+                    }
+                }
+            }
+            Some((i, _)) if attributes.html => {
+                let j = bytes
+                    .by_ref()
+                    .find(|&(_, &b)| is_whitespace(b))
+                    .map_or(len, |(j, _)| j);
+                attributes.position = j;
+                attr!(start_key..end_key, i..j)
+            }
+            Some((i, _)) => err!(Error::UnquotedValue(attributes.offset + i), i),
+            None => attr!(start_key..end_key),
+        }
+    }
+
+    #[test]
+    fn single_pass_matches_old_implementation() {
+        fn collect<'a, F>(mut attributes: Attributes<'a>, mut next: F) -> Vec<String>
+        where
+            F: FnMut(&mut Attributes<'a>) -> Option<Result<(Attribute<'a>, AttributeSpans)>>,
+        {
+            let mut parsed = Vec::new();
+            while let Some(result) = next(&mut attributes) {
+                parsed.push(match result {
+                    Ok((a, spans)) => format!("{:?} {:?}", a, spans),
+                    Err(e) => format!("{:?}", e),
+                });
+                assert!(parsed.len() < 16, "{:?}", parsed);
+            }
+            parsed.push(format!("position {}", attributes.position));
+            parsed
+        }
+
+        // every sequence of up to 7 of these bytes after the name
+        let alphabet = b" a='\"";
+        let mut inputs = vec![b"name".to_vec()];
+        let mut last = 0;
+        for _ in 0..7 {
+            let end = inputs.len();
+            for i in last..end {
+                for &b in alphabet {
+                    let mut input = inputs[i].clone();
+                    input.push(b);
+                    inputs.push(input);
+                }
+            }
+            last = end;
+        }
+
+        for input in &inputs {
+            for config in 0..16 {
+                let attributes = || {
+                    let mut attributes = if config & 1 == 0 {
+                        Attributes::new(input, 4)
+                    } else {
+                        Attributes::html(input, 4)
+                    };
+                    attributes
+                        .with_checks(config & 2 == 0)
+                        .stop_on_error(config & 4 == 0)
+                        .with_offset(10);
+                    if config & 8 != 0 {
+                        attributes.check_names(true).max_attributes(1);
+                    }
+                    attributes
+                };
+                assert_eq!(
+                    collect(attributes(), |a| a.next_with_spans()),
+                    collect(attributes(), old_next_with_spans),
+                    "{:?} with config {}",
+                    ::std::str::from_utf8(input).unwrap(),
+                    config
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn find_non_whitespace_matches_scalar_search() {
+        let whitespaces = b" \t\r\n";
+        for len in 0..20 {
+            for &end in &[b'a', b'=', 0, 0x1F, 0x21, 0x8A, 0xA0, 0xFF] {
+                let mut bytes: Vec<u8> = (0..len).map(|i| whitespaces[i % 4]).collect();
+                assert_eq!(find_non_whitespace(&bytes), None);
+                bytes.push(end);
+                bytes.extend_from_slice(b"  x");
+                assert_eq!(find_non_whitespace(&bytes), Some(len), "{:?}", bytes);
+            }
+        }
+    }
+
+    #[test]
+    fn str_accessors() {
+        let event = "name é='a&lt;b' c='d'".as_bytes();
+        let mut attributes = Attributes::new(event, 0);
+        let a = attributes.next().unwrap().unwrap();
+        assert_eq!(a.key_str().unwrap(), "é");
+        assert_eq!(a.value_str().unwrap(), "a&lt;b");
+        assert_eq!(a.unescaped_value_str().unwrap(), "a<b");
+        let c = attributes.next().unwrap().unwrap();
+        assert!(matches!(
+            c.unescaped_value_str().unwrap(),
+            Cow::Borrowed("d")
+        ));
+
+        let event = b"name \xFF='\xFE' a='&#xFF;\xFF'";
+        let mut attributes = Attributes::new(event, 0);
+        let invalid = attributes.next().unwrap().unwrap();
+        assert!(matches!(invalid.key_str(), Err(Error::Utf8(_))));
+        assert!(matches!(invalid.value_str(), Err(Error::Utf8(_))));
+        assert!(matches!(invalid.unescaped_value_str(), Err(Error::Utf8(_))));
+        let a = attributes.next().unwrap().unwrap();
+        assert!(matches!(a.unescaped_value_str(), Err(Error::Utf8(_))));
+    }
 }
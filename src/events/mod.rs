@@ -5,14 +5,20 @@ pub mod attributes;
 #[cfg(feature = "encoding_rs")]
 use encoding_rs::Encoding;
 use std::borrow::Cow;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+use std::fmt;
 use std::io::BufRead;
 use std::ops::Deref;
+#[cfg(not(feature = "std"))]
+use std::prelude::*;
 use std::str::from_utf8;
 
-use self::attributes::{Attribute, Attributes};
+use self::attributes::{Attribute, Attributes, QuoteStyle};
 use errors::{Error, Result};
-use escape::{escape, unescape};
-use reader::Reader;
+use escape::{escape, escape_attribute, escape_with_quote, unescape, unescape_with};
+use name::{check_name, QName};
+use reader::{is_whitespace, Reader};
 
 use memchr;
 
@@ -33,6 +39,9 @@ pub struct BytesStart<'a> {
     buf: Cow<'a, [u8]>,
     /// end of the element name, the name starts at that the start of `buf`
     name_len: usize,
+    /// position of the start of `buf` in the source document, used to report
+    /// absolute positions in attribute errors (`0` if not created by a `Reader` or modified)
+    offset: usize,
 }
 
 impl<'a> BytesStart<'a> {
@@ -46,6 +55,7 @@ impl<'a> BytesStart<'a> {
         BytesStart {
             buf: Cow::Borrowed(content),
             name_len,
+            offset: 0,
         }
     }
 
@@ -67,6 +77,7 @@ impl<'a> BytesStart<'a> {
         BytesStart {
             buf: Cow::Owned(content.into()),
             name_len,
+            offset: 0,
         }
     }
 
@@ -79,17 +90,32 @@ impl<'a> BytesStart<'a> {
         BytesStart {
             name_len: content.len(),
             buf: Cow::Owned(content),
+            offset: 0,
         }
     }
 
+    /// Sets the position of the event content in the source document.
+    #[inline]
+    pub(crate) fn with_offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Gets the content to modify it, forgetting its position in the source document
+    #[inline]
+    fn to_mut(&mut self) -> &mut Vec<u8> {
+        self.offset = 0;
+        self.buf.to_mut()
+    }
+
     /// Converts the event into an owned event.
     pub fn into_owned(self) -> BytesStart<'static> {
-        Self::owned(self.buf.into_owned(), self.name_len)
+        Self::owned(self.buf.into_owned(), self.name_len).with_offset(self.offset)
     }
 
     /// Converts the event into an owned event without taking ownership of Event
     pub fn to_owned(&self) -> BytesStart<'static> {
-        Self::owned(self.buf.to_owned(), self.name_len)
+        Self::owned(self.buf.to_owned(), self.name_len).with_offset(self.offset)
     }
 
     /// Converts the event into a borrowed event. Most useful when paired with [`to_end`].
@@ -117,10 +143,25 @@ impl<'a> BytesStart<'a> {
     ///
     /// [`to_end`]: #method.to_end
     pub fn to_borrowed(&self) -> BytesStart {
-        BytesStart::borrowed(&self.buf, self.name_len)
+        BytesStart::borrowed(&self.buf, self.name_len).with_offset(self.offset)
     }
 
     /// Creates new paired close tag
+    ///
+    /// The closing tag borrows the name of this tag, without its attributes. Writing it is the
+    /// simplest way to close an element without repeating its name:
+    ///
+    /// ```rust
+    /// use quick_xml::events::{BytesStart, Event};
+    /// use quick_xml::Writer;
+    ///
+    /// let mut writer = Writer::new(Vec::new());
+    /// let start = BytesStart::owned(b"tag attr=\"value\"".to_vec(), 3);
+    /// writer.write_event(Event::Start(start.to_borrowed()))?;
+    /// writer.write_event(Event::End(start.to_end()))?;
+    /// assert_eq!(writer.into_inner(), br#"<tag attr="value"></tag>"#);
+    /// # Ok::<(), quick_xml::Error>(())
+    /// ```
     pub fn to_end(&self) -> BytesEnd {
         BytesEnd::borrowed(self.name())
     }
@@ -147,11 +188,40 @@ impl<'a> BytesStart<'a> {
 
     /// Gets the undecoded raw local tag name (excluding namespace) as a `&[u8]`.
     ///
-    /// All content up to and including the first `:` character is removed from the tag name.
+    /// All content up to and including the first `:` character is removed from the tag name,
+    /// without the attributes. This is purely syntactic: the prefix is not resolved, so the same
+    /// local name may belong to different namespaces.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use quick_xml::events::BytesStart;
+    ///
+    /// let start = BytesStart::borrowed(b"w:p w:rsid='1'", 3);
+    /// assert_eq!(start.local_name(), b"p");
+    /// assert_eq!(BytesStart::borrowed_name(b"p").local_name(), b"p");
+    /// ```
     #[inline]
     pub fn local_name(&self) -> &[u8] {
-        let name = self.name();
-        memchr::memchr(b':', name).map_or(name, |i| &name[i + 1..])
+        self.qname().local_name()
+    }
+
+    /// Gets the undecoded raw prefix of the tag name, without the `:`, if any.
+    ///
+    /// See [`QName::prefix`] for names with several `:` or starting with `:`.
+    ///
+    /// [`QName::prefix`]: ../name/struct.QName.html#method.prefix
+    #[inline]
+    pub fn prefix(&self) -> Option<&[u8]> {
+        self.qname().prefix()
+    }
+
+    /// Gets the tag name as a [`QName`].
+    ///
+    /// [`QName`]: ../name/struct.QName.html
+    #[inline]
+    pub fn qname(&self) -> QName<'_> {
+        QName(self.name())
     }
 
     /// Gets the unescaped tag name.
@@ -164,17 +234,122 @@ impl<'a> BytesStart<'a> {
     }
 
     /// Returns an iterator over the attributes of this tag.
+    ///
+    /// If this event was produced by a [`Reader`], positions reported in attribute errors are
+    /// absolute positions in the source document. Once the event is modified (e.g. by
+    /// [`set_name`] or [`push_attribute`]), they are relative to the start of its content.
+    ///
+    /// [`set_name`]: #method.set_name
+    /// [`push_attribute`]: #method.push_attribute
+    /// [`Reader`]: ../reader/struct.Reader.html
     pub fn attributes(&self) -> Attributes {
-        Attributes::new(self, self.name_len)
+        let mut attributes = Attributes::new(self, self.name_len);
+        attributes.with_offset(self.offset);
+        attributes
     }
 
     /// Returns an iterator over the HTML-like attributes of this tag (no mandatory quotes or `=`).
     pub fn html_attributes(&self) -> Attributes {
-        Attributes::html(self, self.name_len)
+        let mut attributes = Attributes::html(self, self.name_len);
+        attributes.with_offset(self.offset);
+        attributes
+    }
+
+    /// Returns the first attribute of this tag with the given `name`, or `None` if there is none.
+    ///
+    /// Attributes are parsed from the start of the tag without checking for duplicates, and
+    /// parsing stops on the first match. An error is returned if an attribute preceding the
+    /// match, or any attribute when there is no match, is malformed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use quick_xml::events::BytesStart;
+    ///
+    /// let start = BytesStart::borrowed(br#"a id="1" href="/b""#, 1);
+    /// let href = start.try_get_attribute("href")?.unwrap();
+    /// assert_eq!(&*href.value, b"/b");
+    /// assert!(start.try_get_attribute("class")?.is_none());
+    /// # Ok::<(), quick_xml::Error>(())
+    /// ```
+    pub fn try_get_attribute<N: AsRef<[u8]>>(&self, name: N) -> Result<Option<Attribute<'_>>> {
+        let mut attributes = self.attributes();
+        attributes.with_checks(false);
+        for attribute in attributes {
+            let attribute = attribute?;
+            if attribute.key == name.as_ref() {
+                return Ok(Some(attribute));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the first attribute of this tag with the given `name`, like
+    /// [`try_get_attribute`], but ignoring malformed attributes: `None` is returned if there is
+    /// no well-formed match before the first malformed attribute.
+    ///
+    /// [`try_get_attribute`]: #method.try_get_attribute
+    pub fn get_attribute<N: AsRef<[u8]>>(&self, name: N) -> Option<Attribute<'_>> {
+        let mut attributes = self.attributes();
+        attributes.with_checks(false);
+        attributes
+            .map_while(|attribute| attribute.ok())
+            .find(|attribute| attribute.key == name.as_ref())
+    }
+
+    /// Collects the attributes of this tag as `(key, value)` strings, in document order.
+    ///
+    /// Keys are decoded and values are unescaped and decoded with the encoding of `reader`, like
+    /// by [`Attribute::unescape_and_decode_value`]. An error is returned for the first malformed
+    /// attribute, and a duplicated key is an `Error::DuplicatedAttribute`, as when iterating over
+    /// [`attributes`] with checks enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use quick_xml::events::Event;
+    /// use quick_xml::Reader;
+    ///
+    /// let mut reader = Reader::from_str(r#"<server host="localhost" motd="fish &amp; chips"/>"#);
+    /// reader.trim_text(true);
+    /// let mut buf = Vec::new();
+    /// match reader.read_event(&mut buf)? {
+    ///     Event::Empty(ref e) => assert_eq!(
+    ///         e.attributes_map(&reader)?,
+    ///         vec![
+    ///             ("host".to_string(), "localhost".to_string()),
+    ///             ("motd".to_string(), "fish & chips".to_string()),
+    ///         ]
+    ///     ),
+    ///     e => panic!("expecting Empty, found {:?}", e),
+    /// }
+    /// # Ok::<(), quick_xml::Error>(())
+    /// ```
+    ///
+    /// [`Attribute::unescape_and_decode_value`]: attributes/struct.Attribute.html#method.unescape_and_decode_value
+    /// [`attributes`]: #method.attributes
+    pub fn attributes_map<B: BufRead>(&self, reader: &Reader<B>) -> Result<Vec<(String, String)>> {
+        let mut map = Vec::new();
+        for attribute in self.attributes() {
+            let attribute = attribute?;
+            #[cfg(feature = "encoding")]
+            let key = reader.decode(attribute.key).into_owned();
+            #[cfg(not(feature = "encoding"))]
+            let key = reader.decode(attribute.key)?.to_owned();
+            map.push((key, attribute.unescape_and_decode_value(reader)?));
+        }
+        Ok(map)
     }
 
     /// Gets the undecoded raw string with the attributes of this tag as a `&[u8]`,
     /// including the whitespace after the tag name if there is any.
+    ///
+    /// This is the content scanned by [`attributes`], as written in the document, so it can be
+    /// copied to another tag without reformatting the attributes. The `/` closing a self-closing
+    /// tag is not part of an `Empty` event read by a `Reader`, but is kept if it was included in
+    /// the content of a `BytesStart` created manually.
+    ///
+    /// [`attributes`]: #method.attributes
     #[inline]
     pub fn attributes_raw(&self) -> &[u8] {
         &self.buf[self.name_len..]
@@ -235,7 +410,7 @@ impl<'a> BytesStart<'a> {
     /// Adds an attribute to this element.
     pub fn push_attribute<'b, A: Into<Attribute<'b>>>(&mut self, attr: A) {
         let a = attr.into();
-        let bytes = self.buf.to_mut();
+        let bytes = self.to_mut();
         bytes.push(b' ');
         bytes.extend_from_slice(a.key);
         bytes.extend_from_slice(b"=\"");
@@ -243,32 +418,142 @@ impl<'a> BytesStart<'a> {
         bytes.push(b'"');
     }
 
+    /// Adds an attribute with a text `value`, quoted according to `style`.
+    ///
+    /// The value is escaped, only the quote character used being replaced by an entity.
+    pub fn push_attribute_with_style(
+        &mut self,
+        key: &str,
+        value: &str,
+        style: QuoteStyle,
+    ) -> &mut BytesStart<'a> {
+        let quote = style.quote_for(value.as_bytes());
+        let bytes = self.to_mut();
+        bytes.push(b' ');
+        bytes.extend_from_slice(key.as_bytes());
+        bytes.push(b'=');
+        bytes.push(quote);
+        bytes.extend_from_slice(&escape_with_quote(value.as_bytes(), quote));
+        bytes.push(quote);
+        self
+    }
+
     /// Edit the name of the BytesStart in-place
     ///
     /// # Warning
     ///
     /// `name` is not checked to be a valid name
     pub fn set_name(&mut self, name: &[u8]) -> &mut BytesStart<'a> {
-        let bytes = self.buf.to_mut();
-        bytes.splice(..self.name_len, name.iter().cloned());
+        let name_len = self.name_len;
+        self.to_mut().splice(..name_len, name.iter().cloned());
         self.name_len = name.len();
         self
     }
 
     /// Remove all attributes from the ByteStart
     pub fn clear_attributes(&mut self) -> &mut BytesStart<'a> {
-        self.buf.to_mut().truncate(self.name_len);
+        let name_len = self.name_len;
+        self.to_mut().truncate(name_len);
         self
     }
-}
 
-impl<'a> std::fmt::Debug for BytesStart<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        use crate::utils::write_byte_string;
+    /// Removes all attributes with the given `key`, with the whitespace preceding them.
+    ///
+    /// Attributes are parsed without checks: nothing is removed after a malformed attribute.
+    pub fn remove_attribute(&mut self, key: &[u8]) -> &mut BytesStart<'a> {
+        let mut ranges = Vec::new();
+        {
+            let mut attributes = Attributes::new(&self.buf, self.name_len);
+            attributes.with_checks(false);
+            loop {
+                let start = attributes.position;
+                match attributes.next() {
+                    Some(Ok(ref a)) if a.key == key => ranges.push(start..attributes.position),
+                    Some(Ok(_)) => (),
+                    _ => break,
+                }
+            }
+        }
+        if !ranges.is_empty() {
+            let bytes = self.to_mut();
+            for range in ranges.into_iter().rev() {
+                bytes.drain(range);
+            }
+        }
+        self
+    }
 
-        write!(f, "BytesStart {{ buf: ")?;
-        write_byte_string(f, &self.buf)?;
-        write!(f, ", name_len: {} }}", self.name_len)
+    /// Replaces the value of all attributes with the given `key` by the escaped `value`, keeping
+    /// their quotes and the other attributes as is, or adds the attribute if there is none.
+    ///
+    /// Attributes are parsed without checks: nothing is replaced after a malformed attribute.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use quick_xml::events::BytesStart;
+    ///
+    /// let mut start = BytesStart::borrowed(b"a x='1'  y=\"2\"", 1);
+    /// start.replace_attribute(b"x", "<3>").replace_attribute(b"z", "4");
+    /// assert_eq!(start.attributes_raw(), b" x='&lt;3&gt;'  y=\"2\" z=\"4\"");
+    /// ```
+    pub fn replace_attribute(&mut self, key: &[u8], value: &str) -> &mut BytesStart<'a> {
+        let mut values = Vec::new();
+        {
+            let mut attributes = Attributes::new(&self.buf, self.name_len);
+            attributes.with_checks(false);
+            for attribute in attributes.with_positions() {
+                match attribute {
+                    Ok((ref a, ref spans)) if a.key == key => values.push(spans.value.clone()),
+                    Ok(_) => (),
+                    Err(_) => break,
+                }
+            }
+        }
+        if values.is_empty() {
+            self.push_attribute((key, &*escape_attribute(value.as_bytes())));
+            return self;
+        }
+        let bytes = self.to_mut();
+        for range in values.into_iter().rev() {
+            let quote = bytes[range.start - 1];
+            let escaped = escape_with_quote(value.as_bytes(), quote);
+            bytes.splice(range, escaped.iter().cloned());
+        }
+        self
+    }
+}
+
+/// Writes the name and the raw attributes of the tag as lossy UTF-8 strings. The malformed end of
+/// the attributes is written as `..`.
+impl<'a> fmt::Debug for BytesStart<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use crate::utils::write_lossy_string;
+
+        write!(f, "BytesStart {{ name: ")?;
+        write_lossy_string(f, self.name())?;
+        write!(f, ", attributes: [")?;
+        let mut attributes = self.attributes();
+        attributes.with_checks(false);
+        for (i, attribute) in attributes.enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            match attribute {
+                Ok(attribute) => {
+                    write!(f, "(")?;
+                    write_lossy_string(f, attribute.key)?;
+                    write!(f, ", ")?;
+                    write_lossy_string(f, &attribute.value)?;
+                    write!(f, ")")?;
+                }
+                Err(_) => {
+                    write!(f, "..")?;
+                    break;
+                }
+            }
+        }
+        write!(f, "] }}")
     }
 }
 
@@ -394,6 +679,14 @@ impl<'a> BytesDecl<'a> {
             element: self.element.into_owned(),
         }
     }
+
+    /// Converts the event into a borrowed event.
+    #[inline]
+    pub fn to_borrowed(&self) -> BytesDecl<'_> {
+        BytesDecl {
+            element: self.element.to_borrowed(),
+        }
+    }
 }
 
 /// A struct to manage `Event::End` events
@@ -426,31 +719,53 @@ impl<'a> BytesEnd<'a> {
         }
     }
 
+    /// Converts the event into a borrowed event.
+    #[inline]
+    pub fn to_borrowed(&self) -> BytesEnd<'_> {
+        BytesEnd::borrowed(&self.name)
+    }
+
     /// Gets `BytesEnd` event name
     #[inline]
     pub fn name(&self) -> &[u8] {
         &*self.name
     }
 
-    /// local name (excluding namespace) as &[u8] (without eventual attributes)
-    /// returns the name() with any leading namespace removed (all content up to
-    /// and including the first ':' character)
+    /// Gets the undecoded raw local tag name (excluding namespace) as a `&[u8]`.
+    ///
+    /// All content up to and including the first `:` character is removed from the tag name.
+    /// This is purely syntactic: the prefix is not resolved, so the same local name may belong
+    /// to different namespaces.
     #[inline]
     pub fn local_name(&self) -> &[u8] {
-        if let Some(i) = self.name().iter().position(|b| *b == b':') {
-            &self.name()[i + 1..]
-        } else {
-            self.name()
-        }
+        self.qname().local_name()
+    }
+
+    /// Gets the undecoded raw prefix of the tag name, without the `:`, if any.
+    ///
+    /// See [`QName::prefix`] for names with several `:` or starting with `:`.
+    ///
+    /// [`QName::prefix`]: ../name/struct.QName.html#method.prefix
+    #[inline]
+    pub fn prefix(&self) -> Option<&[u8]> {
+        self.qname().prefix()
+    }
+
+    /// Gets the tag name as a [`QName`].
+    ///
+    /// [`QName`]: ../name/struct.QName.html
+    #[inline]
+    pub fn qname(&self) -> QName<'_> {
+        QName(self.name())
     }
 }
 
-impl<'a> std::fmt::Debug for BytesEnd<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        use crate::utils::write_byte_string;
+impl<'a> fmt::Debug for BytesEnd<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use crate::utils::write_lossy_string;
 
         write!(f, "BytesEnd {{ name: ")?;
-        write_byte_string(f, &self.name)?;
+        write_lossy_string(f, &self.name)?;
         write!(f, " }}")
     }
 }
@@ -464,6 +779,10 @@ pub struct BytesText<'a> {
 
 impl<'a> BytesText<'a> {
     /// Creates a new `BytesText` from an escaped byte sequence.
+    ///
+    /// The content is written as is by a `Writer`: use [`from_plain`] for untrusted input.
+    ///
+    /// [`from_plain`]: #method.from_plain
     #[inline]
     pub fn from_escaped<C: Into<Cow<'a, [u8]>>>(content: C) -> BytesText<'a> {
         BytesText {
@@ -472,7 +791,7 @@ impl<'a> BytesText<'a> {
     }
 
     /// Creates a new `BytesText` from a byte sequence. The byte sequence is
-    /// expected not to be escaped.
+    /// expected not to be escaped: `<`, `>`, `&`, `'` and `"` are escaped on construction.
     #[inline]
     pub fn from_plain(content: &'a [u8]) -> BytesText<'a> {
         BytesText {
@@ -490,12 +809,173 @@ impl<'a> BytesText<'a> {
     }
 
     /// Creates a new `BytesText` from a string. The string is expected not to
-    /// be escaped.
+    /// be escaped: `<`, `>`, `&`, `'` and `"` are escaped on construction.
     #[inline]
     pub fn from_plain_str(content: &'a str) -> BytesText<'a> {
         Self::from_plain(content.as_bytes())
     }
 
+    /// Creates the content of a `DocType` event declaring the `name` root element, with the
+    /// optional external (`PUBLIC` or `SYSTEM`) identifiers and internal DTD subset.
+    ///
+    /// Identifiers are quoted with `"`, or with `'` if they contain a `"`.
+    ///
+    /// Returns `Error::NotWellFormed` if `public_id` is given without `system_id`, which XML
+    /// requires after it, or if an identifier contains both `"` and `'`, so cannot be quoted.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use quick_xml::events::BytesText;
+    ///
+    /// let doctype = BytesText::doctype("svg", None, Some("svg11.dtd"), None)?;
+    /// assert_eq!(&*doctype, br#" svg SYSTEM "svg11.dtd""#.as_ref());
+    /// assert!(BytesText::doctype("svg", Some("-//W3C//DTD SVG 1.1//EN"), None, None).is_err());
+    /// # Ok::<(), quick_xml::Error>(())
+    /// ```
+    pub fn doctype(
+        name: &str,
+        public_id: Option<&str>,
+        system_id: Option<&str>,
+        internal_subset: Option<&str>,
+    ) -> Result<BytesText<'static>> {
+        fn push_quoted(content: &mut Vec<u8>, id: &str) -> Result<()> {
+            let quote = match (id.contains('"'), id.contains('\'')) {
+                (false, _) => b'"',
+                (true, false) => b'\'',
+                (true, true) => {
+                    return Err(Error::NotWellFormed(format!(
+                        "identifier `{}` contains both `\"` and `'`",
+                        id
+                    )))
+                }
+            };
+            content.push(b' ');
+            content.push(quote);
+            content.extend_from_slice(id.as_bytes());
+            content.push(quote);
+            Ok(())
+        }
+
+        let mut content = Vec::new();
+        content.push(b' ');
+        content.extend_from_slice(name.as_bytes());
+        match (public_id, system_id) {
+            (Some(public_id), Some(system_id)) => {
+                content.extend_from_slice(b" PUBLIC");
+                push_quoted(&mut content, public_id)?;
+                push_quoted(&mut content, system_id)?;
+            }
+            (Some(_), None) => {
+                return Err(Error::NotWellFormed(
+                    "PUBLIC identifier must be followed by a SYSTEM identifier".to_string(),
+                ))
+            }
+            (None, Some(system_id)) => {
+                content.extend_from_slice(b" SYSTEM");
+                push_quoted(&mut content, system_id)?;
+            }
+            (None, None) => (),
+        }
+        if let Some(internal_subset) = internal_subset {
+            content.extend_from_slice(b" [");
+            content.extend_from_slice(internal_subset.as_bytes());
+            content.push(b']');
+        }
+        Ok(BytesText::from_escaped(content))
+    }
+
+    /// Creates the content of a `PI` event (`<?target content?>`), separating `target` from
+    /// `content` with a space unless `content` is empty.
+    ///
+    /// Returns `Error::NotWellFormed` if `target` is not a valid name or is the reserved `xml`
+    /// (in any case), or if `content` contains `?>`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use quick_xml::events::BytesText;
+    ///
+    /// let pi = BytesText::pi(b"app", b"x > 1?")?;
+    /// assert_eq!(&*pi, b"app x > 1?");
+    /// assert!(BytesText::pi(b"app", b"?>").is_err());
+    /// # Ok::<(), quick_xml::Error>(())
+    /// ```
+    pub fn pi(target: &[u8], content: &[u8]) -> Result<BytesText<'static>> {
+        check_name(target)?;
+        if target.eq_ignore_ascii_case(b"xml") {
+            return Err(Error::NotWellFormed(
+                "processing instruction target `xml` is reserved".to_string(),
+            ));
+        }
+        if let Some(i) = content.windows(2).position(|w| w == b"?>") {
+            return Err(Error::NotWellFormed(format!(
+                "processing instruction contains `?>` at position {}",
+                i
+            )));
+        }
+        let mut pi = target.to_vec();
+        if !content.is_empty() {
+            pi.push(b' ');
+            pi.extend_from_slice(content);
+        }
+        Ok(BytesText::from_escaped(pi))
+    }
+
+    /// Creates the content of an `xml-stylesheet` `PI` event from its pseudo-attributes (e.g.
+    /// `href` and `type`), escaping and quoting their values with `"`.
+    ///
+    /// Returns `Error::NotWellFormed` if a pseudo-attribute name is not a valid name.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use quick_xml::events::BytesText;
+    ///
+    /// let pi = BytesText::xml_stylesheet(vec![("href", "a.xsl?v=1&b"), ("type", "text/xsl")])?;
+    /// assert_eq!(&*pi, br#"xml-stylesheet href="a.xsl?v=1&amp;b" type="text/xsl""#.as_ref());
+    /// # Ok::<(), quick_xml::Error>(())
+    /// ```
+    pub fn xml_stylesheet<'b, I>(pseudo_attributes: I) -> Result<BytesText<'static>>
+    where
+        I: IntoIterator<Item = (&'b str, &'b str)>,
+    {
+        let mut content = Vec::new();
+        for (name, value) in pseudo_attributes {
+            check_name(name.as_bytes())?;
+            if !content.is_empty() {
+                content.push(b' ');
+            }
+            content.extend_from_slice(name.as_bytes());
+            content.extend_from_slice(b"=\"");
+            content.extend_from_slice(&escape_with_quote(value.as_bytes(), b'"'));
+            content.push(b'"');
+        }
+        Self::pi(b"xml-stylesheet", &content)
+    }
+
+    /// Creates a new `BytesText` to be written as a `Comment` event, replacing each `--` of
+    /// `content` with `- -` and appending a space to a trailing `-`, which are not allowed in
+    /// comments.
+    pub fn from_comment_lossy(content: &'a [u8]) -> BytesText<'a> {
+        let is_valid = memchr::memchr_iter(b'-', content)
+            .all(|i| matches!(content.get(i + 1), Some(&b) if b != b'-'));
+        if is_valid {
+            return Self::from_escaped(content);
+        }
+        let mut fixed = Vec::with_capacity(content.len() + 2);
+        for &b in content {
+            if b == b'-' && fixed.last() == Some(&b'-') {
+                fixed.push(b' ');
+            }
+            fixed.push(b);
+        }
+        if fixed.last() == Some(&b'-') {
+            fixed.push(b' ');
+        }
+        Self::from_escaped(fixed)
+    }
+
     /// Ensures that all data is owned to extend the object's lifetime if
     /// necessary.
     #[inline]
@@ -505,6 +985,24 @@ impl<'a> BytesText<'a> {
         }
     }
 
+    /// Converts the event into a borrowed event.
+    #[inline]
+    pub fn to_borrowed(&self) -> BytesText<'_> {
+        BytesText::from_escaped(&*self.content)
+    }
+
+    /// Checks whether the content is empty or only made of XML whitespace (blank, new line,
+    /// carriage return or tab).
+    ///
+    /// The raw content is checked, so an escaped whitespace like `&#32;` is not whitespace. This
+    /// is useful to skip the text between elements when the reader does not [`trim_text`].
+    ///
+    /// [`trim_text`]: ../struct.Reader.html#method.trim_text
+    #[inline]
+    pub fn is_whitespace(&self) -> bool {
+        self.content.iter().all(|&b| is_whitespace(b))
+    }
+
     /// Extracts the inner `Cow` from the `BytesText` event container.
     #[cfg(feature = "serialize")]
     #[inline]
@@ -520,6 +1018,34 @@ impl<'a> BytesText<'a> {
         unescape(self).map_err(Error::EscapeError)
     }
 
+    /// Returns the unescaped content like [`unescaped`], replacing the other entities with the
+    /// value returned by `resolve` for their name.
+    ///
+    /// The predefined entities and character references are replaced first. The values returned
+    /// by `resolve` are inserted as they are, without being unescaped. An entity for which
+    /// `resolve` returns `None` is an `Error::EscapeError` naming it.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use quick_xml::events::BytesText;
+    ///
+    /// let doctype = BytesText::from_escaped(&br#" doc [<!ENTITY me "Jane">]"#[..]);
+    /// let entities = doctype.entities()?;
+    /// let text = BytesText::from_escaped(&b"&me; &amp; co&#x21;"[..]);
+    /// let unescaped = text.unescaped_with(|entity| entities.get(entity).map(|v| &v[..]))?;
+    /// assert_eq!(&*unescaped, b"Jane & co!");
+    /// # Ok::<(), quick_xml::Error>(())
+    /// ```
+    ///
+    /// [`unescaped`]: #method.unescaped
+    pub fn unescaped_with<'e, F>(&self, resolve: F) -> Result<Cow<'_, [u8]>>
+    where
+        F: Fn(&[u8]) -> Option<&'e [u8]>,
+    {
+        unescape_with(self, resolve).map_err(Error::EscapeError)
+    }
+
     /// helper method to unescape then decode self using the reader encoding
     /// but without BOM (Byte order mark)
     ///
@@ -533,7 +1059,7 @@ impl<'a> BytesText<'a> {
         reader: &mut Reader<B>,
     ) -> Result<String> {
         let decoded = reader.decode_without_bom(&*self);
-        let unescaped = unescape(decoded.as_bytes()).map_err(Error::EscapeError)?;
+        let unescaped = reader.unescape_text(decoded.as_bytes())?;
         String::from_utf8(unescaped.into_owned()).map_err(|e| Error::Utf8(e.utf8_error()))
     }
 
@@ -550,7 +1076,7 @@ impl<'a> BytesText<'a> {
         reader: &Reader<B>,
     ) -> Result<String> {
         let decoded = reader.decode_without_bom(&*self)?;
-        let unescaped = unescape(decoded.as_bytes()).map_err(Error::EscapeError)?;
+        let unescaped = reader.unescape_text(decoded.as_bytes())?;
         String::from_utf8(unescaped.into_owned()).map_err(|e| Error::Utf8(e.utf8_error()))
     }
 
@@ -563,7 +1089,7 @@ impl<'a> BytesText<'a> {
     #[cfg(feature = "encoding")]
     pub fn unescape_and_decode<B: BufRead>(&self, reader: &Reader<B>) -> Result<String> {
         let decoded = reader.decode(&*self);
-        let unescaped = unescape(decoded.as_bytes()).map_err(Error::EscapeError)?;
+        let unescaped = reader.unescape_text(decoded.as_bytes())?;
         String::from_utf8(unescaped.into_owned()).map_err(|e| Error::Utf8(e.utf8_error()))
     }
 
@@ -576,7 +1102,7 @@ impl<'a> BytesText<'a> {
     #[cfg(not(feature = "encoding"))]
     pub fn unescape_and_decode<B: BufRead>(&self, reader: &Reader<B>) -> Result<String> {
         let decoded = reader.decode(&*self)?;
-        let unescaped = unescape(decoded.as_bytes()).map_err(Error::EscapeError)?;
+        let unescaped = reader.unescape_text(decoded.as_bytes())?;
         String::from_utf8(unescaped.into_owned()).map_err(|e| Error::Utf8(e.utf8_error()))
     }
 
@@ -584,14 +1110,125 @@ impl<'a> BytesText<'a> {
     pub fn escaped(&self) -> &[u8] {
         self.content.as_ref()
     }
+
+    /// Extracts the internal general entities declared in the content of a `DocType` event,
+    /// mapping their names to their literal (not unescaped) values.
+    ///
+    /// Parameter and external entities are skipped, as well as the declarations in comments.
+    /// When an entity is declared several times, the first declaration is kept.
+    ///
+    /// Requires the `std` feature.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use quick_xml::events::BytesText;
+    ///
+    /// let doctype = BytesText::from_escaped(&br#" doc [<!ENTITY me "Jane &amp; co">]"#[..]);
+    /// let entities = doctype.entities().unwrap();
+    /// assert_eq!(entities[&b"me"[..]], b"Jane &amp; co");
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn entities(&self) -> Result<HashMap<Vec<u8>, Vec<u8>>> {
+        let content = self.escaped();
+        let mut entities = HashMap::new();
+        let mut i = match skip_quoted_until(content, 0, b'[')? {
+            Some(i) => i + 1,
+            None => return Ok(entities),
+        };
+        while let Some(start) = memchr::memchr(b'<', &content[i..]) {
+            let rest = &content[i + start..];
+            if rest.starts_with(b"<!--") {
+                i += start + 4;
+                i += find_subslice(&content[i..], b"-->")
+                    .ok_or_else(|| Error::UnexpectedEof("DOCTYPE comment".to_string()))?
+                    + 3;
+            } else if rest.starts_with(b"<?") {
+                i += start + 2;
+                i += find_subslice(&content[i..], b"?>").ok_or_else(|| {
+                    Error::UnexpectedEof("DOCTYPE processing instruction".to_string())
+                })? + 2;
+            } else if rest.starts_with(b"<!ENTITY") {
+                i += start + 8;
+                let name_start = skip_whitespaces(content, i);
+                if name_start == i {
+                    return Err(Error::UnexpectedToken("ENTITY".to_string()));
+                }
+                if content.get(name_start) != Some(&b'%') {
+                    let name_end = content[name_start..]
+                        .iter()
+                        .position(|&b| is_whitespace(b))
+                        .map(|p| name_start + p)
+                        .ok_or_else(|| Error::UnexpectedEof("DOCTYPE entity".to_string()))?;
+                    let value_start = skip_whitespaces(content, name_end);
+                    match content.get(value_start) {
+                        Some(&quote) if quote == b'"' || quote == b'\'' => {
+                            let value_end = memchr::memchr(quote, &content[value_start + 1..])
+                                .map(|p| value_start + 1 + p)
+                                .ok_or_else(|| {
+                                    Error::UnexpectedEof("DOCTYPE entity value".to_string())
+                                })?;
+                            entities
+                                .entry(content[name_start..name_end].to_vec())
+                                .or_insert_with(|| content[value_start + 1..value_end].to_vec());
+                        }
+                        _ => (),
+                    }
+                }
+                i = skip_quoted_until(content, name_start, b'>')?
+                    .ok_or_else(|| Error::UnexpectedEof("DOCTYPE entity".to_string()))?
+                    + 1;
+            } else {
+                i = skip_quoted_until(content, i + start + 1, b'>')?
+                    .ok_or_else(|| Error::UnexpectedEof("DOCTYPE markup".to_string()))?
+                    + 1;
+            }
+        }
+        Ok(entities)
+    }
+}
+
+/// Returns the position of the first `byte` in `content` after `start`, ignoring the quoted ones
+#[cfg(feature = "std")]
+fn skip_quoted_until(content: &[u8], start: usize, byte: u8) -> Result<Option<usize>> {
+    let mut i = start;
+    while i < content.len() {
+        match content[i] {
+            b if b == byte => return Ok(Some(i)),
+            quote @ b'"' | quote @ b'\'' => {
+                i += memchr::memchr(quote, &content[i + 1..])
+                    .ok_or_else(|| Error::UnexpectedEof("DOCTYPE quoted string".to_string()))?
+                    + 1;
+            }
+            _ => (),
+        }
+        i += 1;
+    }
+    Ok(None)
 }
 
-impl<'a> std::fmt::Debug for BytesText<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        use crate::utils::write_byte_string;
+/// Returns the position of the first non-whitespace byte in `content` after `start`
+#[cfg(feature = "std")]
+fn skip_whitespaces(content: &[u8], start: usize) -> usize {
+    content[start..]
+        .iter()
+        .position(|&b| !is_whitespace(b))
+        .map_or(content.len(), |p| start + p)
+}
+
+/// Returns the position of the first `needle` in `content`
+#[cfg(feature = "std")]
+fn find_subslice(content: &[u8], needle: &[u8]) -> Option<usize> {
+    content.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Writes the raw content as a lossy UTF-8 string, truncated with `…` if it is long.
+impl<'a> fmt::Debug for BytesText<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use crate::utils::write_lossy_string;
 
         write!(f, "BytesText {{ content: ")?;
-        write_byte_string(f, &self.content)?;
+        write_lossy_string(f, &self.content)?;
         write!(f, " }}")
     }
 }
@@ -623,7 +1260,87 @@ pub enum Event<'a> {
     Eof,
 }
 
+/// Kind of the markup elements starting with `<!`, returned by [`Event::bang_kind`].
+///
+/// # Example
+///
+/// ```rust
+/// use quick_xml::events::BangKind;
+/// use quick_xml::Reader;
+///
+/// let xml = "<!DOCTYPE doc><doc><!-- comment --><![CDATA[data]]></doc>";
+/// let mut reader = Reader::from_str(xml);
+/// let mut buf = Vec::new();
+/// let mut contents = Vec::new();
+/// loop {
+///     let (event, span) = reader.read_event_with_span(&mut buf)?;
+///     if let Some(kind) = event.bang_kind() {
+///         // the content of the element, between its delimiters
+///         let start = span.start + kind.start_delimiter().len();
+///         let end = span.end - kind.end_delimiter().len();
+///         contents.push((kind, &xml[start..end]));
+///     }
+///     if let quick_xml::events::Event::Eof = event {
+///         break;
+///     }
+///     buf.clear();
+/// }
+/// assert_eq!(
+///     contents,
+///     vec![
+///         (BangKind::DocType, " doc"),
+///         (BangKind::Comment, " comment "),
+///         (BangKind::CData, "data"),
+///     ]
+/// );
+/// # Ok::<(), quick_xml::Error>(())
+/// ```
+///
+/// [`Event::bang_kind`]: enum.Event.html#method.bang_kind
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BangKind {
+    /// Comment `<!-- ... -->`, read as `Event::Comment`.
+    Comment,
+    /// CData `<![CDATA[...]]>`, read as `Event::CData`.
+    CData,
+    /// Doctype `<!DOCTYPE...>`, read as `Event::DocType`.
+    DocType,
+}
+
+impl BangKind {
+    /// Returns the delimiter starting the element, e.g. `<!--` for a comment.
+    ///
+    /// The `DOCTYPE` keyword is matched case-insensitively when reading.
+    pub fn start_delimiter(self) -> &'static [u8] {
+        match self {
+            BangKind::Comment => b"<!--",
+            BangKind::CData => b"<![CDATA[",
+            BangKind::DocType => b"<!DOCTYPE",
+        }
+    }
+
+    /// Returns the delimiter ending the element, e.g. `-->` for a comment.
+    pub fn end_delimiter(self) -> &'static [u8] {
+        match self {
+            BangKind::Comment => b"-->",
+            BangKind::CData => b"]]>",
+            BangKind::DocType => b">",
+        }
+    }
+}
+
 impl<'a> Event<'a> {
+    /// Returns the kind of the element starting with `<!` of this event, or `None` if it is
+    /// not a `Comment`, `CData` or `DocType` event.
+    pub fn bang_kind(&self) -> Option<BangKind> {
+        match *self {
+            Event::Comment(_) => Some(BangKind::Comment),
+            Event::CData(_) => Some(BangKind::CData),
+            Event::DocType(_) => Some(BangKind::DocType),
+            _ => None,
+        }
+    }
+
     /// Converts the event to an owned version, untied to the lifetime of
     /// buffer used when reading but incurring a new, seperate allocation.
     pub fn into_owned(self) -> Event<'static> {
@@ -640,6 +1357,23 @@ impl<'a> Event<'a> {
             Event::Eof => Event::Eof,
         }
     }
+
+    /// Converts the event to a borrowed version, referencing the data of `self` without
+    /// allocating, e.g. to pass an `Event<'static>` to functions expecting an `Event`.
+    pub fn borrow(&self) -> Event<'_> {
+        match *self {
+            Event::Start(ref e) => Event::Start(e.to_borrowed()),
+            Event::End(ref e) => Event::End(e.to_borrowed()),
+            Event::Empty(ref e) => Event::Empty(e.to_borrowed()),
+            Event::Text(ref e) => Event::Text(e.to_borrowed()),
+            Event::Comment(ref e) => Event::Comment(e.to_borrowed()),
+            Event::CData(ref e) => Event::CData(e.to_borrowed()),
+            Event::Decl(ref e) => Event::Decl(e.to_borrowed()),
+            Event::PI(ref e) => Event::PI(e.to_borrowed()),
+            Event::DocType(ref e) => Event::DocType(e.to_borrowed()),
+            Event::Eof => Event::Eof,
+        }
+    }
 }
 
 impl<'a> Deref for BytesStart<'a> {
@@ -687,6 +1421,29 @@ impl<'a> Deref for Event<'a> {
     }
 }
 
+/// Writes the approximate markup of the event, e.g. `<tag attr="value">`, with its raw content
+/// converted to lossy UTF-8 and truncated with `…` if it is long. `Eof` is written as nothing.
+impl<'a> fmt::Display for Event<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use crate::utils::write_lossy_markup;
+
+        let (open, close) = match *self {
+            Event::Start(_) => ("<", ">"),
+            Event::End(_) => ("</", ">"),
+            Event::Empty(_) => ("<", "/>"),
+            Event::Text(_) => ("", ""),
+            Event::Comment(_) => ("<!--", "-->"),
+            Event::CData(_) => ("<![CDATA[", "]]>"),
+            Event::Decl(_) | Event::PI(_) => ("<?", "?>"),
+            Event::DocType(_) => ("<!DOCTYPE", ">"),
+            Event::Eof => return Ok(()),
+        };
+        f.write_str(open)?;
+        write_lossy_markup(f, self)?;
+        f.write_str(close)
+    }
+}
+
 impl<'a> AsRef<Event<'a>> for Event<'a> {
     fn as_ref(&self) -> &Event<'a> {
         self
@@ -735,6 +1492,113 @@ mod test {
         assert_eq!(parsed_local_names[7], "bus:baz".to_string());
     }
 
+    #[test]
+    fn local_name_without_attributes() {
+        let start = BytesStart::borrowed(b"a x:y='1'", 1);
+        assert_eq!(start.local_name(), b"a");
+        let start = BytesStart::borrowed(b"w:p w:rsid='1'", 3);
+        assert_eq!(start.local_name(), b"p");
+        assert_eq!(start.to_end().local_name(), b"p");
+        let start = BytesStart::borrowed(b"a::b c:d='1'", 4);
+        assert_eq!(start.local_name(), b":b");
+        assert_eq!(BytesEnd::borrowed(b"a::b").local_name(), b":b");
+        assert_eq!(BytesEnd::borrowed(b"b").local_name(), b"b");
+    }
+
+    #[test]
+    fn prefix() {
+        let start = BytesStart::borrowed(b"foo:bus:baz a:b:c='1' :x='2' y:='3' z='4'", 11);
+        assert_eq!(start.prefix(), Some(&b"foo"[..]));
+        assert_eq!(BytesStart::borrowed_name(b":foo").prefix(), Some(&b""[..]));
+        assert_eq!(BytesStart::borrowed_name(b"foo").prefix(), None);
+        assert_eq!(BytesEnd::borrowed(b"foo:").prefix(), Some(&b"foo"[..]));
+        assert_eq!(BytesEnd::borrowed(b"foo").prefix(), None);
+
+        let names: Vec<_> = start
+            .attributes()
+            .map(|a| {
+                let a = a.unwrap();
+                (a.prefix(), a.local_name())
+            })
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                (Some(&b"a"[..]), &b"b:c"[..]),
+                (Some(&b""[..]), &b"x"[..]),
+                (Some(&b"y"[..]), &b""[..]),
+                (None, &b"z"[..]),
+            ]
+        );
+    }
+
+    #[test]
+    fn debug() {
+        let start = BytesStart::borrowed(b"item id='3' name=\"caf\xC3\xA9\"", 4);
+        assert_eq!(
+            format!("{:?}", Event::Start(start)),
+            r#"Start(BytesStart { name: "item", attributes: [("id", "3"), ("name", "café")] })"#
+        );
+        let malformed = BytesStart::borrowed(b"item id='3' name=unquoted", 4);
+        assert_eq!(
+            format!("{:?}", malformed),
+            r#"BytesStart { name: "item", attributes: [("id", "3"), ..] }"#
+        );
+        assert_eq!(
+            format!("{:?}", BytesEnd::borrowed(b"item")),
+            r#"BytesEnd { name: "item" }"#
+        );
+        assert_eq!(
+            format!("{:?}", BytesText::from_escaped(&b"a\n\"b\"\xFF"[..])),
+            "BytesText { content: \"a\\n\\\"b\\\"\u{FFFD}\" }"
+        );
+        let long = BytesText::from_escaped("x".repeat(10_000).into_bytes());
+        let debug = format!("{:?}", long);
+        assert!(debug.len() < 300, "{}", debug.len());
+        assert!(debug.ends_with("\"… }"));
+    }
+
+    #[test]
+    fn display() {
+        let start = BytesStart::borrowed(b"item id='3'", 4);
+        assert_eq!(
+            Event::Start(start.to_borrowed()).to_string(),
+            "<item id='3'>"
+        );
+        assert_eq!(Event::Empty(start).to_string(), "<item id='3'/>");
+        assert_eq!(
+            Event::End(BytesEnd::borrowed(b"item")).to_string(),
+            "</item>"
+        );
+        assert_eq!(
+            Event::Text(BytesText::from_plain_str("a < b")).to_string(),
+            "a &lt; b"
+        );
+        assert_eq!(
+            Event::Comment(BytesText::from_escaped(&b" c "[..])).to_string(),
+            "<!-- c -->"
+        );
+        assert_eq!(
+            Event::CData(BytesText::from_escaped(&b"x"[..])).to_string(),
+            "<![CDATA[x]]>"
+        );
+        assert_eq!(
+            Event::Decl(BytesDecl::new(b"1.0", None, None)).to_string(),
+            "<?xml version=\"1.0\"?>"
+        );
+        assert_eq!(
+            Event::PI(BytesText::from_escaped(&b"pi x"[..])).to_string(),
+            "<?pi x?>"
+        );
+        assert_eq!(
+            Event::DocType(BytesText::from_escaped(&b" doc"[..])).to_string(),
+            "<!DOCTYPE doc>"
+        );
+        assert_eq!(Event::Eof.to_string(), "");
+        let cdata = Event::CData(BytesText::from_escaped(vec![b'x'; 10_000]));
+        assert!(cdata.to_string().ends_with("x…]]>"));
+    }
+
     #[test]
     fn bytestart_create() {
         let b = BytesStart::owned_name("test");
@@ -742,6 +1606,95 @@ mod test {
         assert_eq!(b.name(), b"test");
     }
 
+    #[test]
+    fn bytestart_attributes_raw() {
+        let mut reader = Reader::from_str("<a  x = '1'\ty=\"2\" /><b x='1'>");
+        reader.trim_text(true);
+        let mut buf = Vec::new();
+        match reader.read_event(&mut buf) {
+            Ok(Event::Empty(e)) => {
+                assert_eq!(e.attributes_raw(), b"  x = '1'\ty=\"2\" ");
+                let mut copy = BytesStart::borrowed_name(b"c");
+                copy.extend_attributes(e.attributes().map(|a| a.unwrap()));
+                assert_eq!(copy.attributes_raw(), b" x=\"1\" y=\"2\"");
+            }
+            e => panic!("expecting Empty, found {:?}", e),
+        }
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(e)) => assert_eq!(e.attributes_raw(), b" x='1'"),
+            e => panic!("expecting Start, found {:?}", e),
+        }
+        assert_eq!(BytesStart::borrowed(b"a x='1'/", 1).attributes_raw(), b" x='1'/");
+    }
+
+    #[test]
+    fn bytestart_attributes_map() {
+        let reader = Reader::from_str("");
+        let start = BytesStart::borrowed(b"a x='&lt;1&gt;' y=\"\"", 1);
+        assert_eq!(
+            start.attributes_map(&reader).unwrap(),
+            vec![
+                ("x".to_string(), "<1>".to_string()),
+                ("y".to_string(), String::new()),
+            ]
+        );
+        assert!(BytesStart::borrowed_name(b"a")
+            .attributes_map(&reader)
+            .unwrap()
+            .is_empty());
+
+        match BytesStart::borrowed(b"a x='1' x='2'", 1).attributes_map(&reader) {
+            Err(Error::DuplicatedAttribute(8, 2)) => (),
+            e => panic!("expecting DuplicatedAttribute, found {:?}", e),
+        }
+        match BytesStart::borrowed(b"a x='1' y=2", 1).attributes_map(&reader) {
+            Err(Error::UnquotedValue(_)) => (),
+            e => panic!("expecting UnquotedValue, found {:?}", e),
+        }
+        match BytesStart::borrowed(b"a x='&unknown;'", 1).attributes_map(&reader) {
+            Err(Error::EscapeError(_)) => (),
+            e => panic!("expecting EscapeError, found {:?}", e),
+        }
+    }
+
+    #[test]
+    fn bytestart_attributes_raw_round_trip() {
+        fn attributes(attributes: Attributes) -> Vec<(Vec<u8>, Vec<u8>)> {
+            attributes
+                .map(|a| a.unwrap())
+                .map(|a| (a.key.to_vec(), a.value.into_owned()))
+                .collect()
+        }
+
+        let borrowed = BytesStart::borrowed(b"a  x='1'\ny=\"2\" ", 1);
+        let owned = borrowed.to_owned();
+        for start in &[borrowed, owned] {
+            let mut content = start.name().to_vec();
+            content.extend_from_slice(start.attributes_raw());
+            let copy = BytesStart::owned(content, start.name().len());
+            assert_eq!(copy.attributes_raw(), b"  x='1'\ny=\"2\" ");
+            assert_eq!(
+                attributes(copy.attributes()),
+                attributes(start.attributes())
+            );
+            assert_eq!(
+                attributes(copy.html_attributes()),
+                attributes(start.html_attributes())
+            );
+        }
+
+        let html = BytesStart::borrowed(b"input checked value=x", 5);
+        let copy = BytesStart::owned(html.to_vec(), 5);
+        assert_eq!(copy.attributes_raw(), b" checked value=x");
+        assert_eq!(
+            attributes(copy.html_attributes()),
+            vec![
+                (b"checked".to_vec(), Vec::new()),
+                (b"value".to_vec(), b"x".to_vec())
+            ]
+        );
+    }
+
     #[test]
     fn bytestart_set_name() {
         let mut b = BytesStart::owned_name("test");
@@ -756,6 +1709,139 @@ mod test {
         assert_eq!(b.name(), b"g");
     }
 
+    #[test]
+    fn bytestext_from_comment_lossy() {
+        let t = BytesText::from_comment_lossy(b" a - b ");
+        assert!(matches!(t.content, Cow::Borrowed(b" a - b ")));
+        let t = BytesText::from_comment_lossy(b"a -- b ---");
+        assert_eq!(&*t, b"a - - b - - - ");
+        assert_eq!(&*BytesText::from_comment_lossy(b""), b"");
+    }
+
+    #[test]
+    fn event_borrow() {
+        let mut start = BytesStart::owned_name("a");
+        start.push_attribute(("b", "c"));
+        let events = vec![
+            Event::Start(start),
+            Event::End(BytesEnd::owned(b"a".to_vec())),
+            Event::Text(BytesText::from_plain_str("<").into_owned()),
+            Event::Decl(BytesDecl::new(b"1.0", None, None)),
+            Event::Eof,
+        ];
+        for event in &events {
+            let borrowed = event.borrow();
+            assert_eq!(&*borrowed, &**event);
+            match borrowed {
+                Event::Start(ref e) => {
+                    assert!(matches!(e.buf, Cow::Borrowed(_)));
+                    assert_eq!(e.name(), b"a");
+                    assert_eq!(e.attributes_raw(), b" b=\"c\"");
+                }
+                Event::End(ref e) => assert!(matches!(e.name, Cow::Borrowed(b"a"))),
+                Event::Text(ref e) => assert!(matches!(e.content, Cow::Borrowed(b"&lt;"))),
+                Event::Decl(ref e) => assert_eq!(&*e.version().unwrap(), b"1.0"),
+                Event::Eof => (),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn bytestart_remove_attribute() {
+        let mut b = BytesStart::borrowed(b"test id='1' x=\"a\"  id=\"2\" y='b'", 4);
+        b.remove_attribute(b"id");
+        assert_eq!(b.attributes_raw(), b" x=\"a\" y='b'");
+        b.remove_attribute(b"z").remove_attribute(b"y");
+        assert_eq!(b.attributes_raw(), b" x=\"a\"");
+        assert_eq!(b.name(), b"test");
+    }
+
+    #[test]
+    fn bytestart_replace_attribute() {
+        let mut b = BytesStart::borrowed(b"test id='1' x=\"a\"\n\tid=\"2\" y='b'", 4);
+        b.replace_attribute(b"id", "'\"&\n");
+        assert_eq!(
+            b.attributes_raw(),
+            &b" id='&apos;\"&amp;&#10;' x=\"a\"\n\tid=\"'&quot;&amp;&#10;\" y='b'"[..]
+        );
+        b.replace_attribute(b"y", "").replace_attribute(b"z", "<c>");
+        assert_eq!(
+            b.attributes_raw(),
+            &b" id='&apos;\"&amp;&#10;' x=\"a\"\n\tid=\"'&quot;&amp;&#10;\" y='' z=\"&lt;c&gt;\""[..]
+        );
+        assert_eq!(b.name(), b"test");
+    }
+
+    #[test]
+    fn bytestart_get_attribute() {
+        let b = BytesStart::borrowed(b"test id='1' x=\"a\" id='2' y=b z='c'", 4);
+        let mut attributes = b.attributes();
+        attributes.next();
+        assert_eq!(&*b.try_get_attribute("id").unwrap().unwrap().value, b"1");
+        assert_eq!(&*b.try_get_attribute(b"x").unwrap().unwrap().value, b"a");
+        match b.try_get_attribute("z") {
+            Err(Error::UnquotedValue(27)) => (),
+            e => panic!("expecting UnquotedValue error, found {:?}", e),
+        }
+        assert_eq!(&*b.get_attribute("x").unwrap().value, b"a");
+        assert!(b.get_attribute("z").is_none());
+
+        let b = BytesStart::borrowed(b"test", 4);
+        assert!(b.try_get_attribute("id").unwrap().is_none());
+        assert!(b.get_attribute("id").is_none());
+    }
+
+    #[test]
+    fn bytestart_push_attribute_with_style() {
+        let mut b = BytesStart::owned_name("test");
+        b.push_attribute_with_style("a", "x\"y", QuoteStyle::Auto)
+            .push_attribute_with_style("b", "x'y", QuoteStyle::Auto)
+            .push_attribute_with_style("c", "x\"'<", QuoteStyle::Auto)
+            .push_attribute_with_style("d", "x\"'", QuoteStyle::Single)
+            .push_attribute_with_style("e", "x'", QuoteStyle::Double);
+        assert_eq!(
+            b.attributes_raw(),
+            &b" a='x\"y' b=\"x'y\" c=\"x&quot;'&lt;\" d='x\"&apos;' e=\"x'\""[..]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn bytestext_entities() {
+        let doctype = BytesText::from_escaped(
+            &br#" doc SYSTEM "a[b.dtd" [
+  <!-- <!ENTITY c "comment"> -->
+  <!ENTITY % p "parameter">
+  <!ENTITY ext SYSTEM "ext.xml">
+  <!ELEMENT doc (#PCDATA)>
+  <!ATTLIST doc x CDATA "<!ENTITY y 'attribute'>">
+  <?pi <!ENTITY z "pi">?>
+  <!ENTITY a "x &amp; 'y'">
+  <!ENTITY b 'x "y"'>
+  <!ENTITY a "duplicate">
+]"#[..],
+        );
+        let entities = doctype.entities().unwrap();
+        assert_eq!(entities.len(), 2);
+        assert_eq!(entities[&b"a"[..]], b"x &amp; 'y'");
+        assert_eq!(entities[&b"b"[..]], b"x \"y\"");
+
+        let doctype = BytesText::from_escaped(&b" doc"[..]);
+        assert!(doctype.entities().unwrap().is_empty());
+        let doctype = BytesText::from_escaped(&b" doc [<!ENTITY a \"x>]"[..]);
+        assert!(doctype.entities().is_err());
+    }
+
+    #[test]
+    fn bytestext_is_whitespace() {
+        assert!(BytesText::from_escaped(&b""[..]).is_whitespace());
+        assert!(BytesText::from_escaped(&b" \r\n\t"[..]).is_whitespace());
+        assert!(!BytesText::from_escaped(&b" x "[..]).is_whitespace());
+        assert!(!BytesText::from_escaped(&b"&#32;"[..]).is_whitespace());
+        assert!(!BytesText::from_escaped_str("\u{A0}").is_whitespace());
+    }
+
     #[test]
     fn bytestart_clear_attributes() {
         let mut b = BytesStart::owned_name("test");
@@ -766,4 +1852,36 @@ mod test {
         assert_eq!(b.len(), 4);
         assert_eq!(b.name(), b"test");
     }
+
+    #[test]
+    fn bytestext_pi() {
+        assert_eq!(&*BytesText::pi(b"empty", b"").unwrap(), b"empty");
+        assert_eq!(&*BytesText::pi(b"a", b"what?").unwrap(), b"a what?");
+        assert_eq!(&*BytesText::pi(b"a", b"x > 1").unwrap(), b"a x > 1");
+        assert_eq!(&*BytesText::pi(b"a", b">?").unwrap(), b"a >?");
+        for &(target, content) in &[
+            (&b"xml"[..], &b""[..]),
+            (b"XML", b"a"),
+            (b"1a", b""),
+            (b"a b", b""),
+            (b"", b"a"),
+            (b"a", b"x ?> y"),
+        ] {
+            match BytesText::pi(target, content) {
+                Err(Error::NotWellFormed(_)) => (),
+                e => panic!("expecting NotWellFormed for {:?}, found {:?}", target, e),
+            }
+        }
+
+        let pi = BytesText::xml_stylesheet(vec![("href", "a\"b?>.css"), ("type", "text/css")]);
+        assert_eq!(
+            &*pi.unwrap(),
+            br#"xml-stylesheet href="a&quot;b?&gt;.css" type="text/css""#.as_ref()
+        );
+        assert_eq!(
+            &*BytesText::xml_stylesheet(None).unwrap(),
+            b"xml-stylesheet"
+        );
+        assert!(BytesText::xml_stylesheet(vec![("a b", "c")]).is_err());
+    }
 }
@@ -105,13 +105,24 @@
 //!
 //! # Features
 //!
-//! quick-xml supports 2 additional features, non activated by default:
+//! quick-xml supports 3 additional features, non activated by default:
 //! - `encoding`: support non utf8 xmls
 //! - `serialize`: support serde `Serialize`/`Deserialize`
+//! - `async-tokio`: support writing to tokio `AsyncWrite` implementors
+//!
+//! The `std` feature, activated by default, is required by the `Writer`, by reading from
+//! `BufRead` implementors and by all the features above. Without it, the crate is `no_std` and
+//! only needs `alloc`: the `Reader` then reads from byte slices (`Reader::from_str` or
+//! `Reader::from_reader(&[u8])`), along with escaping, events and attributes.
+#![cfg_attr(not(feature = "std"), no_std)]
 #![forbid(unsafe_code)]
 #![deny(missing_docs)]
 #![recursion_limit = "1024"]
 
+#[cfg(not(feature = "std"))]
+#[macro_use]
+extern crate alloc;
+
 #[cfg(feature = "encoding_rs")]
 extern crate encoding_rs;
 extern crate memchr;
@@ -119,26 +130,40 @@ extern crate memchr;
 extern crate serde;
 #[cfg(all(test, feature = "serialize"))]
 extern crate serde_value;
+#[cfg(feature = "async-tokio")]
+extern crate tokio;
 
 #[cfg(feature = "serialize")]
 pub mod de;
 mod errors;
+#[cfg(not(feature = "std"))]
+mod std;
 mod escapei;
 pub mod escape {
     //! Manage xml character escapes
     pub(crate) use escapei::EscapeError;
-    pub use escapei::{escape, unescape};
+    pub use escapei::{
+        escape, escape_attribute, escape_with_quote, unescape, unescape_lenient, unescape_with,
+    };
 }
 pub mod events;
+pub mod name;
 mod reader;
 #[cfg(feature = "serialize")]
 pub mod se;
 mod utils;
+#[cfg(feature = "std")]
 mod writer;
 
 // reexports
 #[cfg(feature = "serialize")]
 pub use errors::serialize::DeError;
 pub use errors::{Error, Result};
-pub use reader::Reader;
-pub use writer::Writer;
+pub use reader::{RawObserver, Reader, ReaderConfig};
+#[cfg(feature = "std")]
+pub use reader::{Chunks, Parser};
+#[cfg(feature = "std")]
+pub use writer::{
+    copy_all, reformat, ElementWriter, FmtWriter, TextWriter, WrappedTagEnd, Writer,
+    WriterConfig,
+};
@@ -0,0 +1,278 @@
+//! Qualified names of elements and attributes
+//!
+//! Provides the [`QName`] newtype, distinguishing qualified names (`prefix:local`) from other byte
+//! slices in comparisons and maps.
+//!
+//! [`QName`]: struct.QName.html
+
+use std::fmt;
+use std::ops::Deref;
+#[cfg(not(feature = "std"))]
+use std::prelude::*;
+use std::str::from_utf8;
+
+use errors::{Error, Result};
+use memchr;
+
+/// A qualified name (`prefix:local` or `local`) of an element or attribute, as written in the
+/// document.
+///
+/// Two `QName`s are equal, and hash the same way, when their prefix and local name are both
+/// equal: `a:x` and `b:x` are different names, even if `a` and `b` are bound to the same
+/// namespace. Compare [`local_name`]s to ignore prefixes, or resolve them with the `Reader`
+/// namespace methods to compare expanded names.
+///
+/// The name is split at its first `:`, so that the local name keeps any other `:`, and the
+/// prefix is empty for a name starting with `:`:
+///
+/// ```
+/// use quick_xml::name::QName;
+///
+/// let name = QName::from("a:b:c");
+/// assert_eq!(name.prefix(), Some(&b"a"[..]));
+/// assert_eq!(name.local_name(), b"b:c");
+/// assert_eq!(QName::from(":x").prefix(), Some(&b""[..]));
+/// assert_eq!(QName::from("x:").local_name(), b"");
+/// ```
+///
+/// [`local_name`]: #method.local_name
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct QName<'a>(pub &'a [u8]);
+
+impl<'a> QName<'a> {
+    /// Gets the undecoded raw qualified name.
+    #[inline]
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    /// Gets the prefix of the name, without the `:`, if any.
+    ///
+    /// Returns `None` for a name without `:`, and an empty prefix for a name starting with `:`.
+    #[inline]
+    pub fn prefix(&self) -> Option<&'a [u8]> {
+        memchr::memchr(b':', self.0).map(|i| &self.0[..i])
+    }
+
+    /// Gets the local name, excluding the prefix and the `:`.
+    ///
+    /// Returns the whole name if it has no `:`, and an empty local name if it ends with its
+    /// only `:`.
+    #[inline]
+    pub fn local_name(&self) -> &'a [u8] {
+        memchr::memchr(b':', self.0).map_or(self.0, |i| &self.0[i + 1..])
+    }
+
+    /// Returns `true` if this name declares a namespace, i.e. is `xmlns` or `xmlns:prefix`.
+    pub fn is_xmlns(&self) -> bool {
+        self.0 == b"xmlns" || self.prefix() == Some(b"xmlns")
+    }
+}
+
+impl<'a> From<&'a [u8]> for QName<'a> {
+    #[inline]
+    fn from(name: &'a [u8]) -> QName<'a> {
+        QName(name)
+    }
+}
+
+impl<'a> From<&'a str> for QName<'a> {
+    #[inline]
+    fn from(name: &'a str) -> QName<'a> {
+        QName(name.as_bytes())
+    }
+}
+
+impl<'a> From<QName<'a>> for &'a [u8] {
+    #[inline]
+    fn from(name: QName<'a>) -> &'a [u8] {
+        name.0
+    }
+}
+
+impl<'a> AsRef<[u8]> for QName<'a> {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self.0
+    }
+}
+
+impl<'a> Deref for QName<'a> {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        self.0
+    }
+}
+
+impl<'a> fmt::Debug for QName<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use crate::utils::write_byte_string;
+
+        write!(f, "QName(")?;
+        write_byte_string(f, self.0)?;
+        write!(f, ")")
+    }
+}
+
+/// Class of the ASCII characters allowed in names, after the first character
+const NAME_CHAR: u8 = 1;
+/// Class of the ASCII characters allowed anywhere in names
+const NAME_START_CHAR: u8 = 2 | NAME_CHAR;
+
+/// Classes of the ASCII characters in names, `0` for the characters not allowed
+const ASCII_NAME_CHARS: [u8; 128] = {
+    let mut table = [0; 128];
+    let mut i = 0;
+    while i < 128 {
+        table[i] = match i as u8 {
+            b':' | b'A'..=b'Z' | b'_' | b'a'..=b'z' => NAME_START_CHAR,
+            b'-' | b'.' | b'0'..=b'9' => NAME_CHAR,
+            _ => 0,
+        };
+        i += 1;
+    }
+    table
+};
+
+/// Returns the position of the first byte of `name` not matching the XML `Name` production (`0`
+/// if `name` is empty), or `None` if it is a valid name.
+///
+/// ASCII bytes are checked with a table, only other characters are decoded.
+pub(crate) fn invalid_name_position(name: &[u8]) -> Option<usize> {
+    if name.is_empty() {
+        return Some(0);
+    }
+    let mut i = 0;
+    while i < name.len() {
+        let b = name[i];
+        if b < 0x80 {
+            let class = if i == 0 { NAME_START_CHAR } else { NAME_CHAR };
+            if ASCII_NAME_CHARS[b as usize] & class != class {
+                return Some(i);
+            }
+            i += 1;
+            continue;
+        }
+        let width = match b {
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => return Some(i),
+        };
+        let c = name
+            .get(i..i + width)
+            .and_then(|c| from_utf8(c).ok())
+            .and_then(|c| c.chars().next());
+        match c {
+            Some(c) if i == 0 && is_name_start_char(c) => (),
+            Some(c) if i > 0 && is_name_char(c) => (),
+            _ => return Some(i),
+        }
+        i += width;
+    }
+    None
+}
+
+/// Checks that `name` matches the XML `Name` production
+pub(crate) fn check_name(name: &[u8]) -> Result<()> {
+    match invalid_name_position(name) {
+        None => Ok(()),
+        Some(_) => Err(Error::NotWellFormed(format!(
+            "`{}` is not a valid name",
+            String::from_utf8_lossy(name)
+        ))),
+    }
+}
+
+/// `NameStartChar` of the XML specification
+fn is_name_start_char(c: char) -> bool {
+    matches!(c,
+        ':' | 'A'..='Z' | '_' | 'a'..='z'
+        | '\u{C0}'..='\u{D6}'
+        | '\u{D8}'..='\u{F6}'
+        | '\u{F8}'..='\u{2FF}'
+        | '\u{370}'..='\u{37D}'
+        | '\u{37F}'..='\u{1FFF}'
+        | '\u{200C}'..='\u{200D}'
+        | '\u{2070}'..='\u{218F}'
+        | '\u{2C00}'..='\u{2FEF}'
+        | '\u{3001}'..='\u{D7FF}'
+        | '\u{F900}'..='\u{FDCF}'
+        | '\u{FDF0}'..='\u{FFFD}'
+        | '\u{10000}'..='\u{EFFFF}'
+    )
+}
+
+/// `NameChar` of the XML specification
+fn is_name_char(c: char) -> bool {
+    is_name_start_char(c)
+        || matches!(c,
+            '-' | '.' | '0'..='9' | '\u{B7}' | '\u{300}'..='\u{36F}' | '\u{203F}'..='\u{2040}'
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "std")]
+    use std::collections::HashSet;
+
+    #[test]
+    fn prefix_and_local_name() {
+        let name = QName::from("a:x");
+        assert_eq!(name.prefix(), Some(&b"a"[..]));
+        assert_eq!(name.local_name(), b"x");
+        assert_eq!(name.as_bytes(), b"a:x");
+        let name = QName::from(&b"x"[..]);
+        assert_eq!(name.prefix(), None);
+        assert_eq!(name.local_name(), b"x");
+        let name = QName::from("a:b:c");
+        assert_eq!(name.prefix(), Some(&b"a"[..]));
+        assert_eq!(name.local_name(), b"b:c");
+        let name = QName::from(":x");
+        assert_eq!(name.prefix(), Some(&b""[..]));
+        assert_eq!(name.local_name(), b"x");
+        let name = QName::from("x:");
+        assert_eq!(name.prefix(), Some(&b"x"[..]));
+        assert_eq!(name.local_name(), b"");
+        let name = QName::from(":");
+        assert_eq!(name.prefix(), Some(&b""[..]));
+        assert_eq!(name.local_name(), b"");
+        assert!(QName::from("xmlns").is_xmlns());
+        assert!(QName::from("xmlns:a").is_xmlns());
+        assert!(!QName::from("xmlnsa").is_xmlns());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn equality_and_hash() {
+        let names: HashSet<_> = vec![QName::from("a:x"), QName::from("b:x"), QName::from("a:x")]
+            .into_iter()
+            .collect();
+        assert_eq!(names.len(), 2);
+        assert!(names.contains(&QName::from("b:x")));
+        assert!(!names.contains(&QName::from("x")));
+        let bytes: &[u8] = QName::from("a:x").into();
+        assert_eq!(bytes, b"a:x");
+    }
+
+    #[test]
+    fn name_validation() {
+        for name in &["a", "_a-1.b", "a:b", ":a", "A9", "\u{e9}t\u{e9}", "x\u{b7}y"] {
+            assert_eq!(invalid_name_position(name.as_bytes()), None, "{}", name);
+        }
+        assert_eq!(invalid_name_position(b""), Some(0));
+        assert_eq!(invalid_name_position(b"1badName"), Some(0));
+        assert_eq!(invalid_name_position(b"-a"), Some(0));
+        assert_eq!(invalid_name_position(b".a"), Some(0));
+        assert_eq!(invalid_name_position(b"foo bar="), Some(3));
+        assert_eq!(invalid_name_position(b"a=b"), Some(1));
+        // `·` and combining characters cannot start a name
+        assert_eq!(invalid_name_position("\u{b7}a".as_bytes()), Some(0));
+        assert_eq!(invalid_name_position("a\u{d7}".as_bytes()), Some(1));
+        assert_eq!(invalid_name_position(b"ab\xFF"), Some(2));
+        assert_eq!(invalid_name_position(b"a\xC3"), Some(1));
+    }
+}
@@ -1,19 +1,76 @@
 //! A module to handle `Reader`
 
-#[cfg(feature = "encoding")]
 use std::borrow::Cow;
+#[cfg(feature = "std")]
 use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use std::io::BufRead;
+#[cfg(feature = "std")]
+use std::io::{self, BufReader};
+use std::mem;
+use std::ops::Range;
+#[cfg(feature = "std")]
 use std::path::Path;
+#[cfg(not(feature = "std"))]
+use std::prelude::*;
 use std::str::from_utf8;
 
 #[cfg(feature = "encoding")]
 use encoding_rs::{Encoding, UTF_16BE, UTF_16LE};
 
 use errors::{Error, Result};
+use escape::{escape, unescape, unescape_lenient};
 use events::{attributes::Attribute, BytesDecl, BytesEnd, BytesStart, BytesText, Event};
 
 use memchr;
+use name::invalid_name_position;
+
+/// Maximum nesting depth set by [`Reader::with_safe_defaults`].
+///
+/// [`Reader::with_safe_defaults`]: struct.Reader.html#method.with_safe_defaults
+const SAFE_MAX_DEPTH: usize = 1024;
+
+/// Kind of markup read by the `Reader`, starting after the `<`
+#[derive(Clone, Copy)]
+enum Markup {
+    /// `Start` or `Empty` element, with the position of its content in the document
+    Start(usize),
+    /// `End` element
+    End,
+    /// Element starting with a `!`
+    Bang(BangType),
+    /// Element starting with a `?`, with the position of its content in the document
+    QuestionMark(usize),
+}
+
+/// Kind of element starting with a `!`
+#[derive(Clone, Copy)]
+enum BangType {
+    /// `<!--...-->`
+    Comment,
+    /// `<![CDATA[...]]>`
+    CData,
+    /// `<!DOCTYPE...>`
+    DocType,
+    /// any other `<!...>`, if `unknown_bang_as_text`
+    Unknown,
+}
+
+impl BangType {
+    /// Creates the event from the content of the element, starting with the `!`
+    fn to_event(self, buf: &[u8]) -> Event<'_> {
+        match self {
+            BangType::Comment => Event::Comment(BytesText::from_escaped(&buf[3..buf.len() - 2])),
+            BangType::CData => Event::CData(BytesText::from_plain(&buf[8..buf.len() - 2])),
+            BangType::DocType => Event::DocType(BytesText::from_escaped(&buf[8..])),
+            BangType::Unknown => {
+                let mut text = b"&lt;".to_vec();
+                text.extend_from_slice(&escape(buf));
+                text.extend_from_slice(b"&gt;");
+                Event::Text(BytesText::from_escaped(text))
+            }
+        }
+    }
+}
 
 #[derive(Clone)]
 enum TagState {
@@ -28,6 +85,10 @@ enum TagState {
 ///
 /// Consumes a `BufRead` and streams XML `Event`s.
 ///
+/// The reader does not require a single root element: fragments and concatenated
+/// documents are read as a sequence of top-level siblings until `Event::Eof`, unless
+/// [`check_document_structure`] is set.
+///
 /// # Examples
 ///
 /// ```
@@ -62,6 +123,8 @@ enum TagState {
 ///     buf.clear();
 /// }
 /// ```
+///
+/// [`check_document_structure`]: #method.check_document_structure
 #[derive(Clone)]
 pub struct Reader<B: BufRead> {
     /// reader
@@ -70,23 +133,26 @@ pub struct Reader<B: BufRead> {
     buf_position: usize,
     /// current state Open/Close
     tag_state: TagState,
-    /// expand empty element into an opening and closing element
-    expand_empty_elements: bool,
-    /// trims Text events, skip the element if text is empty
-    trim_text: bool,
-    /// trims trailing whitespaces from markup names in closing tags `</a >`
-    trim_markup_names_in_closing_tags: bool,
-    /// check if End nodes match last Start node
-    check_end_names: bool,
-    /// check if comments contains `--` (false per default)
-    check_comments: bool,
+    /// settings changed by the builder methods
+    config: ReaderConfig,
+    /// whether the root element has been read, if `check_document_structure`
+    root_read: bool,
     /// all currently Started elements which didn't have a matching
     /// End element yet
     opened_buffer: Vec<u8>,
     /// opened name start indexes
     opened_starts: Vec<usize>,
+    /// number of currently opened elements
+    depth: usize,
+    /// opened elements with an `xml:space` attribute, as their depth and whether they preserve
+    /// whitespaces
+    xml_space: Vec<(usize, bool)>,
     /// a buffer to manage namespaces
     ns_buffer: NamespaceBufferIndex,
+    /// event read by `peek_event` with its span, to be returned by the next `read_event`
+    peeked: Option<(Event<'static>, Range<usize>)>,
+    /// buffer reused by `read_event_unbuffered`
+    unbuffered: Vec<u8>,
     #[cfg(feature = "encoding")]
     /// the encoding specified in the xml, defaults to utf8
     encoding: &'static Encoding,
@@ -102,14 +168,15 @@ impl<B: BufRead> Reader<B> {
             reader,
             opened_buffer: Vec::new(),
             opened_starts: Vec::new(),
+            depth: 0,
+            xml_space: Vec::new(),
             tag_state: TagState::Closed,
-            expand_empty_elements: false,
-            trim_text: false,
-            trim_markup_names_in_closing_tags: true,
-            check_end_names: true,
+            config: ReaderConfig::default(),
             buf_position: 0,
-            check_comments: false,
+            root_read: false,
             ns_buffer: NamespaceBufferIndex::default(),
+            peeked: None,
+            unbuffered: Vec::new(),
             #[cfg(feature = "encoding")]
             encoding: ::encoding_rs::UTF_8,
             #[cfg(feature = "encoding")]
@@ -129,7 +196,7 @@ impl<B: BufRead> Reader<B> {
     /// [`Start`]: events/enum.Event.html#variant.Start
     /// [`End`]: events/enum.Event.html#variant.End
     pub fn expand_empty_elements(&mut self, val: bool) -> &mut Reader<B> {
-        self.expand_empty_elements = val;
+        self.config.expand_empty_elements = val;
         self
     }
 
@@ -138,11 +205,33 @@ impl<B: BufRead> Reader<B> {
     /// When set to `true`, all [`Text`] events are trimmed. If they are empty, no event will be
     /// pushed.
     ///
+    /// Text inside an element with an `xml:space="preserve"` attribute is never trimmed, unless a
+    /// descendant restores trimming with `xml:space="default"`.
+    ///
     /// (`false` by default)
     ///
     /// [`Text`]: events/enum.Event.html#variant.Text
     pub fn trim_text(&mut self, val: bool) -> &mut Reader<B> {
-        self.trim_text = val;
+        self.config.trim_text = val;
+        self
+    }
+
+    /// Changes which characters are removed around character data by [`trim_text`].
+    ///
+    /// By default, the XML whitespace characters (blank, new line, carriage return and tab) are
+    /// trimmed. With a custom `predicate`, the leading and trailing characters for which it
+    /// returns `true` are trimmed instead, e.g. `|c| c.is_whitespace() || c == '\u{A0}'` to also
+    /// trim non-breaking spaces. The text is decoded as UTF-8 for the predicate, and trimming
+    /// stops at the first byte which is not valid UTF-8.
+    ///
+    /// This only affects trimming: the markup, e.g. the whitespace separating attributes, is
+    /// still parsed as defined by the XML specification, and [`check_document_structure`] still
+    /// only accepts XML whitespace around the root element.
+    ///
+    /// [`trim_text`]: #method.trim_text
+    /// [`check_document_structure`]: #method.check_document_structure
+    pub fn whitespace_predicate(&mut self, predicate: fn(char) -> bool) -> &mut Reader<B> {
+        self.config.whitespace = Some(predicate);
         self
     }
 
@@ -158,7 +247,7 @@ impl<B: BufRead> Reader<B> {
     ///
     /// [`End`]: events/enum.Event.html#variant.End
     pub fn trim_markup_names_in_closing_tags(&mut self, val: bool) -> &mut Reader<B> {
-        self.trim_markup_names_in_closing_tags = val;
+        self.config.trim_markup_names_in_closing_tags = val;
         self
     }
 
@@ -176,7 +265,7 @@ impl<B: BufRead> Reader<B> {
     ///
     /// [`End`]: events/enum.Event.html#variant.End
     pub fn check_end_names(&mut self, val: bool) -> &mut Reader<B> {
-        self.check_end_names = val;
+        self.config.check_end_names = val;
         self
     }
 
@@ -191,13 +280,168 @@ impl<B: BufRead> Reader<B> {
     ///
     /// [`Comment`]: events/enum.Event.html#variant.Comment
     pub fn check_comments(&mut self, val: bool) -> &mut Reader<B> {
-        self.check_comments = val;
+        self.config.check_comments = val;
+        self
+    }
+
+    /// Changes whether element names should be validated.
+    ///
+    /// When set to `true`, the names of [`Start`], [`Empty`] and [`End`] events are checked
+    /// against the `Name` production of the XML specification: they must start with a letter,
+    /// `_` or `:`, followed by letters, digits, `-`, `.`, `_` or `:`. Otherwise, [`read_event`]
+    /// returns `Error::InvalidName` with the position of the first invalid byte in the document.
+    /// As names are checked as UTF-8, only enable it for documents in UTF-8 (or ASCII-only
+    /// names). Attribute names are checked by [`Attributes::check_names`].
+    ///
+    /// (`false` by default)
+    ///
+    /// [`Start`]: events/enum.Event.html#variant.Start
+    /// [`Empty`]: events/enum.Event.html#variant.Empty
+    /// [`End`]: events/enum.Event.html#variant.End
+    /// [`read_event`]: #method.read_event
+    /// [`Attributes::check_names`]: events/attributes/struct.Attributes.html#method.check_names
+    pub fn check_names(&mut self, val: bool) -> &mut Reader<B> {
+        self.config.check_names = val;
+        self
+    }
+
+    /// Changes whether comments should be skipped.
+    ///
+    /// When set to `true`, no [`Comment`] event is emitted: the reader reads past comments as if
+    /// they were not in the document. This also applies to helpers like [`read_to_end`] and
+    /// [`read_text`].
+    ///
+    /// (`false` by default)
+    ///
+    /// [`Comment`]: events/enum.Event.html#variant.Comment
+    /// [`read_to_end`]: #method.read_to_end
+    /// [`read_text`]: #method.read_text
+    pub fn ignore_comments(&mut self, val: bool) -> &mut Reader<B> {
+        self.config.ignore_comments = val;
+        self
+    }
+
+    /// Changes whether processing instructions should be skipped.
+    ///
+    /// When set to `true`, no [`PI`] or [`Decl`] event is emitted: the reader reads past
+    /// processing instructions and the XML declaration as if they were not in the document. The
+    /// encoding defined in the XML declaration is still used.
+    ///
+    /// (`false` by default)
+    ///
+    /// [`PI`]: events/enum.Event.html#variant.PI
+    /// [`Decl`]: events/enum.Event.html#variant.Decl
+    pub fn ignore_pi(&mut self, val: bool) -> &mut Reader<B> {
+        self.config.ignore_pi = val;
+        self
+    }
+
+    /// Changes whether the document type declaration should be skipped.
+    ///
+    /// When set to `true`, no [`DocType`] event is emitted.
+    ///
+    /// (`false` by default)
+    ///
+    /// [`DocType`]: events/enum.Event.html#variant.DocType
+    pub fn ignore_doctype(&mut self, val: bool) -> &mut Reader<B> {
+        self.config.ignore_doctype = val;
+        self
+    }
+
+    /// Changes whether unrecognized entities are kept as is when unescaping text.
+    ///
+    /// When set to `true`, the [`BytesText`] methods unescaping with this reader, like
+    /// [`unescape_and_decode`], keep an unknown entity (e.g. declared in a DTD) verbatim instead of
+    /// returning an `EscapeError`. Known entities and character references are still
+    /// unescaped.
+    ///
+    /// (`false` by default)
+    ///
+    /// [`BytesText`]: events/struct.BytesText.html
+    /// [`unescape_and_decode`]: events/struct.BytesText.html#method.unescape_and_decode
+    pub fn lenient_entities(&mut self, val: bool) -> &mut Reader<B> {
+        self.config.lenient_entities = val;
+        self
+    }
+
+    /// Changes whether the input must be a single document, with exactly one root element.
+    ///
+    /// When set to `true`, only whitespace, comments, processing instructions, the XML and the
+    /// document type declarations are accepted outside the root element. Otherwise,
+    /// [`read_event`] returns `Error::TextOutsideRoot` for text or CDATA, `Error::MultipleRoots`
+    /// for another element, and `Error::MissingRoot` at the end of an input without element.
+    /// When set to `false`, fragments and concatenated documents are accepted.
+    ///
+    /// (`false` by default)
+    ///
+    /// [`read_event`]: #method.read_event
+    pub fn check_document_structure(&mut self, val: bool) -> &mut Reader<B> {
+        self.config.check_document_structure = val;
+        self
+    }
+
+    /// Changes whether elements starting with `<!` which are not comments, CDATA sections or
+    /// document type declarations are read as text.
+    ///
+    /// When set to `true`, such an element, like `<!foo>`, is emitted as a [`Text`] event
+    /// containing the whole element escaped (`&lt;!foo&gt;`), so that it is written back
+    /// unchanged by a `Writer`. When set to `false`, [`read_event`] returns an
+    /// `Error::UnknownBang` with the content of the element.
+    ///
+    /// (`false` by default)
+    ///
+    /// [`Text`]: events/enum.Event.html#variant.Text
+    /// [`read_event`]: #method.read_event
+    pub fn unknown_bang_as_text(&mut self, val: bool) -> &mut Reader<B> {
+        self.config.unknown_bang_as_text = val;
+        self
+    }
+
+    /// Changes the maximum nesting depth of elements.
+    ///
+    /// When more than `val` elements are opened at the same time, [`read_event`] returns an
+    /// [`Error::MaxDepthExceeded`]. This protects consumers (recursive ones in particular) against
+    /// maliciously deeply nested documents.
+    ///
+    /// (unlimited by default)
+    ///
+    /// [`read_event`]: #method.read_event
+    /// [`Error::MaxDepthExceeded`]: ../enum.Error.html#variant.MaxDepthExceeded
+    pub fn max_depth(&mut self, val: usize) -> &mut Reader<B> {
+        self.config.max_depth = val;
+        self
+    }
+
+    /// Applies settings suitable for reading untrusted input.
+    ///
+    /// Currently this limits the nesting depth of elements to 1024 (see [`max_depth`]).
+    ///
+    /// [`max_depth`]: #method.max_depth
+    pub fn with_safe_defaults(&mut self) -> &mut Reader<B> {
+        self.max_depth(SAFE_MAX_DEPTH)
+    }
+
+    /// Gets the settings changed by the builder methods, e.g. to apply them to another reader
+    /// with [`set_config`].
+    ///
+    /// [`set_config`]: #method.set_config
+    pub fn config(&self) -> &ReaderConfig {
+        &self.config
+    }
+
+    /// Replaces all the settings changed by the builder methods with `config`.
+    pub fn set_config(&mut self, config: ReaderConfig) -> &mut Reader<B> {
+        self.config = config;
         self
     }
 
     /// Gets the current byte position in the input data.
     ///
-    /// Useful when debugging errors.
+    /// This is the position following the last read event, or the position of the last error
+    /// when it is known. Useful when debugging errors. See [`read_event_with_span`] to also get
+    /// the start of each event.
+    ///
+    /// [`read_event_with_span`]: #method.read_event_with_span
     pub fn buffer_position(&self) -> usize {
         // when internal state is Opened, we have actually read until '<',
         // which we don't want to show
@@ -209,67 +453,82 @@ impl<B: BufRead> Reader<B> {
     }
 
     /// private function to read until '<' is found
-    /// return a `Text` event
-    fn read_until_open<'a, 'b>(&'a mut self, buf: &'b mut Vec<u8>) -> Result<Event<'b>> {
+    /// return the range of the `Text` event in `buf`, or `None` if there is no event to emit
+    fn read_until_open(&mut self, buf: &mut Vec<u8>) -> Result<Option<Range<usize>>> {
         self.tag_state = TagState::Opened;
         let buf_start = buf.len();
         match read_until(&mut self.reader, b'<', buf, &mut self.buf_position) {
-            Ok(0) => Ok(Event::Eof),
+            Ok(0) => {
+                self.tag_state = TagState::Exit;
+                Ok(None)
+            }
             Ok(_) => {
-                let (start, len) = if self.trim_text {
-                    match buf.iter().skip(buf_start).position(|&b| !is_whitespace(b)) {
-                        Some(start) => (
-                            buf_start + start,
-                            buf.iter()
-                                .rposition(|&b| !is_whitespace(b))
-                                .map_or_else(|| buf.len(), |p| p + 1),
-                        ),
-                        None => return self.read_event(buf),
+                let (start, len) = if self.config.trim_text && !self.preserve_space() {
+                    match trim_whitespace(&buf[buf_start..], self.config.whitespace) {
+                        Some(text) => (buf_start + text.start, buf_start + text.end),
+                        None => return Ok(None),
                     }
                 } else {
                     (buf_start, buf.len())
                 };
-                Ok(Event::Text(BytesText::from_escaped(&buf[start..len])))
+                if self.config.trim_text && start == len {
+                    // empty text inside an `xml:space="preserve"` element
+                    return Ok(None);
+                }
+                Ok(Some(start..len))
             }
             Err(e) => Err(e),
         }
     }
 
     /// private function to read until '>' is found
-    fn read_until_close<'a, 'b>(&'a mut self, buf: &'b mut Vec<u8>) -> Result<Event<'b>> {
+    /// return the kind of markup read, or `None` if there is no event to emit
+    fn read_until_close(&mut self, buf: &mut Vec<u8>) -> Result<Option<Markup>> {
         self.tag_state = TagState::Closed;
 
         // need to read 1 character to decide whether pay special attention to attribute values
         let buf_start = buf.len();
-        let start = loop {
-            match self.reader.fill_buf() {
-                Ok(n) if n.is_empty() => return Ok(Event::Eof),
-                Ok(n) => {
-                    // We intentionally don't `consume()` the byte, otherwise we would have to
-                    // handle things like '<>' here already.
-                    break n[0];
-                }
-                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
-                Err(e) => return Err(Error::Io(e)),
+        // position of the byte following `<` in the document
+        let offset = self.buf_position;
+        let start = match fill_buf(&mut self.reader)? {
+            n if n.is_empty() => {
+                self.tag_state = TagState::Exit;
+                return Ok(None);
             }
+            // We intentionally don't `consume()` the byte, otherwise we would have to
+            // handle things like '<>' here already.
+            n => n[0],
         };
 
         if start != b'/' && start != b'!' && start != b'?' {
             match read_elem_until(&mut self.reader, b'>', buf, &mut self.buf_position) {
-                Ok(0) => Ok(Event::Eof),
-                Ok(_) => {
-                    // we already *know* that we are in this case
-                    self.read_start(&buf[buf_start..])
+                Ok(0) => {
+                    self.tag_state = TagState::Exit;
+                    Ok(None)
                 }
+                // we already *know* that we are in this case
+                Ok(_) => Ok(Some(Markup::Start(offset))),
                 Err(e) => Err(e),
             }
         } else {
             match read_until(&mut self.reader, b'>', buf, &mut self.buf_position) {
-                Ok(0) => Ok(Event::Eof),
+                Ok(0) => {
+                    self.tag_state = TagState::Exit;
+                    Ok(None)
+                }
                 Ok(_) => match start {
-                    b'/' => self.read_end(&buf[buf_start..]),
-                    b'!' => self.read_bang(buf_start, buf),
-                    b'?' => self.read_question_mark(&buf[buf_start..]),
+                    b'/' => Ok(Some(Markup::End)),
+                    b'!' => match self.read_bang(buf_start, buf)? {
+                        BangType::Comment if self.config.ignore_comments => Ok(None),
+                        BangType::DocType if self.config.ignore_doctype => Ok(None),
+                        bang => Ok(Some(Markup::Bang(bang))),
+                    },
+                    b'?' if self.config.ignore_pi => {
+                        // still parse the event, it may change the reader encoding
+                        self.read_question_mark(&buf[buf_start..], offset)?;
+                        Ok(None)
+                    }
+                    b'?' => Ok(Some(Markup::QuestionMark(offset))),
                     _ => unreachable!(
                         "We checked that `start` must be one of [/!?], was {:?} \
                              instead.",
@@ -282,12 +541,12 @@ impl<B: BufRead> Reader<B> {
     }
 
     /// reads `BytesElement` starting with a `/`,
-    /// if `self.check_end_names`, checks that element matches last opened element
+    /// if `self.config.check_end_names`, checks that element matches last opened element
     /// return `End` event
     fn read_end<'a, 'b>(&'a mut self, buf: &'b [u8]) -> Result<Event<'b>> {
         // XML standard permits whitespaces after the markup name in closing tags.
         // Let's strip them from the buffer before comparing tag names.
-        let name = if self.trim_markup_names_in_closing_tags {
+        let name = if self.config.trim_markup_names_in_closing_tags {
             if let Some(pos_end_name) = buf[1..].iter().rposition(|&b| !b.is_ascii_whitespace()) {
                 let (name, _) = buf[1..].split_at(pos_end_name + 1);
                 name
@@ -297,7 +556,14 @@ impl<B: BufRead> Reader<B> {
         } else {
             &buf[1..]
         };
-        if self.check_end_names {
+        if self.config.check_names {
+            if let Some(i) = invalid_name_position(name) {
+                // `buf` is followed by the `>` and starts after the `<`
+                let offset = self.buf_position - buf.len() - 1;
+                return Err(Error::InvalidName(offset + 1 + i));
+            }
+        }
+        if self.config.check_end_names {
             let mismatch_err = |expected: &[u8], found: &[u8], buf_position: &mut usize| {
                 *buf_position -= buf.len();
                 Err(Error::EndEventMismatch {
@@ -312,26 +578,26 @@ impl<B: BufRead> Reader<B> {
                         mismatch_err(expected, name, &mut self.buf_position)
                     } else {
                         self.opened_buffer.truncate(start);
+                        self.depth = self.depth.saturating_sub(1);
+                        self.close_xml_space();
                         Ok(Event::End(BytesEnd::borrowed(name)))
                     }
                 }
                 None => mismatch_err(b"", &buf[1..], &mut self.buf_position),
             }
         } else {
+            self.depth = self.depth.saturating_sub(1);
+            self.close_xml_space();
             Ok(Event::End(BytesEnd::borrowed(name)))
         }
     }
 
     /// reads `BytesElement` starting with a `!`,
-    /// return `Comment`, `CData` or `DocType` event
+    /// return whether it is a `Comment`, `CData` or `DocType` event
     ///
     /// Note: depending on the start of the Event, we may need to read more
     /// data, thus we need a mutable buffer
-    fn read_bang<'a, 'b>(
-        &'a mut self,
-        buf_start: usize,
-        buf: &'b mut Vec<u8>,
-    ) -> Result<Event<'b>> {
+    fn read_bang(&mut self, buf_start: usize, buf: &mut Vec<u8>) -> Result<BangType> {
         if buf[buf_start..].starts_with(b"!--") {
             while buf.len() < buf_start + 5 || !buf.ends_with(b"--") {
                 buf.push(b'>');
@@ -345,18 +611,17 @@ impl<B: BufRead> Reader<B> {
                 }
             }
             let len = buf.len();
-            if self.check_comments {
+            if self.config.check_comments {
                 // search if '--' not in comments
                 if let Some(p) = memchr::memchr_iter(b'-', &buf[buf_start + 3..len - 2])
-                    .position(|p| buf[buf_start + 3 + p + 1] == b'-')
+                    .find(|p| buf[buf_start + 3 + p + 1] == b'-')
                 {
-                    self.buf_position -= buf.len() - buf_start + p;
+                    // point to the first `-` (`buf` does not contain the closing `>`)
+                    self.buf_position -= len - (buf_start + 3 + p) + 1;
                     return Err(Error::UnexpectedToken("--".to_string()));
                 }
             }
-            Ok(Event::Comment(BytesText::from_escaped(
-                &buf[buf_start + 3..len - 2],
-            )))
+            Ok(BangType::Comment)
         } else if buf.len() >= buf_start + 8 {
             match &buf[buf_start + 1..buf_start + 8] {
                 b"[CDATA[" => {
@@ -371,9 +636,7 @@ impl<B: BufRead> Reader<B> {
                             Err(e) => return Err(e),
                         }
                     }
-                    Ok(Event::CData(BytesText::from_plain(
-                        &buf[buf_start + 8..buf.len() - 2],
-                    )))
+                    Ok(BangType::CData)
                 }
                 x if x.eq_ignore_ascii_case(b"DOCTYPE") => {
                     let mut count = buf.iter().skip(buf_start).filter(|&&b| b == b'<').count();
@@ -392,26 +655,34 @@ impl<B: BufRead> Reader<B> {
                             Err(e) => return Err(e),
                         }
                     }
-                    Ok(Event::DocType(BytesText::from_escaped(
-                        &buf[buf_start + 8..buf.len()],
-                    )))
+                    Ok(BangType::DocType)
                 }
-                _ => Err(Error::UnexpectedBang),
+                _ => self.unknown_bang(&buf[buf_start..]),
             }
         } else {
-            self.buf_position -= buf.len() - buf_start;
-            Err(Error::UnexpectedBang)
+            self.unknown_bang(&buf[buf_start..])
         }
     }
 
+    /// Accepts the unknown `<!...>` element read into `buf` if `unknown_bang_as_text`, or
+    /// returns an error pointing to its `<`
+    fn unknown_bang(&mut self, buf: &[u8]) -> Result<BangType> {
+        if self.config.unknown_bang_as_text {
+            return Ok(BangType::Unknown);
+        }
+        self.buf_position -= buf.len() + 2;
+        Err(Error::UnknownBang(String::from_utf8_lossy(&buf[1..]).into_owned()))
+    }
+
     /// reads `BytesElement` starting with a `?`,
     /// return `Decl` or `PI` event
     #[cfg(feature = "encoding")]
-    fn read_question_mark<'a, 'b>(&'a mut self, buf: &'b [u8]) -> Result<Event<'b>> {
+    fn read_question_mark<'a, 'b>(&'a mut self, buf: &'b [u8], offset: usize) -> Result<Event<'b>> {
         let len = buf.len();
         if len > 2 && buf[len - 1] == b'?' {
             if len > 5 && &buf[1..4] == b"xml" && is_whitespace(buf[4]) {
-                let event = BytesDecl::from_start(BytesStart::borrowed(&buf[1..len - 1], 3));
+                let start = BytesStart::borrowed(&buf[1..len - 1], 3).with_offset(offset + 1);
+                let event = BytesDecl::from_start(start);
                 // Try getting encoding from the declaration event
                 if let Some(enc) = event.encoder() {
                     self.encoding = enc;
@@ -430,11 +701,12 @@ impl<B: BufRead> Reader<B> {
     /// reads `BytesElement` starting with a `?`,
     /// return `Decl` or `PI` event
     #[cfg(not(feature = "encoding"))]
-    fn read_question_mark<'a, 'b>(&'a mut self, buf: &'b [u8]) -> Result<Event<'b>> {
+    fn read_question_mark<'a, 'b>(&'a mut self, buf: &'b [u8], offset: usize) -> Result<Event<'b>> {
         let len = buf.len();
         if len > 2 && buf[len - 1] == b'?' {
             if len > 5 && &buf[1..4] == b"xml" && is_whitespace(buf[4]) {
-                let event = BytesDecl::from_start(BytesStart::borrowed(&buf[1..len - 1], 3));
+                let start = BytesStart::borrowed(&buf[1..len - 1], 3).with_offset(offset + 1);
+                let event = BytesDecl::from_start(start);
                 Ok(Event::Decl(event))
             } else {
                 Ok(Event::PI(BytesText::from_escaped(&buf[1..len - 1])))
@@ -448,6 +720,7 @@ impl<B: BufRead> Reader<B> {
     #[inline]
     fn close_expanded_empty(&mut self) -> Result<Event<'static>> {
         self.tag_state = TagState::Closed;
+        self.depth -= 1;
         let name = self
             .opened_buffer
             .split_off(self.opened_starts.pop().unwrap());
@@ -456,26 +729,84 @@ impl<B: BufRead> Reader<B> {
 
     /// reads `BytesElement` starting with any character except `/`, `!` or ``?`
     /// return `Start` or `Empty` event
-    fn read_start<'a, 'b>(&'a mut self, buf: &'b [u8]) -> Result<Event<'b>> {
+    fn read_start<'a, 'b>(&'a mut self, buf: &'b [u8], offset: usize) -> Result<Event<'b>> {
         // TODO: do this directly when reading bufreader ...
         let len = buf.len();
         let name_end = buf.iter().position(|&b| is_whitespace(b)).unwrap_or(len);
-        if let Some(&b'/') = buf.last() {
+        let is_empty = buf.last() == Some(&b'/');
+        if self.config.check_names {
+            let name = &buf[..name_end.min(if is_empty { len - 1 } else { len })];
+            if let Some(i) = invalid_name_position(name) {
+                return Err(Error::InvalidName(offset + i));
+            }
+        }
+        if !is_empty || self.config.expand_empty_elements {
+            if self.depth >= self.config.max_depth {
+                // point to the `<` of the offending tag
+                self.buf_position -= len + 2;
+                return Err(Error::MaxDepthExceeded(self.config.max_depth));
+            }
+            self.depth += 1;
+        }
+        if is_empty {
             let end = if name_end < len { name_end } else { len - 1 };
-            if self.expand_empty_elements {
+            if self.config.expand_empty_elements {
                 self.tag_state = TagState::Empty;
                 self.opened_starts.push(self.opened_buffer.len());
                 self.opened_buffer.extend(&buf[..end]);
-                Ok(Event::Start(BytesStart::borrowed(&buf[..len - 1], end)))
+                let start = BytesStart::borrowed(&buf[..len - 1], end).with_offset(offset);
+                Ok(Event::Start(start))
             } else {
-                Ok(Event::Empty(BytesStart::borrowed(&buf[..len - 1], end)))
+                let start = BytesStart::borrowed(&buf[..len - 1], end).with_offset(offset);
+                Ok(Event::Empty(start))
             }
         } else {
-            if self.check_end_names {
+            if self.config.check_end_names {
                 self.opened_starts.push(self.opened_buffer.len());
                 self.opened_buffer.extend(&buf[..name_end]);
             }
-            Ok(Event::Start(BytesStart::borrowed(buf, name_end)))
+            let start = BytesStart::borrowed(buf, name_end).with_offset(offset);
+            if self.config.trim_text {
+                self.open_xml_space(&start);
+            }
+            Ok(Event::Start(start))
+        }
+    }
+
+    /// enters the `xml:space` scope of the element just opened, if it has this attribute
+    fn open_xml_space(&mut self, start: &BytesStart) {
+        if !start.windows(9).any(|w| w == b"xml:space") {
+            return;
+        }
+        let mut attributes = start.attributes();
+        attributes.with_checks(false);
+        let value = attributes
+            .filter_map(|a| a.ok())
+            .find(|a| a.key == b"xml:space")
+            .map(|a| a.value);
+        match value.as_deref() {
+            Some(b"preserve") => self.xml_space.push((self.depth, true)),
+            Some(b"default") => self.xml_space.push((self.depth, false)),
+            _ => (),
+        }
+    }
+
+    /// leaves the `xml:space` scope of the element just closed, if any
+    #[inline]
+    fn close_xml_space(&mut self) {
+        if let Some(&(depth, _)) = self.xml_space.last() {
+            if depth > self.depth {
+                self.xml_space.pop();
+            }
+        }
+    }
+
+    /// whether the current element is in an `xml:space="preserve"` scope
+    #[inline]
+    fn preserve_space(&self) -> bool {
+        match self.xml_space.last() {
+            Some(&(_, preserve)) => preserve,
+            None => false,
         }
     }
 
@@ -494,6 +825,9 @@ impl<B: BufRead> Reader<B> {
     ///   end of your loop).
     /// - Reserve the buffer length if you know the file size (using `Vec::with_capacity`).
     ///
+    /// Reading stops at the end of the input data, or at the first error: all the following calls
+    /// return `Event::Eof`.
+    ///
     /// # Examples
     ///
     /// ```
@@ -523,19 +857,287 @@ impl<B: BufRead> Reader<B> {
     /// println!("Text events: {:?}", txt);
     /// ```
     pub fn read_event<'a, 'b>(&'a mut self, buf: &'b mut Vec<u8>) -> Result<Event<'b>> {
-        let event = match self.tag_state {
-            TagState::Opened => self.read_until_close(buf),
-            TagState::Closed => self.read_until_open(buf),
-            TagState::Empty => self.close_expanded_empty(),
-            TagState::Exit => return Ok(Event::Eof),
-        };
+        self.read_event_with_span(buf).map(|(event, _)| event)
+    }
+
+    /// Reads the next event like [`read_event`], with the range of its bytes in the input data.
+    ///
+    /// The range covers the whole markup of the event, including delimiters such as `<` and `>`,
+    /// or only the trimmed content of `Text` events if [`trim_text`] is set. It is empty, at the
+    /// position following the element, for the `End` event of an [`expanded`] `Empty` element,
+    /// and at the end of the input data for `Eof`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use quick_xml::Reader;
+    /// use quick_xml::events::Event;
+    ///
+    /// let xml = "<a x='1'> text <b/></a>";
+    /// let mut reader = Reader::from_str(xml);
+    /// reader.trim_text(true).expand_empty_elements(true);
+    /// let mut buf = Vec::new();
+    /// let mut spans = Vec::new();
+    /// loop {
+    ///     match reader.read_event_with_span(&mut buf) {
+    ///         Ok((Event::Eof, _)) => break,
+    ///         Ok((_, span)) => spans.push(&xml[span]),
+    ///         Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
+    ///     }
+    ///     buf.clear();
+    /// }
+    /// assert_eq!(spans, vec!["<a x='1'>", "text", "<b/>", "", "</a>"]);
+    /// ```
+    ///
+    /// [`read_event`]: #method.read_event
+    /// [`trim_text`]: #method.trim_text
+    /// [`expanded`]: #method.expand_empty_elements
+    pub fn read_event_with_span<'b>(
+        &mut self,
+        buf: &'b mut Vec<u8>,
+    ) -> Result<(Event<'b>, Range<usize>)> {
+        self.read_event_observed(buf, None)
+    }
+
+    /// Reads the next event like `read_event_with_span`, passing its source to `on_raw`
+    fn read_event_observed<'b>(
+        &mut self,
+        buf: &'b mut Vec<u8>,
+        on_raw: Option<RawCallback>,
+    ) -> Result<(Event<'b>, Range<usize>)> {
+        if let Some(peeked) = self.peeked.take() {
+            return Ok(peeked);
+        }
+        let top_level = self.depth == 0 && !matches!(self.tag_state, TagState::Exit);
+        let mut event = self.read_next_event(buf, on_raw);
+        if self.config.check_document_structure && top_level {
+            let error = match event {
+                Ok((ref e, ref span)) => self.check_top_level(e, span.start).err(),
+                Err(_) => None,
+            };
+            if let Some(error) = error {
+                event = Err(error);
+            }
+        }
         match event {
-            Err(_) | Ok(Event::Eof) => self.tag_state = TagState::Exit,
+            Err(_) | Ok((Event::Eof, _)) => self.tag_state = TagState::Exit,
             _ => {}
         }
         event
     }
 
+    /// Checks that an event read outside the root element is allowed by
+    /// `check_document_structure`
+    fn check_top_level(&mut self, event: &Event, position: usize) -> Result<()> {
+        let error = match *event {
+            Event::Start(_) | Event::Empty(_) if self.root_read => Error::MultipleRoots(position),
+            Event::Start(_) | Event::Empty(_) => {
+                self.root_read = true;
+                return Ok(());
+            }
+            Event::Text(ref e) if !e.is_whitespace() => {
+                Error::TextOutsideRoot(position)
+            }
+            Event::CData(_) => Error::TextOutsideRoot(position),
+            Event::Eof if !self.root_read => Error::MissingRoot,
+            _ => return Ok(()),
+        };
+        self.buf_position = position;
+        Err(error)
+    }
+
+    /// Returns a reader of the events of this `Reader`, calling `on_raw` with the source bytes
+    /// of each event before returning it.
+    ///
+    /// `on_raw` is called in order with every part of the input data consumed by the `Reader`,
+    /// so that their concatenation is the input data: the source of each event (see
+    /// [`read_event_with_span`]) with the whitespace trimmed from `Text` events, and the content
+    /// skipped between events (e.g. whitespace-only text or ignored comments). The bytes are
+    /// passed from `buf`, without being copied, which only costs the delimiters of the markup
+    /// kept in it.
+    ///
+    /// Events peeked with [`peek_event`] before are returned without calling `on_raw`, and the
+    /// source of an event which is an error is not passed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use quick_xml::Reader;
+    /// use quick_xml::events::Event;
+    ///
+    /// let mut reader = Reader::from_str("<a x='1'> text<!-- > --></a>");
+    /// reader.trim_text(true).ignore_comments(true);
+    /// let mut sources = Vec::new();
+    /// let mut observed = reader.on_raw(|raw| sources.push(raw.to_vec()));
+    /// let mut buf = Vec::new();
+    /// while !matches!(observed.read_event(&mut buf)?, Event::Eof) {
+    ///     buf.clear();
+    /// }
+    /// assert_eq!(
+    ///     sources,
+    ///     vec![&b"<a x='1'>"[..], b" text", b"<!-- > -->", b"</a>"]
+    /// );
+    /// # Ok::<(), quick_xml::Error>(())
+    /// ```
+    ///
+    /// [`read_event_with_span`]: #method.read_event_with_span
+    /// [`peek_event`]: #method.peek_event
+    pub fn on_raw<F: FnMut(&[u8])>(&mut self, on_raw: F) -> RawObserver<'_, B, F> {
+        RawObserver {
+            reader: self,
+            on_raw,
+        }
+    }
+
+    /// Reads the next event like [`read_event`], without a buffer from the caller.
+    ///
+    /// This is the convenient but allocating counterpart of `read_event`: the event is read into
+    /// a buffer owned by the `Reader` and reused between calls, then converted into an owned
+    /// event, which copies its content. Prefer `read_event` when performance matters.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use quick_xml::Reader;
+    /// use quick_xml::events::Event;
+    ///
+    /// let mut reader = Reader::from_str("<a>text</a>");
+    /// reader.trim_text(true);
+    /// let mut events = Vec::new();
+    /// loop {
+    ///     match reader.read_event_unbuffered() {
+    ///         Ok(Event::Eof) => break,
+    ///         Ok(e) => events.push(e),
+    ///         Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
+    ///     }
+    /// }
+    /// assert_eq!(events.len(), 3);
+    /// ```
+    ///
+    /// [`read_event`]: #method.read_event
+    pub fn read_event_unbuffered(&mut self) -> Result<Event<'static>> {
+        let mut buf = mem::take(&mut self.unbuffered);
+        buf.clear();
+        let event = self.read_event(&mut buf).map(Event::into_owned);
+        self.unbuffered = buf;
+        event
+    }
+
+    /// Reads the next event without consuming it: the next call to `read_event` returns it.
+    ///
+    /// Calling `peek_event` again before `read_event` returns the same event. As the event must
+    /// outlive `buf`, it is converted into an owned event, which allocates. Errors are not
+    /// cached and `buffer_position` already points after the peeked event.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use quick_xml::Reader;
+    /// use quick_xml::events::Event;
+    ///
+    /// let mut reader = Reader::from_str("<a/><b/>");
+    /// reader.trim_text(true);
+    /// let mut buf = Vec::new();
+    /// match reader.peek_event(&mut buf) {
+    ///     Ok(&Event::Empty(ref e)) => assert_eq!(e.name(), b"a"),
+    ///     e => panic!("Unexpected event {:?}", e),
+    /// }
+    /// match reader.read_event(&mut buf) {
+    ///     Ok(Event::Empty(ref e)) => assert_eq!(e.name(), b"a"),
+    ///     e => panic!("Unexpected event {:?}", e),
+    /// }
+    /// ```
+    pub fn peek_event(&mut self, buf: &mut Vec<u8>) -> Result<&Event<'static>> {
+        let peeked = match self.peeked.take() {
+            Some(peeked) => peeked,
+            None => {
+                let (event, span) = self.read_event_with_span(buf)?;
+                (event.into_owned(), span)
+            }
+        };
+        Ok(&self.peeked.get_or_insert(peeked).0)
+    }
+
+    /// Reads until the next event that must be emitted
+    ///
+    /// Events are only created once we know they must be emitted, so that skipped content can be
+    /// removed from `buf` before reading further.
+    fn read_next_event<'a, 'b>(
+        &'a mut self,
+        buf: &'b mut Vec<u8>,
+        mut on_raw: Option<RawCallback>,
+    ) -> Result<(Event<'b>, Range<usize>)> {
+        loop {
+            let buf_start = buf.len();
+            let position = self.buf_position;
+            match self.tag_state {
+                TagState::Opened => {
+                    // keep the delimiters of the markup in `buf` to pass its whole source
+                    let delimiters = on_raw.is_some() as usize;
+                    if on_raw.is_some() {
+                        buf.push(b'<');
+                    }
+                    let markup = self.read_until_close(buf)?;
+                    if let TagState::Exit = self.tag_state {
+                        // nothing follows the `<`, so there is no markup to pass
+                        buf.truncate(buf_start);
+                        continue;
+                    }
+                    if on_raw.is_some() {
+                        buf.push(b'>');
+                    }
+                    if let Some(markup) = markup {
+                        let content = &buf[buf_start + delimiters..buf.len() - delimiters];
+                        let event = self.read_markup(markup, content)?;
+                        if let Some(on_raw) = on_raw.as_mut() {
+                            on_raw(&buf[buf_start..]);
+                        }
+                        // the `<` has already been read with the previous event
+                        return Ok((event, position - 1..self.buf_position));
+                    }
+                    // ignored markup
+                    if let Some(on_raw) = on_raw.as_mut() {
+                        on_raw(&buf[buf_start..]);
+                    }
+                }
+                TagState::Closed => {
+                    let text = self.read_until_open(buf)?;
+                    // the whitespace trimmed from the text is passed with it
+                    if let Some(on_raw) = on_raw.as_mut() {
+                        if buf.len() > buf_start {
+                            on_raw(&buf[buf_start..]);
+                        }
+                    }
+                    if let Some(text) = text {
+                        let span =
+                            position + text.start - buf_start..position + text.end - buf_start;
+                        return Ok((Event::Text(BytesText::from_escaped(&buf[text])), span));
+                    }
+                }
+                TagState::Empty => {
+                    let event = self.close_expanded_empty()?;
+                    if let Some(on_raw) = on_raw.as_mut() {
+                        on_raw(b"");
+                    }
+                    return Ok((event, position..position));
+                }
+                TagState::Exit => return Ok((Event::Eof, position..position)),
+            }
+            // skipped content, already passed to `on_raw`
+            buf.truncate(buf_start);
+        }
+    }
+
+    /// Creates the event corresponding to the markup read by `read_until_close`
+    fn read_markup<'a, 'b>(&'a mut self, markup: Markup, buf: &'b [u8]) -> Result<Event<'b>> {
+        match markup {
+            Markup::Start(offset) => self.read_start(buf, offset),
+            Markup::End => self.read_end(buf),
+            Markup::Bang(bang) => Ok(bang.to_event(buf)),
+            Markup::QuestionMark(offset) => self.read_question_mark(buf, offset),
+        }
+    }
+
     /// Resolves a potentially qualified **event name** into (namespace name, local name).
     ///
     /// *Qualified* attribute names have the form `prefix:local-name` where the`prefix` is defined
@@ -570,6 +1172,24 @@ impl<B: BufRead> Reader<B> {
             .resolve_namespace(qname, namespace_buffer, false)
     }
 
+    /// Resolves a namespace **prefix** into the namespace name bound to it in the current scope.
+    ///
+    /// An empty `prefix` looks up the current *default namespace*. Returns `None` if the prefix
+    /// is not bound, or if it was unbound with an empty declaration such as `xmlns=""`.
+    ///
+    /// Only declarations seen through [`read_namespaced_event`] are known, and they stay in scope
+    /// until the `End` event of the declaring element has been read.
+    ///
+    /// [`read_namespaced_event`]: #method.read_namespaced_event
+    #[inline]
+    pub fn resolve_namespace<'c>(
+        &self,
+        prefix: &[u8],
+        namespace_buffer: &'c [u8],
+    ) -> Option<&'c [u8]> {
+        self.ns_buffer.find_prefix_value(prefix, namespace_buffer)
+    }
+
     /// Reads the next event and resolves its namespace (if applicable).
     ///
     /// # Examples
@@ -616,9 +1236,22 @@ impl<B: BufRead> Reader<B> {
         &'a mut self,
         buf: &'b mut Vec<u8>,
         namespace_buffer: &'c mut Vec<u8>,
+    ) -> Result<(Option<&'c [u8]>, Event<'b>)> {
+        self.read_namespaced_event_observed(buf, namespace_buffer, None)
+    }
+
+    /// Reads the next event like `read_namespaced_event`, passing its source to `on_raw`
+    fn read_namespaced_event_observed<'b, 'c>(
+        &mut self,
+        buf: &'b mut Vec<u8>,
+        namespace_buffer: &'c mut Vec<u8>,
+        on_raw: Option<RawCallback>,
     ) -> Result<(Option<&'c [u8]>, Event<'b>)> {
         self.ns_buffer.pop_empty_namespaces(namespace_buffer);
-        match self.read_event(buf) {
+        match self
+            .read_event_observed(buf, on_raw)
+            .map(|(event, _)| event)
+        {
             Ok(Event::Eof) => Ok((None, Event::Eof)),
             Ok(Event::Start(e)) => {
                 self.ns_buffer.push_new_namespaces(&e, namespace_buffer);
@@ -683,6 +1316,15 @@ impl<B: BufRead> Reader<B> {
         self.encoding.decode(bytes).0
     }
 
+    /// Decodes a slice using the encoding specified in the XML declaration, like [`decode`], but
+    /// returns `Error::UndecodableBytes` on malformed sequences instead of replacing them.
+    ///
+    /// [`decode`]: #method.decode
+    #[cfg(feature = "encoding")]
+    pub fn decode_strict<'c>(&self, bytes: &'c [u8]) -> Result<Cow<'c, str>> {
+        self.decoder().decode_strict(bytes)
+    }
+
     /// Decodes a UTF8 slice without BOM (Byte order mark) regardless of XML declaration.
     ///
     /// Decode `bytes` without BOM and with malformed sequences replaced with the
@@ -742,6 +1384,15 @@ impl<B: BufRead> Reader<B> {
         from_utf8(bytes).map_err(Error::Utf8)
     }
 
+    /// Unescapes text, keeping unknown entities if `lenient_entities` is set
+    pub(crate) fn unescape_text<'c>(&self, text: &'c [u8]) -> Result<Cow<'c, [u8]>> {
+        if self.config.lenient_entities {
+            unescape_lenient(text).map_err(Error::EscapeError)
+        } else {
+            unescape(text).map_err(Error::EscapeError)
+        }
+    }
+
     /// Get utf8 decoder
     #[cfg(feature = "encoding")]
     pub fn decoder(&self) -> Decoder {
@@ -760,19 +1411,70 @@ impl<B: BufRead> Reader<B> {
     ///
     /// Manages nested cases where parent and child elements have the same name
     pub fn read_to_end<K: AsRef<[u8]>>(&mut self, end: K, buf: &mut Vec<u8>) -> Result<()> {
+        self.read_to_end_with_span(end, buf).map(|_| ())
+    }
+
+    /// Reads until end element is found like [`read_to_end`], returning the range of the skipped
+    /// content in the input data, between the start tag already read and the end tag.
+    ///
+    /// If the input data is available as a slice, this gives access to the raw inner XML of an
+    /// element without copying it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use quick_xml::Reader;
+    /// use quick_xml::events::Event;
+    ///
+    /// let xml = "<outer><a> <a>x</a><b/> </a></outer>";
+    /// let mut reader = Reader::from_str(xml);
+    /// reader.trim_text(true);
+    /// let mut buf = Vec::new();
+    /// loop {
+    ///     match reader.read_event(&mut buf) {
+    ///         Ok(Event::Start(ref e)) if e.name() == b"a" => break,
+    ///         Ok(_) => (),
+    ///         e => panic!("Unexpected event {:?}", e),
+    ///     }
+    /// }
+    /// let span = reader.read_to_end_with_span(b"a", &mut buf).unwrap();
+    /// assert_eq!(&xml[span], " <a>x</a><b/> ");
+    /// ```
+    ///
+    /// [`read_to_end`]: #method.read_to_end
+    pub fn read_to_end_with_span<K: AsRef<[u8]>>(
+        &mut self,
+        end: K,
+        buf: &mut Vec<u8>,
+    ) -> Result<Range<usize>> {
+        self.read_to_end_observed(end.as_ref(), buf, None)
+    }
+
+    /// Reads until end element is found like `read_to_end_with_span`, passing the source of the
+    /// skipped events to `on_raw`
+    fn read_to_end_observed(
+        &mut self,
+        end: &[u8],
+        buf: &mut Vec<u8>,
+        mut on_raw: Option<RawCallback>,
+    ) -> Result<Range<usize>> {
+        let start = match self.peeked {
+            Some((_, ref span)) => span.start,
+            None => self.buf_position,
+        };
         let mut depth = 0;
-        let end = end.as_ref();
         loop {
-            match self.read_event(buf) {
-                Ok(Event::End(ref e)) if e.name() == end => {
+            let on_raw = on_raw.as_mut().map(|f| &mut **f as RawCallback);
+            match self.read_event_observed(buf, on_raw) {
+                Ok((Event::End(ref e), ref span)) if e.name() == end => {
                     if depth == 0 {
-                        return Ok(());
+                        return Ok(start..span.start);
                     }
                     depth -= 1;
                 }
-                Ok(Event::Start(ref e)) if e.name() == end => depth += 1,
+                Ok((Event::Start(ref e), _)) if e.name() == end => depth += 1,
                 Err(e) => return Err(e),
-                Ok(Event::Eof) => {
+                Ok((Event::Eof, _)) => {
                     return Err(Error::UnexpectedEof(format!("</{:?}>", from_utf8(end))));
                 }
                 _ => (),
@@ -816,14 +1518,25 @@ impl<B: BufRead> Reader<B> {
     /// [`Text`]: events/enum.Event.html#variant.Text
     /// [`End`]: events/enum.Event.html#variant.End
     pub fn read_text<K: AsRef<[u8]>>(&mut self, end: K, buf: &mut Vec<u8>) -> Result<String> {
-        let s = match self.read_event(buf) {
+        self.read_text_observed(end.as_ref(), buf, None)
+    }
+
+    /// Reads optional text like `read_text`, passing the source of the read events to `on_raw`
+    fn read_text_observed(
+        &mut self,
+        end: &[u8],
+        buf: &mut Vec<u8>,
+        mut on_raw: Option<RawCallback>,
+    ) -> Result<String> {
+        let observed = on_raw.as_mut().map(|f| &mut **f as RawCallback);
+        let s = match self.read_event_observed(buf, observed).map(|(event, _)| event) {
             Ok(Event::Text(e)) => e.unescape_and_decode(self),
-            Ok(Event::End(ref e)) if e.name() == end.as_ref() => return Ok("".to_string()),
+            Ok(Event::End(ref e)) if e.name() == end => return Ok("".to_string()),
             Err(e) => return Err(e),
             Ok(Event::Eof) => return Err(Error::UnexpectedEof("Text".to_string())),
             _ => return Err(Error::TextNotFound),
         };
-        self.read_to_end(end, buf)?;
+        self.read_to_end_observed(end, buf, on_raw)?;
         s
     }
 
@@ -884,8 +1597,13 @@ impl<B: BufRead> Reader<B> {
     }
 }
 
+#[cfg(feature = "std")]
 impl Reader<BufReader<File>> {
     /// Creates an XML reader from a file path.
+    ///
+    /// The file is wrapped in a `BufReader`. Errors opening it are returned as `Error::Io`.
+    /// With the `encoding` feature, the encoding is detected from the BOM or the XML declaration
+    /// while reading, as for any other `Reader`.
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Reader<BufReader<File>>> {
         let file = File::open(path).map_err(Error::Io)?;
         let reader = BufReader::new(file);
@@ -900,6 +1618,452 @@ impl<'a> Reader<&'a [u8]> {
     }
 }
 
+/// The settings of a [`Reader`], to save and restore them or to share them between readers.
+///
+/// Each option has the same meaning and default value as the `Reader` method of the same name,
+/// which changes the option in [`Reader::config`].
+///
+/// # Example
+///
+/// ```rust
+/// use quick_xml::events::Event;
+/// use quick_xml::{Reader, ReaderConfig};
+///
+/// let mut config = ReaderConfig::new();
+/// config.trim_text(true).expand_empty_elements(true);
+/// let mut reader = Reader::from_str("<a> <b/> </a>");
+/// reader.set_config(config.clone());
+/// let mut buf = Vec::new();
+/// reader.read_event(&mut buf)?;
+/// match reader.read_event(&mut buf)? {
+///     Event::Start(ref e) => assert_eq!(e.name(), b"b"),
+///     e => panic!("expecting Start, found {:?}", e),
+/// }
+///
+/// // the settings of a reader can be copied to another one
+/// let mut other = Reader::from_str("<c/>");
+/// other.set_config(reader.config().clone());
+/// # Ok::<(), quick_xml::Error>(())
+/// ```
+///
+/// [`Reader`]: struct.Reader.html
+/// [`Reader::config`]: struct.Reader.html#method.config
+#[derive(Clone, Debug)]
+pub struct ReaderConfig {
+    /// expand empty element into an opening and closing element
+    expand_empty_elements: bool,
+    /// trims Text events, skip the element if text is empty
+    trim_text: bool,
+    /// trims trailing whitespaces from markup names in closing tags `</a >`
+    trim_markup_names_in_closing_tags: bool,
+    /// check if End nodes match last Start node
+    check_end_names: bool,
+    /// check if comments contains `--` (false per default)
+    check_comments: bool,
+    /// check that element names match the XML `Name` production
+    check_names: bool,
+    /// do not emit `Comment` events
+    ignore_comments: bool,
+    /// do not emit `PI` and `Decl` events
+    ignore_pi: bool,
+    /// do not emit `DocType` events
+    ignore_doctype: bool,
+    /// keep unknown entities as is when unescaping text
+    lenient_entities: bool,
+    /// check that the document has a single root element, without text around it
+    check_document_structure: bool,
+    /// emit unknown `<!...>` elements as `Text` events instead of returning an error
+    unknown_bang_as_text: bool,
+    /// characters trimmed by `trim_text`, instead of the XML whitespace bytes
+    whitespace: Option<fn(char) -> bool>,
+    /// maximum number of opened elements before returning an error
+    max_depth: usize,
+}
+
+impl Default for ReaderConfig {
+    fn default() -> Self {
+        ReaderConfig {
+            expand_empty_elements: false,
+            trim_text: false,
+            trim_markup_names_in_closing_tags: true,
+            check_end_names: true,
+            check_comments: false,
+            check_names: false,
+            ignore_comments: false,
+            ignore_pi: false,
+            ignore_doctype: false,
+            lenient_entities: false,
+            check_document_structure: false,
+            unknown_bang_as_text: false,
+            whitespace: None,
+            max_depth: usize::MAX,
+        }
+    }
+}
+
+impl ReaderConfig {
+    /// Creates the default configuration, the one of `Reader::from_reader`.
+    pub fn new() -> ReaderConfig {
+        ReaderConfig::default()
+    }
+
+    /// See `Reader::expand_empty_elements`.
+    pub fn expand_empty_elements(&mut self, val: bool) -> &mut ReaderConfig {
+        self.expand_empty_elements = val;
+        self
+    }
+
+    /// See `Reader::trim_text`.
+    pub fn trim_text(&mut self, val: bool) -> &mut ReaderConfig {
+        self.trim_text = val;
+        self
+    }
+
+    /// See `Reader::whitespace_predicate`.
+    pub fn whitespace_predicate(&mut self, predicate: fn(char) -> bool) -> &mut ReaderConfig {
+        self.whitespace = Some(predicate);
+        self
+    }
+
+    /// See `Reader::trim_markup_names_in_closing_tags`.
+    pub fn trim_markup_names_in_closing_tags(&mut self, val: bool) -> &mut ReaderConfig {
+        self.trim_markup_names_in_closing_tags = val;
+        self
+    }
+
+    /// See `Reader::check_end_names`.
+    pub fn check_end_names(&mut self, val: bool) -> &mut ReaderConfig {
+        self.check_end_names = val;
+        self
+    }
+
+    /// See `Reader::check_comments`.
+    pub fn check_comments(&mut self, val: bool) -> &mut ReaderConfig {
+        self.check_comments = val;
+        self
+    }
+
+    /// See `Reader::check_names`.
+    pub fn check_names(&mut self, val: bool) -> &mut ReaderConfig {
+        self.check_names = val;
+        self
+    }
+
+    /// See `Reader::ignore_comments`.
+    pub fn ignore_comments(&mut self, val: bool) -> &mut ReaderConfig {
+        self.ignore_comments = val;
+        self
+    }
+
+    /// See `Reader::ignore_pi`.
+    pub fn ignore_pi(&mut self, val: bool) -> &mut ReaderConfig {
+        self.ignore_pi = val;
+        self
+    }
+
+    /// See `Reader::ignore_doctype`.
+    pub fn ignore_doctype(&mut self, val: bool) -> &mut ReaderConfig {
+        self.ignore_doctype = val;
+        self
+    }
+
+    /// See `Reader::lenient_entities`.
+    pub fn lenient_entities(&mut self, val: bool) -> &mut ReaderConfig {
+        self.lenient_entities = val;
+        self
+    }
+
+    /// See `Reader::check_document_structure`.
+    pub fn check_document_structure(&mut self, val: bool) -> &mut ReaderConfig {
+        self.check_document_structure = val;
+        self
+    }
+
+    /// See `Reader::unknown_bang_as_text`.
+    pub fn unknown_bang_as_text(&mut self, val: bool) -> &mut ReaderConfig {
+        self.unknown_bang_as_text = val;
+        self
+    }
+
+    /// See `Reader::max_depth`.
+    pub fn max_depth(&mut self, val: usize) -> &mut ReaderConfig {
+        self.max_depth = val;
+        self
+    }
+
+    /// See `Reader::with_safe_defaults`.
+    pub fn with_safe_defaults(&mut self) -> &mut ReaderConfig {
+        self.max_depth(SAFE_MAX_DEPTH)
+    }
+}
+
+/// Function called with each part of the input data consumed by a `RawObserver`
+type RawCallback<'f> = &'f mut dyn FnMut(&[u8]);
+
+/// A reader of events calling a function with the source bytes of each event, created by
+/// [`Reader::on_raw`].
+///
+/// [`Reader::on_raw`]: struct.Reader.html#method.on_raw
+pub struct RawObserver<'r, B: BufRead, F> {
+    reader: &'r mut Reader<B>,
+    on_raw: F,
+}
+
+impl<'r, B: BufRead, F: FnMut(&[u8])> RawObserver<'r, B, F> {
+    /// Reads the next event like [`Reader::read_event`].
+    ///
+    /// [`Reader::read_event`]: struct.Reader.html#method.read_event
+    pub fn read_event<'b>(&mut self, buf: &'b mut Vec<u8>) -> Result<Event<'b>> {
+        self.read_event_with_span(buf).map(|(event, _)| event)
+    }
+
+    /// Reads the next event with its span like [`Reader::read_event_with_span`].
+    ///
+    /// [`Reader::read_event_with_span`]: struct.Reader.html#method.read_event_with_span
+    pub fn read_event_with_span<'b>(
+        &mut self,
+        buf: &'b mut Vec<u8>,
+    ) -> Result<(Event<'b>, Range<usize>)> {
+        self.reader.read_event_observed(buf, Some(&mut self.on_raw))
+    }
+
+    /// Reads the next event and resolves its namespace like [`Reader::read_namespaced_event`].
+    ///
+    /// [`Reader::read_namespaced_event`]: struct.Reader.html#method.read_namespaced_event
+    pub fn read_namespaced_event<'b, 'c>(
+        &mut self,
+        buf: &'b mut Vec<u8>,
+        namespace_buffer: &'c mut Vec<u8>,
+    ) -> Result<(Option<&'c [u8]>, Event<'b>)> {
+        self.reader
+            .read_namespaced_event_observed(buf, namespace_buffer, Some(&mut self.on_raw))
+    }
+
+    /// Reads until end element is found like [`Reader::read_to_end`], passing the source of the
+    /// skipped events.
+    ///
+    /// [`Reader::read_to_end`]: struct.Reader.html#method.read_to_end
+    pub fn read_to_end<K: AsRef<[u8]>>(&mut self, end: K, buf: &mut Vec<u8>) -> Result<()> {
+        self.reader
+            .read_to_end_observed(end.as_ref(), buf, Some(&mut self.on_raw))
+            .map(|_| ())
+    }
+
+    /// Reads optional text between start and end tags like [`Reader::read_text`].
+    ///
+    /// [`Reader::read_text`]: struct.Reader.html#method.read_text
+    pub fn read_text<K: AsRef<[u8]>>(&mut self, end: K, buf: &mut Vec<u8>) -> Result<String> {
+        self.reader
+            .read_text_observed(end.as_ref(), buf, Some(&mut self.on_raw))
+    }
+
+    /// Returns the underlying `Reader`, e.g. to get its position.
+    pub fn reader(&self) -> &Reader<B> {
+        self.reader
+    }
+}
+
+#[cfg(feature = "std")]
+/// A push parser, reading events from input fed in chunks instead of pulled from a `BufRead`.
+///
+/// Chunks are passed to [`feed`] as they arrive, e.g. from a socket polled by an async runtime,
+/// then [`next_event`] returns the events completed by the input fed so far, or `None` when more
+/// input is needed. Tags and text split across chunks are kept until they are complete. Once all
+/// the input is fed, [`finish`] flushes the trailing text and makes `next_event` return
+/// `Event::Eof`, or an error if the input ends in the middle of a tag.
+///
+/// The events are read by an inner [`Reader`] with the same settings and positions. An event
+/// split across chunks is parsed again each time a chunk is fed, so chunks should not be much
+/// smaller than the events.
+///
+/// # Example
+///
+/// ```
+/// use quick_xml::events::Event;
+/// use quick_xml::Parser;
+///
+/// let mut parser = Parser::new();
+/// parser.reader_mut().trim_text(true);
+/// let mut names = Vec::new();
+/// for chunk in &["<root><ite", "m>text</item", "><item/></ro", "ot>"] {
+///     parser.feed(chunk.as_bytes());
+///     while let Some(event) = parser.next_event()? {
+///         match event {
+///             Event::Start(e) | Event::Empty(e) => names.push(e.name().to_vec()),
+///             _ => (),
+///         }
+///     }
+/// }
+/// parser.finish();
+/// match parser.next_event()? {
+///     Some(Event::Eof) => (),
+///     e => panic!("expecting Eof, found {:?}", e),
+/// }
+/// assert_eq!(names, vec![&b"root"[..], b"item", b"item"]);
+/// # Ok::<(), quick_xml::Error>(())
+/// ```
+///
+/// [`feed`]: #method.feed
+/// [`next_event`]: #method.next_event
+/// [`finish`]: #method.finish
+/// [`Reader`]: struct.Reader.html
+pub struct Parser {
+    reader: Reader<Chunks>,
+    /// buffer of the last event
+    buf: Vec<u8>,
+}
+
+#[cfg(feature = "std")]
+impl Parser {
+    /// Creates a push parser with the default `Reader` settings.
+    pub fn new() -> Parser {
+        Parser {
+            reader: Reader::from_reader(Chunks::default()),
+            buf: Vec::new(),
+        }
+    }
+
+    /// Appends `chunk` to the input.
+    pub fn feed(&mut self, chunk: &[u8]) {
+        let chunks = &mut self.reader.reader;
+        chunks.data.drain(..chunks.position);
+        chunks.position = 0;
+        chunks.data.extend_from_slice(chunk);
+    }
+
+    /// Marks the end of the input, so that the text after the last tag is flushed and
+    /// [`next_event`] returns `Event::Eof` once all the events are read.
+    ///
+    /// A tag not closed by the end of the input is then an `Error::UnexpectedEof`.
+    ///
+    /// [`next_event`]: #method.next_event
+    pub fn finish(&mut self) {
+        self.reader.reader.finished = true;
+    }
+
+    /// Reads the next event completed by the input fed so far, or returns `None` if more input
+    /// is needed to complete it.
+    pub fn next_event(&mut self) -> Result<Option<Event<'_>>> {
+        self.buf.clear();
+        if self.reader.reader.finished {
+            self.reader.reader.eof_reached = false;
+            let event = self.reader.read_event(&mut self.buf)?;
+            return match event {
+                Event::Text(_) | Event::Eof => Ok(Some(event)),
+                // tags are read until the end of the input if they are not closed
+                _ if self.reader.reader.eof_reached => {
+                    self.reader.tag_state = TagState::Exit;
+                    Err(Error::UnexpectedEof("Tag".to_string()))
+                }
+                _ => Ok(Some(event)),
+            };
+        }
+        let position = self.reader.reader.position;
+        let buf_position = self.reader.buf_position;
+        let tag_state = self.reader.tag_state.clone();
+        match self.reader.read_event(&mut self.buf) {
+            Err(Error::Io(ref e)) if e.kind() == io::ErrorKind::WouldBlock => {
+                // read the incomplete event again once more input is fed
+                self.reader.reader.position = position;
+                self.reader.buf_position = buf_position;
+                self.reader.tag_state = tag_state;
+                Ok(None)
+            }
+            event => event.map(Some),
+        }
+    }
+
+    /// Returns the inner `Reader`, e.g. to decode events or get its position.
+    pub fn reader(&self) -> &Reader<Chunks> {
+        &self.reader
+    }
+
+    /// Returns the inner `Reader`, e.g. to change its settings. Its events must be read with
+    /// [`next_event`].
+    ///
+    /// [`next_event`]: #method.next_event
+    pub fn reader_mut(&mut self) -> &mut Reader<Chunks> {
+        &mut self.reader
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for Parser {
+    fn default() -> Self {
+        Parser::new()
+    }
+}
+
+#[cfg(feature = "std")]
+/// The input fed to a [`Parser`], read by its inner `Reader`.
+///
+/// Reading past the input fed so far fails with an `io::Error` of kind `WouldBlock`, until the
+/// parser is finished.
+///
+/// [`Parser`]: struct.Parser.html
+#[derive(Clone, Debug, Default)]
+pub struct Chunks {
+    /// input fed and not read yet, after `position`
+    data: Vec<u8>,
+    position: usize,
+    /// whether all the input is fed
+    finished: bool,
+    /// whether the end of the input has been read, once finished
+    eof_reached: bool,
+}
+
+#[cfg(feature = "std")]
+impl io::Read for Chunks {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = {
+            let available = self.fill_buf()?;
+            let len = available.len().min(buf.len());
+            buf[..len].copy_from_slice(&available[..len]);
+            len
+        };
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+#[cfg(feature = "std")]
+impl BufRead for Chunks {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.position == self.data.len() {
+            if !self.finished {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            self.eof_reached = true;
+        }
+        Ok(&self.data[self.position..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.position += amt;
+    }
+}
+
+/// fill the buffer of `r`, retrying if interrupted
+#[cfg(feature = "std")]
+#[inline]
+fn fill_buf<R: BufRead>(r: &mut R) -> Result<&[u8]> {
+    loop {
+        match r.fill_buf() {
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(Error::Io(e)),
+            Ok(_) => break,
+        }
+    }
+    r.fill_buf().map_err(Error::Io)
+}
+
+/// fill the buffer of `r`, which cannot fail without `std`
+#[cfg(not(feature = "std"))]
+#[inline]
+fn fill_buf<R: BufRead>(r: &mut R) -> Result<&[u8]> {
+    Ok(r.fill_buf())
+}
+
 /// read until `byte` is found or end of file
 /// return the position of byte
 #[inline]
@@ -913,13 +2077,12 @@ fn read_until<R: BufRead>(
     let mut done = false;
     while !done {
         let used = {
-            let available = match r.fill_buf() {
+            let available = match fill_buf(r) {
                 Ok(n) if n.is_empty() => break,
                 Ok(n) => n,
-                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
                 Err(e) => {
                     *position += read;
-                    return Err(Error::Io(e));
+                    return Err(e);
                 }
             };
 
@@ -973,13 +2136,12 @@ fn read_elem_until<R: BufRead>(
     let mut done = false;
     while !done {
         let used = {
-            let available = match r.fill_buf() {
+            let available = match fill_buf(r) {
                 Ok(n) if n.is_empty() => return Ok(read),
                 Ok(n) => n,
-                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
                 Err(e) => {
                     *position += read;
-                    return Err(Error::Io(e));
+                    return Err(e);
                 }
             };
 
@@ -1031,6 +2193,41 @@ pub(crate) fn is_whitespace(b: u8) -> bool {
     }
 }
 
+/// Returns the range of `text` without its leading and trailing whitespace, or `None` if it is
+/// only whitespace. Whitespace is matched by `predicate` on UTF-8 characters if set, or by
+/// `is_whitespace` otherwise
+fn trim_whitespace(text: &[u8], predicate: Option<fn(char) -> bool>) -> Option<Range<usize>> {
+    let predicate = match predicate {
+        Some(predicate) => predicate,
+        None => {
+            let start = text.iter().position(|&b| !is_whitespace(b))?;
+            let end = text.iter().rposition(|&b| !is_whitespace(b)).map_or(start, |p| p + 1);
+            return Some(start..end);
+        }
+    };
+    let valid = match from_utf8(text) {
+        Ok(valid) => valid,
+        Err(e) => from_utf8(&text[..e.valid_up_to()]).unwrap_or_default(),
+    };
+    let start = valid.len() - valid.trim_start_matches(predicate).len();
+    if start == text.len() {
+        return None;
+    }
+    let mut end = text.len();
+    while end > start {
+        // the last character is made of the last 4 bytes at most
+        let tail = &text[start.max(end.saturating_sub(4))..end];
+        let last = (0..tail.len())
+            .find_map(|i| from_utf8(&tail[i..]).ok())
+            .and_then(|s| s.chars().next_back());
+        match last {
+            Some(c) if predicate(c) => end -= c.len_utf8(),
+            _ => break,
+        }
+    }
+    Some(start..end)
+}
+
 /// A namespace declaration. Can either bind a namespace to a prefix or define the current default
 /// namespace.
 #[derive(Debug, Clone)]
@@ -1109,6 +2306,14 @@ impl NamespaceBufferIndex {
             .and_then(|n| n.opt_value(buffer))
     }
 
+    #[inline]
+    fn find_prefix_value<'c>(&self, prefix: &[u8], buffer: &'c [u8]) -> Option<&'c [u8]> {
+        self.slices
+            .iter()
+            .rfind(|n| &buffer[n.start..n.start + n.prefix_len] == prefix)
+            .and_then(|n| n.opt_value(buffer))
+    }
+
     fn pop_empty_namespaces(&mut self, buffer: &mut Vec<u8>) {
         if !self.pending_pop {
             return;
@@ -1224,4 +2429,16 @@ impl Decoder {
     pub fn decode<'c>(&self, bytes: &'c [u8]) -> Cow<'c, str> {
         self.encoding.decode(bytes).0
     }
+
+    /// Decodes `bytes` like `Reader::decode_strict`
+    #[cfg(feature = "encoding")]
+    pub fn decode_strict<'c>(&self, bytes: &'c [u8]) -> Result<Cow<'c, str>> {
+        let (encoding, bytes) = match Encoding::for_bom(bytes) {
+            Some((encoding, bom_len)) => (encoding, &bytes[bom_len..]),
+            None => (self.encoding, bytes),
+        };
+        encoding
+            .decode_without_bom_handling_and_without_replacement(bytes)
+            .ok_or_else(|| Error::UndecodableBytes(encoding.name()))
+    }
 }
@@ -93,6 +93,11 @@ impl<'r, W: Write> Serializer<'r, W> {
         Self { writer, root_tag }
     }
 
+    /// Consumes this serializer, returning the inner writer
+    pub(crate) fn into_inner(self) -> Writer<W> {
+        self.writer
+    }
+
     fn write_primitive<P: std::fmt::Display>(
         &mut self,
         value: P,
@@ -0,0 +1,45 @@
+//! The parts of the standard library used by this crate, built from `core` and `alloc` when the
+//! `std` feature is not activated.
+//!
+//! In Rust 2015, `use std::...` paths are relative to the crate root, so the modules shared with
+//! the `std` build find these items under the same paths.
+
+pub use core::*;
+
+pub use alloc::{borrow, fmt, str, string};
+
+pub mod collections {
+    //! Collections from `alloc`; there is no `HashMap` without `std`
+    pub use alloc::collections::*;
+}
+
+pub mod prelude {
+    //! The items of the standard prelude which are not in the `core` prelude
+    pub use alloc::borrow::ToOwned;
+    pub use alloc::string::{String, ToString};
+    pub use alloc::vec::Vec;
+}
+
+pub mod io {
+    //! Replacement of `std::io::BufRead` for the in-memory inputs a `Reader` can read from
+
+    /// A buffered source of bytes which cannot fail, implemented for byte slices.
+    pub trait BufRead {
+        /// Returns the bytes not consumed yet, empty once everything is consumed
+        fn fill_buf(&mut self) -> &[u8];
+        /// Marks the `amt` first bytes returned by `fill_buf` as consumed
+        fn consume(&mut self, amt: usize);
+    }
+
+    impl<'a> BufRead for &'a [u8] {
+        #[inline]
+        fn fill_buf(&mut self) -> &[u8] {
+            self
+        }
+
+        #[inline]
+        fn consume(&mut self, amt: usize) {
+            *self = &self[amt..];
+        }
+    }
+}
@@ -1,4 +1,9 @@
-pub fn write_byte_string(f: &mut std::fmt::Formatter<'_>, byte_string: &[u8]) -> std::fmt::Result {
+use std::borrow::Cow;
+use std::fmt;
+#[cfg(not(feature = "std"))]
+use std::prelude::*;
+
+pub fn write_byte_string(f: &mut fmt::Formatter<'_>, byte_string: &[u8]) -> fmt::Result {
     write!(f, "\"")?;
     for b in byte_string {
         match *b {
@@ -11,14 +16,94 @@ pub fn write_byte_string(f: &mut std::fmt::Formatter<'_>, byte_string: &[u8]) ->
     Ok(())
 }
 
+/// Maximum number of characters written by `write_lossy_string` and `write_lossy_markup`
+const MAX_LOSSY_CHARS: usize = 256;
+
+/// Converts the start of `bytes` to a lossy UTF-8 string of at most `MAX_LOSSY_CHARS`
+/// characters, returning whether `bytes` was truncated
+fn truncated_lossy(bytes: &[u8]) -> (Cow<'_, str>, bool) {
+    // a character is made of 4 bytes at most
+    let limit = 4 * MAX_LOSSY_CHARS;
+    let lossy = String::from_utf8_lossy(&bytes[..bytes.len().min(limit)]);
+    match lossy.char_indices().nth(MAX_LOSSY_CHARS) {
+        Some((end, _)) => match lossy {
+            Cow::Borrowed(s) => (Cow::Borrowed(&s[..end]), true),
+            Cow::Owned(mut s) => {
+                s.truncate(end);
+                (Cow::Owned(s), true)
+            }
+        },
+        None => (lossy, bytes.len() > limit),
+    }
+}
+
+/// Writes `bytes` as a quoted lossy UTF-8 string, followed by `…` if truncated
+pub fn write_lossy_string(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+    let (lossy, truncated) = truncated_lossy(bytes);
+    write!(f, "{:?}", lossy)?;
+    if truncated {
+        write!(f, "…")?;
+    }
+    Ok(())
+}
+
+/// Writes `bytes` as a lossy UTF-8 string, followed by `…` if truncated
+pub fn write_lossy_markup(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+    let (lossy, truncated) = truncated_lossy(bytes);
+    f.write_str(&lossy)?;
+    if truncated {
+        write!(f, "…")?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    struct Lossy<'a>(&'a [u8]);
+
+    impl<'a> fmt::Debug for Lossy<'a> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write_lossy_string(f, self.0)
+        }
+    }
+
+    impl<'a> fmt::Display for Lossy<'a> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write_lossy_markup(f, self.0)
+        }
+    }
+
+    #[test]
+    fn write_lossy() {
+        let bytes = "a\n\"é\"".as_bytes();
+        assert_eq!(format!("{:?}", Lossy(bytes)), r#""a\n\"é\"""#);
+        assert_eq!(format!("{}", Lossy(bytes)), "a\n\"é\"");
+        assert_eq!(format!("{}", Lossy(b"a\xFFb")), "a\u{FFFD}b");
+    }
+
+    #[test]
+    fn write_lossy_truncated() {
+        let long = "é".repeat(MAX_LOSSY_CHARS);
+        assert_eq!(format!("{}", Lossy(long.as_bytes())), long);
+        let longer = "é".repeat(10 * MAX_LOSSY_CHARS);
+        assert_eq!(format!("{}", Lossy(longer.as_bytes())), long.clone() + "…");
+        assert_eq!(
+            format!("{:?}", Lossy(longer.as_bytes())),
+            format!("{:?}…", long)
+        );
+        let ascii = "a".repeat(4 * MAX_LOSSY_CHARS + 1);
+        assert_eq!(
+            format!("{}", Lossy(ascii.as_bytes())),
+            "a".repeat(MAX_LOSSY_CHARS) + "…"
+        );
+    }
+
     struct ByteString(Vec<u8>);
 
-    impl std::fmt::Debug for ByteString {
-        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    impl fmt::Debug for ByteString {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
             write_byte_string(f, &self.0)
         }
     }
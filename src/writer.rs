@@ -1,9 +1,31 @@
 //! A module to handle `Writer`
 
-use std::io::Write;
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{self, BufRead, BufWriter, Read, Write};
+use std::mem;
+use std::str::from_utf8;
+
+#[cfg(feature = "encoding")]
+use encoding_rs::{EncoderResult, Encoding, UTF_8};
+#[cfg(feature = "async-tokio")]
+use std::{
+    future::{self, Future},
+    pin::Pin,
+    task::{Context, Poll},
+};
 
 use errors::{Error, Result};
-use events::Event;
+use escape::{escape, escape_attribute, unescape};
+use events::attributes::{Attributes, QuoteStyle};
+use events::{attributes::Attribute, BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use memchr;
+use name::{check_name, QName};
+use reader::{is_whitespace, Reader};
+#[cfg(feature = "async-tokio")]
+use tokio::io::AsyncWrite;
+#[cfg(feature = "serialize")]
+use {errors::serialize::DeError, se::Serializer, serde::Serialize};
 
 /// XML writer.
 ///
@@ -57,119 +79,2395 @@ use events::Event;
 /// # }
 /// ```
 #[derive(Clone)]
-pub struct Writer<W: Write> {
+pub struct Writer<W> {
     /// underlying writer
     writer: W,
     indent: Option<Indentation>,
+    /// reject `Text` events containing `<`
+    check_escaped_text: bool,
+    /// reject `Decl` events written after other content
+    check_declaration: bool,
+    /// whether some content, apart from a BOM, has already been written
+    written: bool,
+    /// reject `End` events not matching the last written `Start` event
+    check_end_names: bool,
+    /// names of all written `Start` events without matching `End` event yet
+    opened_buffer: Vec<u8>,
+    /// opened name start indexes
+    opened_starts: Vec<usize>,
+    /// reject events which would produce XML which is not well-formed
+    validate: bool,
+    /// whether a root element has been written
+    has_root: bool,
+    /// split `CData` events containing `]]>` into several sections
+    split_cdata: bool,
+    /// quote character to rewrite all attribute values with
+    attribute_quote: Option<QuoteStyle>,
+    /// sort attributes like canonical XML
+    canonical_attributes: bool,
+    /// namespaces in scope, if `canonical_attributes`
+    attribute_namespaces: Namespaces,
+    /// namespaces in scope, if writing canonical XML
+    canonical: Option<Namespaces>,
+    #[cfg(feature = "encoding")]
+    /// encoding to transcode the UTF-8 events into
+    encoding: Option<&'static Encoding>,
+    /// write `Start` events directly followed by their `End` event as `Empty` events
+    collapse_empty_elements: bool,
+    /// `Start` event not written yet, if `collapse_empty_elements`
+    pending_start: Option<BytesStart<'static>>,
+    /// namespaces declared by `write_start_ns`
+    ns: NamespaceScopes,
+    /// number of bytes written to `writer`
+    bytes_written: u64,
+    #[cfg(feature = "async-tokio")]
+    /// formatted bytes not written yet to the asynchronous `writer`
+    unsent: Vec<u8>,
+}
+
+impl<W> Writer<W> {
+    /// Creates a Writer from a generic Write
+    ///
+    /// Events are written to `inner` as they come without buffering: wrap files or sockets into
+    /// a `BufWriter`, or use [`new_buffered`].
+    ///
+    /// [`new_buffered`]: #method.new_buffered
+    pub fn new(inner: W) -> Writer<W> {
+        Writer {
+            writer: inner,
+            indent: None,
+            check_escaped_text: false,
+            check_declaration: false,
+            written: false,
+            check_end_names: false,
+            opened_buffer: Vec::new(),
+            opened_starts: Vec::new(),
+            validate: false,
+            has_root: false,
+            split_cdata: false,
+            attribute_quote: None,
+            canonical_attributes: false,
+            attribute_namespaces: Namespaces::default(),
+            canonical: None,
+            #[cfg(feature = "encoding")]
+            encoding: None,
+            collapse_empty_elements: false,
+            pending_start: None,
+            ns: NamespaceScopes::default(),
+            bytes_written: 0,
+            #[cfg(feature = "async-tokio")]
+            unsent: Vec::new(),
+        }
+    }
+
+    /// Creates a Writer with configured whitespace indents from a generic Write
+    pub fn new_with_indent(inner: W, indent_char: u8, indent_size: usize) -> Writer<W> {
+        WriterConfig::new()
+            .indent(indent_char, indent_size)
+            .build(inner)
+    }
+
+    /// Creates a Writer transcoding the UTF-8 content of events into `encoding`.
+    ///
+    /// Characters which cannot be represented in `encoding` are written as character references
+    /// (e.g. `&#1078;`) in texts and attribute values, and are rejected with
+    /// `Error::UnencodableCharacter` elsewhere (names, comments, CDATA, ...). Content written
+    /// with a [`TextWriter`] or [`write_text_from_reader`] is transcoded like text, while bytes
+    /// written with [`write`] or [`write_raw`] are written as is, so they must already be in
+    /// `encoding`.
+    ///
+    /// As with `Encoding::encode`, the UTF-16 encodings are replaced by UTF-8. Like browsers,
+    /// `encoding_rs` maps the `ISO-8859-1` (Latin-1) label to windows-1252, which is declared as
+    /// such: it encodes the same characters from U+00A0, but uses most bytes from 0x80 to 0x9F
+    /// for characters such as `€`, so U+0080 to U+009F are written as character references,
+    /// except the five which windows-1252 leaves unassigned.
+    ///
+    /// [`TextWriter`]: struct.TextWriter.html
+    /// [`write_text_from_reader`]: #method.write_text_from_reader
+    /// [`write`]: #method.write
+    /// [`write_raw`]: #method.write_raw
+    #[cfg(feature = "encoding")]
+    pub fn with_encoding(inner: W, encoding: &'static Encoding) -> Writer<W> {
+        WriterConfig::new().encoding(encoding).build(inner)
+    }
+
+    /// Changes the line break written before indented events (`\n` by default).
+    ///
+    /// This method will do nothing if `Writer` was not constructed with `new_with_indent`.
+    pub fn newline(&mut self, newline: &[u8]) -> &mut Writer<W> {
+        if let Some(i) = self.indent.as_mut() {
+            i.newline = newline.to_vec();
+        }
+        self
+    }
+
+    /// Changes the length in bytes above which the tag of a `Start` or `Empty` event is written
+    /// with each attribute on its own line, aligned after the element name.
+    ///
+    /// Attributes are written in the same order and with the same escaping. Where the `>` or
+    /// `/>` goes is set by [`wrapped_tag_end`]. Tags which cannot be parsed are not wrapped.
+    ///
+    /// (`None` by default). This method will do nothing if `Writer` was not constructed with
+    /// `new_with_indent`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use quick_xml::events::{BytesStart, Event};
+    /// use quick_xml::Writer;
+    ///
+    /// let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    /// writer.wrap_attributes(Some(20));
+    /// let start = BytesStart::borrowed_name(b"service");
+    /// writer.write_event(Event::Start(start.clone().with_attributes(vec![("a", "1")])))?;
+    /// writer.write_event(Event::Empty(start.with_attributes(vec![("a", "1"), ("b", "2")])))?;
+    /// writer.write_end()?;
+    /// assert_eq!(
+    ///     writer.into_string()?,
+    ///     "<service a=\"1\">\n  <service a=\"1\"\n           b=\"2\"/>\n</service>"
+    /// );
+    /// # Ok::<(), quick_xml::Error>(())
+    /// ```
+    ///
+    /// [`wrapped_tag_end`]: #method.wrapped_tag_end
+    pub fn wrap_attributes(&mut self, max_len: Option<usize>) -> &mut Writer<W> {
+        if let Some(i) = self.indent.as_mut() {
+            i.wrap_attributes = max_len;
+        }
+        self
+    }
+
+    /// Changes where the `>` or `/>` of the tags wrapped by [`wrap_attributes`] is written
+    /// (`WrappedTagEnd::AfterLastAttribute` by default).
+    ///
+    /// This method will do nothing if `Writer` was not constructed with `new_with_indent`.
+    ///
+    /// [`wrap_attributes`]: #method.wrap_attributes
+    pub fn wrapped_tag_end(&mut self, style: WrappedTagEnd) -> &mut Writer<W> {
+        if let Some(i) = self.indent.as_mut() {
+            i.wrapped_tag_end = style;
+        }
+        self
+    }
+
+    /// Changes whether `Text` events are checked for unescaped `<` before being written.
+    ///
+    /// `Text` events are written as is, so one created with `BytesText::from_escaped` from
+    /// unescaped input produces invalid XML. When enabled, `write_event` returns
+    /// `Error::UnescapedText` for such events instead.
+    ///
+    /// (`false` by default)
+    pub fn check_escaped_text(&mut self, val: bool) -> &mut Writer<W> {
+        self.check_escaped_text = val;
+        self
+    }
+
+    /// Changes how `CData` events containing the `]]>` section end are written.
+    ///
+    /// Such content cannot be written in a single CDATA section. By default `write_event`
+    /// returns `Error::UnescapedCData`; when enabled, the content is split into adjacent
+    /// sections instead (`a]]>b` is written as `<![CDATA[a]]]]><![CDATA[>b]]>`), which a
+    /// `Reader` reads back as consecutive `CData` events.
+    ///
+    /// (`false` by default)
+    pub fn split_cdata(&mut self, val: bool) -> &mut Writer<W> {
+        self.split_cdata = val;
+        self
+    }
+
+    /// Changes whether `Decl` events are checked to be written before any other content.
+    ///
+    /// When enabled, `write_event` returns `Error::MisplacedDeclaration` for a `Decl` event
+    /// written after another event. A BOM written with `write_bom` may precede it.
+    ///
+    /// (`false` by default)
+    pub fn check_declaration(&mut self, val: bool) -> &mut Writer<W> {
+        self.check_declaration = val;
+        self
+    }
+
+    /// Changes whether `End` events are checked to match the last opened `Start` event.
+    ///
+    /// When enabled, `write_event` returns `Error::EndEventMismatch` for an `End` event which
+    /// does not close the last opened element. Opened elements are always tracked, see
+    /// [`write_end`].
+    ///
+    /// (`false` by default)
+    ///
+    /// [`write_end`]: #method.write_end
+    pub fn check_end_names(&mut self, val: bool) -> &mut Writer<W> {
+        self.check_end_names = val;
+        self
+    }
+
+    /// Sets the quote character, `"` or `'`, of all attribute values written.
+    ///
+    /// Attributes of `Start` and `Empty` events are written as is by default, e.g. with the
+    /// quotes of the document they were read from. Once set, all attributes are rewritten with
+    /// `quote`, escaping it in their values (other characters are kept as they are).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `quote` is not `b'"'` or `b'\''`.
+    pub fn attribute_quote(&mut self, quote: u8) -> &mut Writer<W> {
+        let style = match quote {
+            b'"' => QuoteStyle::Double,
+            b'\'' => QuoteStyle::Single,
+            _ => panic!("attribute quote must be `\"` or `'`"),
+        };
+        self.attribute_quote_style(style)
+    }
+
+    /// Sets the quote style of all attribute values written.
+    ///
+    /// Same as [`attribute_quote`], but [`QuoteStyle::Auto`] chooses the quote of each value
+    /// separately, avoiding escapes whenever possible.
+    ///
+    /// [`attribute_quote`]: #method.attribute_quote
+    /// [`QuoteStyle::Auto`]: events/attributes/enum.QuoteStyle.html#variant.Auto
+    pub fn attribute_quote_style(&mut self, style: QuoteStyle) -> &mut Writer<W> {
+        self.attribute_quote = Some(style);
+        self
+    }
+
+    /// Changes whether the attributes of `Start` and `Empty` events are sorted like in
+    /// [Canonical XML], so that the same attributes are always written in the same order.
+    ///
+    /// Namespace declarations come first, sorted by prefix (the default namespace first), followed
+    /// by the attributes without prefix sorted by name, and the other attributes sorted by
+    /// namespace URI and local name. Prefixes are resolved with the namespaces declared on the
+    /// element and its ancestors written since this option is enabled: attributes with an
+    /// undeclared prefix come last, sorted by name. Attributes with equal keys keep their order.
+    ///
+    /// Attributes are written with their original quotes (or according to
+    /// [`attribute_quote`]) and separated by a single space. See [`canonical`] to also
+    /// canonicalize the rest of the document.
+    ///
+    /// (`false` by default)
+    ///
+    /// [Canonical XML]: https://www.w3.org/TR/xml-c14n#DocumentOrder
+    /// [`attribute_quote`]: #method.attribute_quote
+    /// [`canonical`]: #method.canonical
+    pub fn canonical_attributes(&mut self, val: bool) -> &mut Writer<W> {
+        self.canonical_attributes = val;
+        self
+    }
+
+    /// Changes whether events are checked to produce a well-formed document.
+    ///
+    /// When enabled, `write_event` returns `Error::NotWellFormed` instead of writing an event if:
+    /// - an element or attribute name does not match the XML [`Name`] production
+    /// - an attribute value or a text contains a `<`, or a `&` which does not start a reference
+    /// - an element has several attributes with the same key
+    /// - a comment contains `--` or ends with `-`
+    /// - a `End` event does not close the last opened element
+    /// - a second root element is written
+    ///
+    /// It also implies [`check_declaration`], returning `Error::MisplacedDeclaration` for a
+    /// `Decl` event written after another event.
+    ///
+    /// (`false` by default)
+    ///
+    /// [`Name`]: https://www.w3.org/TR/xml/#NT-Name
+    /// [`check_declaration`]: #method.check_declaration
+    pub fn validate(&mut self, val: bool) -> &mut Writer<W> {
+        self.validate = val;
+        self
+    }
+
+    /// Changes whether events are rewritten as [Canonical XML] 1.0 (with comments), so that
+    /// equivalent documents are written the same way:
+    /// - `Decl` and `DocType` events and text outside of the root element are skipped, and a line
+    ///   feed separates the root element from the comments and processing instructions around it
+    /// - `Empty` events are written as start and end tags, and `CData` events as text
+    /// - line endings are normalized to `\n`, then text and attribute values are unescaped and
+    ///   escaped again with the canonical references (e.g. `&#xD;` for a carriage return)
+    /// - attribute values are normalized and quoted with `"`, namespace declarations come first
+    ///   sorted by prefix, followed by the other attributes sorted by namespace URI and local
+    ///   name, and declarations already in scope are skipped
+    ///
+    /// Indentation is not applied. Entities and default attributes declared in a DTD are not
+    /// supported, and namespace prefixes are not rewritten.
+    ///
+    /// (`false` by default)
+    ///
+    /// [Canonical XML]: https://www.w3.org/TR/xml-c14n
+    pub fn canonical(&mut self, val: bool) -> &mut Writer<W> {
+        self.canonical = if val {
+            Some(Namespaces::default())
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Changes whether a `Start` event directly followed by its `End` event is written as a
+    /// self-closing tag (`<tag/>`) like an `Empty` event.
+    ///
+    /// When set to `true`, `Start` events are deferred until the next event, or a call to
+    /// [`flush`], [`write_end`], [`write_raw`] or [`write_indent`], or some content written with
+    /// a [`TextWriter`]. A `Start` event still pending when the `Writer` is consumed is written
+    /// by [`into_string`] and [`try_into_inner`], but lost with [`into_inner`].
+    ///
+    /// (`false` by default)
+    ///
+    /// [`flush`]: #method.flush
+    /// [`write_end`]: #method.write_end
+    /// [`write_raw`]: #method.write_raw
+    /// [`write_indent`]: #method.write_indent
+    /// [`TextWriter`]: struct.TextWriter.html
+    /// [`into_string`]: #method.into_string
+    /// [`try_into_inner`]: #method.try_into_inner
+    /// [`into_inner`]: #method.into_inner
+    pub fn collapse_empty_elements(&mut self, val: bool) -> &mut Writer<W> {
+        self.collapse_empty_elements = val;
+        self
+    }
+
+    /// Changes how [`write_start_ns`] generates the prefixes of undeclared namespaces.
+    ///
+    /// `generate` is called with the namespace URI and the number of prefixes already rejected
+    /// for it because they are bound to another namespace, empty or reserved (`xml` and
+    /// `xmlns`), so it must return a different prefix for each number. If more prefixes are
+    /// rejected than there are prefixes in scope plus three, `write_start_ns` returns
+    /// `Error::NotWellFormed`. By default, `ns0`, `ns1`, ... are generated.
+    ///
+    /// [`write_start_ns`]: #method.write_start_ns
+    pub fn prefix_generator(&mut self, generate: fn(&str, usize) -> String) -> &mut Writer<W> {
+        self.ns.generate_prefix = generate;
+        self
+    }
+
+    /// Declares `uri` as the default namespace of the next element written with
+    /// [`write_start_ns`], so that its elements are written without prefixes.
+    ///
+    /// [`write_start_ns`]: #method.write_start_ns
+    pub fn set_default_namespace(&mut self, uri: &str) -> &mut Writer<W> {
+        self.ns.default = Some(uri.as_bytes().to_vec());
+        self
+    }
+
+    /// Returns the total number of bytes written to the underlying writer so far, including
+    /// indentation, the BOM and the content written with a [`TextWriter`].
+    ///
+    /// A `Start` event deferred by [`collapse_empty_elements`] is only counted once written.
+    ///
+    /// [`TextWriter`]: struct.TextWriter.html
+    /// [`collapse_empty_elements`]: #method.collapse_empty_elements
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// Consumes this `Writer`, returning the underlying writer.
+    ///
+    /// A `Start` event deferred by [`collapse_empty_elements`] is not written: use
+    /// [`try_into_inner`] to write it first.
+    ///
+    /// [`collapse_empty_elements`]: #method.collapse_empty_elements
+    /// [`try_into_inner`]: #method.try_into_inner
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Get inner writer, keeping ownership
+    pub fn inner(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Gets a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Gets a mutable reference to the underlying writer.
+    ///
+    /// Writing to it directly bypasses indentation and checks of this `Writer`.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Moves the state of this `Writer` (indentation, opened elements, ...) into a new `Writer`
+    /// writing into `inner`, until it is given back with `join_state`
+    fn split_state<U>(&mut self, inner: U) -> Writer<U> {
+        Writer {
+            writer: inner,
+            indent: self.indent.take(),
+            check_escaped_text: self.check_escaped_text,
+            check_declaration: self.check_declaration,
+            written: self.written,
+            check_end_names: self.check_end_names,
+            opened_buffer: mem::take(&mut self.opened_buffer),
+            opened_starts: mem::take(&mut self.opened_starts),
+            validate: self.validate,
+            has_root: self.has_root,
+            split_cdata: self.split_cdata,
+            attribute_quote: self.attribute_quote,
+            canonical_attributes: self.canonical_attributes,
+            attribute_namespaces: mem::take(&mut self.attribute_namespaces),
+            canonical: self.canonical.take(),
+            #[cfg(feature = "encoding")]
+            encoding: self.encoding,
+            collapse_empty_elements: self.collapse_empty_elements,
+            pending_start: self.pending_start.take(),
+            ns: mem::take(&mut self.ns),
+            bytes_written: self.bytes_written,
+            #[cfg(feature = "async-tokio")]
+            unsent: Vec::new(),
+        }
+    }
+
+    /// Gets back the state moved by `split_state`, returning the inner writer of `other`
+    fn join_state<U>(&mut self, other: Writer<U>) -> U {
+        self.indent = other.indent;
+        self.written = other.written;
+        self.opened_buffer = other.opened_buffer;
+        self.opened_starts = other.opened_starts;
+        self.has_root = other.has_root;
+        self.attribute_namespaces = other.attribute_namespaces;
+        self.canonical = other.canonical;
+        self.pending_start = other.pending_start;
+        self.ns = other.ns;
+        self.bytes_written = other.bytes_written;
+        other.writer
+    }
+}
+
+impl<W: Write> Writer<W> {
+    /// Writes the given event to the underlying writer, returning the number of bytes written.
+    ///
+    /// The count includes the indentation written before the event, and the `Start` event
+    /// deferred by [`collapse_empty_elements`] if this event causes it to be written.
+    ///
+    /// [`collapse_empty_elements`]: #method.collapse_empty_elements
+    pub fn write_event<'a, E: AsRef<Event<'a>>>(&mut self, event: E) -> Result<usize> {
+        let bytes_written = self.bytes_written;
+        let result = match *event.as_ref() {
+            // like when written, empty text cannot separate a start tag from its end tag
+            Event::Text(ref e) if e.is_empty() => Ok(()),
+            Event::End(ref e) => {
+                let result = match self.pending_start.take() {
+                    Some(start) if start.name() == e.name() => {
+                        self.write_event_now(Event::Empty(start))
+                    }
+                    Some(start) => self
+                        .write_event_now(Event::Start(start))
+                        .and_then(|_| self.write_event_now(event.as_ref())),
+                    None => self.write_event_now(event.as_ref()),
+                };
+                self.close_ns_scopes();
+                result
+            }
+            Event::Start(ref e) if self.collapse_empty_elements => {
+                self.write_pending_start()?;
+                self.pending_start = Some(e.to_owned());
+                Ok(())
+            }
+            _ => {
+                self.write_pending_start()?;
+                self.write_event_now(event.as_ref())
+            }
+        };
+        result.map(|_| (self.bytes_written - bytes_written) as usize)
+    }
+
+    /// Writes a start tag of the `local_name` element in the `namespace` URI (or in no namespace
+    /// if empty), with `(namespace, local name, value)` attributes, declaring the namespaces
+    /// which are not in scope yet.
+    ///
+    /// Elements use the default namespace declared with [`set_default_namespace`] when possible,
+    /// then any prefix in scope bound to their namespace. Otherwise, a prefix is declared with a
+    /// name from the [`prefix_generator`]. Attribute values are escaped. The declarations are in
+    /// scope until the element is closed, e.g. with [`write_end`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use quick_xml::Writer;
+    ///
+    /// let mut writer = Writer::new(Vec::new());
+    /// writer.write_start_ns("urn:a", "x", vec![("urn:b", "y", "1"), ("", "z", "2")])?;
+    /// writer.write_start_ns("urn:b", "x", None)?;
+    /// writer.close_all()?;
+    /// assert_eq!(
+    ///     writer.into_string()?,
+    ///     r#"<ns0:x xmlns:ns0="urn:a" xmlns:ns1="urn:b" ns1:y="1" z="2"><ns1:x></ns1:x></ns0:x>"#
+    /// );
+    /// # Ok::<(), quick_xml::Error>(())
+    /// ```
+    ///
+    /// [`set_default_namespace`]: #method.set_default_namespace
+    /// [`prefix_generator`]: #method.prefix_generator
+    /// [`write_end`]: #method.write_end
+    pub fn write_start_ns<'b, I>(
+        &mut self,
+        namespace: &str,
+        local_name: &str,
+        attributes: I,
+    ) -> Result<()>
+    where
+        I: IntoIterator<Item = (&'b str, &'b str, &'b str)>,
+    {
+        let depth = self.opened_starts.len() + self.pending_start.iter().count() + 1;
+        let start = self.ns.open_scope(depth);
+        let name = self.ns.qualify(namespace, local_name, true);
+        let attributes = name.and_then(|name| {
+            let attributes = attributes
+                .into_iter()
+                .map(|(namespace, local_name, value)| {
+                    Ok((self.ns.qualify(namespace, local_name, false)?, value))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Ok((name, attributes))
+        });
+        let (name, attributes) = match attributes {
+            Ok(qualified) => qualified,
+            Err(e) => {
+                self.ns.close_scopes(depth - 1);
+                return Err(e);
+            }
+        };
+
+        let mut tag = BytesStart::owned_name(name);
+        for (prefix, uri) in &self.ns.namespaces.bindings[start..] {
+            let mut key = b"xmlns".to_vec();
+            if !prefix.is_empty() {
+                key.push(b':');
+                key.extend_from_slice(prefix);
+            }
+            tag.push_attribute((&*key, &*escape_attribute(uri)));
+        }
+        for (key, value) in attributes {
+            tag.push_attribute((&*key, &*escape_attribute(value.as_bytes())));
+        }
+        let result = self.write_event(Event::Start(tag));
+        if result.is_err() {
+            self.ns.close_scopes(depth - 1);
+        }
+        result.map(|_| ())
+    }
+
+    /// Removes the namespaces declared by `write_start_ns` for the elements closed
+    fn close_ns_scopes(&mut self) {
+        let depth = self.opened_starts.len() + self.pending_start.iter().count();
+        self.ns.close_scopes(depth);
+    }
+
+    /// Writes the `Start` event deferred by `collapse_empty_elements`, if any
+    fn write_pending_start(&mut self) -> Result<()> {
+        match self.pending_start.take() {
+            Some(start) => self.write_event_now(Event::Start(start)),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the given event right away
+    fn write_event_now<'a, E: AsRef<Event<'a>>>(&mut self, event: E) -> Result<()> {
+        if self.validate {
+            self.validate_event(event.as_ref())?;
+        }
+        if self.canonical.is_some() {
+            return self.write_canonical(event.as_ref());
+        }
+        #[cfg(feature = "encoding")]
+        let encoded;
+        #[cfg(feature = "encoding")]
+        let event = match self.encoding {
+            Some(encoding) if encoding != UTF_8 => {
+                encoded = encode_event(event.as_ref(), encoding)?;
+                &encoded
+            }
+            _ => event.as_ref(),
+        };
+        #[cfg(not(feature = "encoding"))]
+        let event = event.as_ref();
+        match *event {
+            Event::Start(_) | Event::Empty(_) if self.opened_starts.is_empty() => {
+                self.has_root = true;
+            }
+            _ => (),
+        }
+        let mut next_should_line_break = true;
+        let result = match *event {
+            Event::Start(ref e) => {
+                let result = self.write_start(e, b">");
+                if let Some(i) = self.indent.as_mut() {
+                    i.grow();
+                }
+                self.opened_starts.push(self.opened_buffer.len());
+                self.opened_buffer.extend(e.name());
+                result
+            }
+            Event::End(ref e) => {
+                self.close_opened(e.name())?;
+                if self.canonical_attributes {
+                    self.attribute_namespaces.close();
+                }
+                if let Some(i) = self.indent.as_mut() {
+                    i.shrink();
+                }
+                let result = self.write_wrapped(b"</", e, b">");
+                let depth = self.opened_starts.len();
+                if let Some(i) = self.indent.as_mut() {
+                    if matches!(i.mixed_depth, Some(d) if d > depth) {
+                        i.mixed_depth = None;
+                    }
+                }
+                result
+            }
+            Event::Empty(ref e) => self.write_start(e, b"/>"),
+            Event::Text(ref e) => {
+                // empty text (e.g. between tags of an untrimmed document) cannot break the layout
+                if e.is_empty() {
+                    return Ok(());
+                }
+                if self.check_escaped_text {
+                    if let Some(i) = memchr::memchr(b'<', e) {
+                        return Err(Error::UnescapedText(i));
+                    }
+                }
+                next_should_line_break = false;
+                self.start_mixed_content();
+                self.write(&e.escaped())
+            }
+            Event::Comment(ref e) => self.write_wrapped(b"<!--", e, b"-->"),
+            Event::CData(ref e) => {
+                next_should_line_break = false;
+                self.start_mixed_content();
+                self.write_cdata(e)
+            }
+            Event::Decl(ref e) => {
+                if (self.check_declaration || self.validate) && self.written {
+                    return Err(Error::MisplacedDeclaration);
+                }
+                self.write_wrapped(b"<?", e, b"?>")
+            }
+            Event::PI(ref e) => self.write_wrapped(b"<?", e, b"?>"),
+            Event::DocType(ref e) => self.write_wrapped(b"<!DOCTYPE", e, b">"),
+            Event::Eof => Ok(()),
+        };
+        if let Some(i) = self.indent.as_mut() {
+            i.should_line_break = next_should_line_break && i.mixed_depth.is_none();
+        }
+        result
+    }
+
+    /// Disables indentation until the current element is closed, because it has text content
+    /// which would be changed by line breaks
+    fn start_mixed_content(&mut self) {
+        let depth = self.opened_starts.len();
+        if let Some(i) = self.indent.as_mut() {
+            if depth > 0 && i.mixed_depth.is_none() {
+                i.mixed_depth = Some(depth);
+            }
+        }
+    }
+
+    /// Whether events are written as they are inside of an element with text content
+    fn in_mixed_content(&self) -> bool {
+        matches!(self.indent, Some(ref i) if i.mixed_depth.is_some())
+    }
+
+    /// Flushes the underlying writer, e.g. the buffer of a writer created by `new_buffered`.
+    pub fn flush(&mut self) -> Result<()> {
+        self.write_pending_start()?;
+        self.writer.flush().map_err(Error::Io)
+    }
+
+    /// Writes an `End` event closing the last opened element.
+    ///
+    /// Returns `Error::EndEventMismatch` if all written `Start` events are already closed.
+    pub fn write_end(&mut self) -> Result<()> {
+        if let Some(start) = self.pending_start.take() {
+            let result = self.write_event_now(Event::Empty(start));
+            self.close_ns_scopes();
+            return result;
+        }
+        match self.opened_starts.last() {
+            Some(&start) => {
+                let name = self.opened_buffer[start..].to_vec();
+                self.write_event(Event::End(BytesEnd::owned(name)))?;
+                Ok(())
+            }
+            None => Err(Error::EndEventMismatch {
+                expected: String::new(),
+                found: String::new(),
+            }),
+        }
+    }
+
+    /// Writes `End` events for all opened elements, e.g. when aborting a document.
+    pub fn close_all(&mut self) -> Result<()> {
+        while !self.opened_starts.is_empty() || self.pending_start.is_some() {
+            self.write_end()?;
+        }
+        Ok(())
+    }
+
+    /// Writes `event` as canonical XML
+    fn write_canonical(&mut self, event: &Event) -> Result<()> {
+        let top_level = self.opened_starts.is_empty();
+        match *event {
+            Event::Start(ref e) => {
+                self.has_root = true;
+                self.write_canonical_start(e)?;
+                self.opened_starts.push(self.opened_buffer.len());
+                self.opened_buffer.extend(e.name());
+                Ok(())
+            }
+            Event::Empty(ref e) => {
+                self.has_root = true;
+                self.write_canonical_start(e)?;
+                self.close_canonical_scope();
+                self.write_wrapped(b"</", e.name(), b">")
+            }
+            Event::End(ref e) => {
+                self.close_opened(e.name())?;
+                self.close_canonical_scope();
+                self.write_wrapped(b"</", e.name(), b">")
+            }
+            Event::Text(ref e) if !top_level => {
+                let text = normalize_newlines(e);
+                let text = unescape(&text).map_err(Error::EscapeError)?;
+                self.write(&escape_canonical(&text, false))
+            }
+            Event::CData(ref e) if !top_level => {
+                let text = e.unescaped()?;
+                self.write(&escape_canonical(&normalize_newlines(&text), false))
+            }
+            Event::Comment(ref e) => {
+                self.write_canonical_node(top_level, b"<!--", &normalize_newlines(e), b"-->")
+            }
+            Event::PI(ref e) => {
+                let content = normalize_newlines(e);
+                let target_len = content
+                    .iter()
+                    .position(|&b| is_whitespace(b))
+                    .unwrap_or_else(|| content.len());
+                let mut pi = content[..target_len].to_vec();
+                let data = &content[target_len..];
+                if let Some(start) = data.iter().position(|&b| !is_whitespace(b)) {
+                    pi.push(b' ');
+                    pi.extend_from_slice(&data[start..]);
+                }
+                self.write_canonical_node(top_level, b"<?", &pi, b"?>")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes a canonical start tag, opening a new namespace scope
+    fn write_canonical_start(&mut self, e: &BytesStart) -> Result<()> {
+        let namespaces = self.canonical.get_or_insert_with(Namespaces::default);
+        let mut declarations = Vec::new();
+        let mut attributes = Vec::new();
+        for attribute in e.attributes() {
+            let attribute = attribute?;
+            let value = attribute.normalized_value()?.into_owned();
+            let qname = attribute.qname();
+            if qname.is_xmlns() {
+                let prefix = qname.prefix().map_or(&b""[..], |_| qname.local_name());
+                // superfluous declarations are omitted
+                if namespaces.resolve(prefix).unwrap_or(b"") == &*value {
+                    continue;
+                }
+                declarations.push((prefix.to_vec(), value.clone()));
+            }
+            attributes.push((attribute.key, value));
+        }
+        namespaces.open(declarations);
+        attributes.sort_by_cached_key(|&(key, _)| canonical_order(key, namespaces));
+
+        let mut content = e.name().to_vec();
+        for (key, value) in attributes {
+            content.push(b' ');
+            content.extend_from_slice(key);
+            content.extend_from_slice(b"=\"");
+            content.extend_from_slice(&escape_canonical(&value, true));
+            content.push(b'"');
+        }
+        self.write_wrapped(b"<", &content, b">")
+    }
+
+    /// Removes the namespaces declared by the last canonical start tag from the scope
+    fn close_canonical_scope(&mut self) {
+        if let Some(namespaces) = self.canonical.as_mut() {
+            namespaces.close();
+        }
+    }
+
+    /// Writes a canonical comment or processing instruction, separated from the root element by
+    /// a line feed outside of it
+    fn write_canonical_node(
+        &mut self,
+        top_level: bool,
+        before: &[u8],
+        value: &[u8],
+        after: &[u8],
+    ) -> Result<()> {
+        if top_level && self.has_root {
+            self.write(b"\n")?;
+        }
+        self.write_wrapped(before, value, after)?;
+        if top_level && !self.has_root {
+            self.write(b"\n")?;
+        }
+        Ok(())
+    }
+
+    /// Writes a `Start` or `Empty` event, with the attributes requoted and wrapped if needed
+    fn write_start(&mut self, e: &BytesStart, after: &[u8]) -> Result<()> {
+        let rewritten;
+        let content = if self.canonical_attributes {
+            let mut declarations = Vec::new();
+            for attribute in e.attributes() {
+                let attribute = attribute?;
+                let qname = attribute.qname();
+                if qname.is_xmlns() {
+                    let prefix = qname.prefix().map_or(&b""[..], |_| qname.local_name());
+                    declarations.push((prefix.to_vec(), attribute.value.into_owned()));
+                }
+            }
+            self.attribute_namespaces.open(declarations);
+            let namespaces = &self.attribute_namespaces;
+            let result = rewrite_attributes(e, self.attribute_quote, Some(namespaces));
+            if after == b"/>" {
+                self.attribute_namespaces.close();
+            }
+            rewritten = result?;
+            &rewritten
+        } else if self.attribute_quote.is_some() {
+            rewritten = rewrite_attributes(e, self.attribute_quote, None)?;
+            &rewritten
+        } else {
+            &**e
+        };
+        let len = 1 + content.len() + after.len();
+        let wrapped = match self.indent {
+            Some(ref i) if matches!(i.wrap_attributes, Some(max) if len > max) => {
+                wrap_attributes(content, e.name().len(), i)
+            }
+            _ => None,
+        };
+        match wrapped {
+            Some(wrapped) => self.write_wrapped(b"<", &wrapped, after),
+            None => self.write_wrapped(b"<", content, after),
+        }
+    }
+
+    /// Writes `content` as CDATA, splitting the sections around any `]]>`
+    fn write_cdata(&mut self, content: &[u8]) -> Result<()> {
+        let mut content = content;
+        if let Some(i) = find_cdata_end(content) {
+            if !self.split_cdata {
+                return Err(Error::UnescapedCData(i));
+            }
+        }
+        self.write(b"<![CDATA[")?;
+        while let Some(i) = find_cdata_end(content) {
+            // keep `]]` in this section and start the next one with `>`
+            self.write(&content[..i + 2])?;
+            self.write(b"]]><![CDATA[")?;
+            content = &content[i + 2..];
+        }
+        self.write(content)?;
+        self.write(b"]]>")
+    }
+
+    /// Checks that `event` keeps the document well-formed
+    fn validate_event(&self, event: &Event) -> Result<()> {
+        match *event {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                if self.has_root && self.opened_starts.is_empty() {
+                    return Err(Error::NotWellFormed(
+                        "document must have a single root element".to_string(),
+                    ));
+                }
+                check_name(e.name())?;
+                for attribute in e.attributes() {
+                    let attribute = match attribute {
+                        Err(Error::DuplicatedAttribute(..)) => return Err(duplicated_attribute(e)),
+                        attribute => attribute?,
+                    };
+                    check_name(attribute.key)?;
+                    check_escaped(&attribute.value)?;
+                }
+            }
+            Event::End(ref e) => {
+                let expected = match self.opened_starts.last() {
+                    Some(&start) => &self.opened_buffer[start..],
+                    None => b"",
+                };
+                if e.name() != expected {
+                    return Err(Error::NotWellFormed(format!(
+                        "end tag `{}` does not match opened element `{}`",
+                        String::from_utf8_lossy(e.name()),
+                        String::from_utf8_lossy(expected)
+                    )));
+                }
+            }
+            Event::Text(ref e) => check_escaped(e)?,
+            Event::Comment(ref e) => check_comment(e)?,
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Forgets the last opened element, checking that it is `name` if `check_end_names` is set
+    fn close_opened(&mut self, name: &[u8]) -> Result<()> {
+        if self.check_end_names {
+            let expected = match self.opened_starts.last() {
+                Some(&start) => &self.opened_buffer[start..],
+                None => b"",
+            };
+            if name != expected {
+                return Err(Error::EndEventMismatch {
+                    expected: from_utf8(expected).unwrap_or("").to_owned(),
+                    found: from_utf8(name).unwrap_or("").to_owned(),
+                });
+            }
+        }
+        if let Some(start) = self.opened_starts.pop() {
+            self.opened_buffer.truncate(start);
+        }
+        Ok(())
+    }
+
+    /// Writes the UTF-8 byte order mark (`EF BB BF`).
+    ///
+    /// Returns `Error::MisplacedDeclaration` if some content has already been written.
+    pub fn write_bom(&mut self) -> Result<()> {
+        if self.written {
+            return Err(Error::MisplacedDeclaration);
+        }
+        self.writer
+            .write_all(&[0xEF, 0xBB, 0xBF])
+            .map_err(Error::Io)?;
+        self.bytes_written += 3;
+        Ok(())
+    }
+
+    /// Writes an XML declaration (`<?xml version="..." encoding="..." standalone="..."?>`).
+    ///
+    /// See [`BytesDecl::new`] for the meaning of the arguments. The `encoding` of a writer
+    /// created with [`with_encoding`] is always declared, in place of the given one.
+    ///
+    /// [`BytesDecl::new`]: events/struct.BytesDecl.html#method.new
+    /// [`with_encoding`]: #method.with_encoding
+    pub fn write_declaration(
+        &mut self,
+        version: &[u8],
+        encoding: Option<&[u8]>,
+        standalone: Option<&[u8]>,
+    ) -> Result<()> {
+        #[cfg(feature = "encoding")]
+        let encoding = self
+            .encoding
+            .map_or(encoding, |encoding| Some(encoding.name().as_bytes()));
+        self.write_event(Event::Decl(BytesDecl::new(version, encoding, standalone)))?;
+        Ok(())
+    }
+
+    /// Writes a comment (`<!--text-->`).
+    ///
+    /// Returns `Error::NotWellFormed` if `text` contains `--` or ends with `-`, which are not
+    /// allowed in comments. Use [`BytesText::from_comment_lossy`] to write such text anyway.
+    ///
+    /// [`BytesText::from_comment_lossy`]: events/struct.BytesText.html#method.from_comment_lossy
+    pub fn write_comment(&mut self, text: &[u8]) -> Result<()> {
+        check_comment(text)?;
+        self.write_event(Event::Comment(BytesText::from_escaped(text)))?;
+        Ok(())
+    }
+
+    /// Writes a processing instruction (`<?target data?>`), without data if `data` is empty.
+    ///
+    /// Returns `Error::NotWellFormed` if `target` is not a valid name or is the reserved `xml`
+    /// (in any case), or if `data` contains `?>`, as checked by [`BytesText::pi`].
+    ///
+    /// [`BytesText::pi`]: events/struct.BytesText.html#method.pi
+    pub fn write_pi(&mut self, target: &[u8], data: &[u8]) -> Result<()> {
+        self.write_event(Event::PI(BytesText::pi(target, data)?))?;
+        Ok(())
+    }
+
+    /// Writes bytes
+    #[inline]
+    pub fn write(&mut self, value: &[u8]) -> Result<()> {
+        self.written = true;
+        self.writer.write_all(value).map_err(Error::Io)?;
+        self.bytes_written += value.len() as u64;
+        Ok(())
+    }
+
+    /// Writes a trusted, already serialized markup `fragment` as is, e.g. the output of a
+    /// template.
+    ///
+    /// The fragment bypasses all escaping, transcoding, name checks and validation: it must be
+    /// well-formed at the current position for the output to be. With indentation, the fragment
+    /// is written on a new indented line like an element, its own content being left untouched,
+    /// and the next event starts a new line.
+    pub fn write_raw(&mut self, fragment: &[u8]) -> Result<()> {
+        self.write_pending_start()?;
+        self.write_wrapped(b"", fragment, b"")?;
+        if let Some(i) = self.indent.as_mut() {
+            i.should_line_break = i.mixed_depth.is_none();
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn write_wrapped(&mut self, before: &[u8], value: &[u8], after: &[u8]) -> Result<()> {
+        if let Some(ref i) = self.indent {
+            if i.should_line_break {
+                self.writer.write_all(&i.newline).map_err(Error::Io)?;
+                self.writer
+                    .write_all(&i.indents[..i.indents_len])
+                    .map_err(Error::Io)?;
+                self.bytes_written += (i.newline.len() + i.indents_len) as u64;
+            }
+        }
+        self.write(before)?;
+        self.write(value)?;
+        self.write(after)?;
+        Ok(())
+    }
+
+    /// Consumes this `Writer`, returning the underlying writer once the `Start` event deferred by
+    /// [`collapse_empty_elements`], if any, is written.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use quick_xml::events::{BytesStart, Event};
+    /// use quick_xml::Writer;
+    ///
+    /// let mut writer = Writer::new(Vec::new());
+    /// writer.collapse_empty_elements(true);
+    /// writer.write_event(Event::Start(BytesStart::borrowed_name(b"tag")))?;
+    /// assert_eq!(writer.try_into_inner()?, b"<tag>");
+    /// # Ok::<(), quick_xml::Error>(())
+    /// ```
+    ///
+    /// [`collapse_empty_elements`]: #method.collapse_empty_elements
+    pub fn try_into_inner(mut self) -> Result<W> {
+        self.write_pending_start()?;
+        Ok(self.writer)
+    }
+
+    /// Manually write a newline and indentation at the proper level.
+    ///
+    /// This can be used when the heuristic to line break and indent after any [Event] apart
+    /// from [Text] fails such as when a [Start] occurs directly after [Text]: once an element
+    /// has text content, none of its events are indented until its [End] event.
+    /// The next event is written right after the indentation, without another line break.
+    /// This method will do nothing if `Writer` was not constructed with `new_with_indent`.
+    ///
+    /// [Event]: events/enum.Event.html
+    /// [Text]: events/enum.Event.html#variant.Text
+    /// [Start]: events/enum.Event.html#variant.Start
+    /// [End]: events/enum.Event.html#variant.End
+    pub fn write_indent(&mut self) -> Result<()> {
+        self.write_pending_start()?;
+        if let Some(i) = self.indent.as_mut() {
+            self.written = true;
+            self.writer.write_all(&i.newline).map_err(Error::Io)?;
+            self.writer
+                .write_all(&i.indents[..i.indents_len])
+                .map_err(Error::Io)?;
+            self.bytes_written += (i.newline.len() + i.indents_len) as u64;
+            i.should_line_break = false;
+        }
+        Ok(())
+    }
+
+    /// Serializes `value` with `tag_name` as root tag at the current position and indentation.
+    ///
+    /// Like with `write_event_async`, the value is formatted into a buffer with the state of
+    /// this writer, which is given back once done, and the buffer is then written.
+    #[cfg(feature = "serialize")]
+    fn write_serializable<T: Serialize>(
+        &mut self,
+        tag_name: &str,
+        value: &T,
+    ) -> std::result::Result<(), DeError> {
+        self.write_pending_start()?;
+        let mut serializer = Serializer::with_root(self.split_state(Vec::new()), Some(tag_name));
+        let result = value.serialize(&mut serializer);
+        let bytes = self.join_state(serializer.into_inner());
+        result?;
+        self.writer.write_all(&bytes).map_err(Error::Io)?;
+        Ok(())
+    }
+
+    /// Provides a simple, high-level API for writing XML elements.
+    ///
+    /// Returns an [`ElementWriter`] that simplifies setting attributes and writing
+    /// content inside the element.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use quick_xml::Result;
+    /// # fn main() -> Result<()> {
+    /// use quick_xml::Writer;
+    ///
+    /// let mut writer = Writer::new(Vec::new());
+    ///
+    /// // writes <tag attr1="value1"/>
+    /// writer
+    ///     .create_element(b"tag")
+    ///     .with_attribute(("attr1", "value1"))
+    ///     .write_empty()?;
+    ///
+    /// // writes <tag attr1="value1" attr2="value2">with some text inside</tag>
+    /// writer
+    ///     .create_element(b"tag")
+    ///     .with_attributes(vec![("attr1", "value1"), ("attr2", "value2")])
+    ///     .write_text_content("with some text inside")?;
+    ///
+    /// // writes <tag><fruit quantity="0">apple</fruit><fruit quantity="1">orange</fruit></tag>
+    /// writer
+    ///     .create_element(b"tag")
+    ///     .write_inner_content(|writer| {
+    ///         let fruits = ["apple", "orange"];
+    ///         for (quant, item) in fruits.iter().enumerate() {
+    ///             writer
+    ///                 .create_element(b"fruit")
+    ///                 .with_attribute(("quantity", quant.to_string().as_str()))
+    ///                 .write_text_content(item)?;
+    ///         }
+    ///         Ok(())
+    ///     })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`ElementWriter`]: struct.ElementWriter.html
+    pub fn create_element<'a, N>(&'a mut self, name: &'a N) -> ElementWriter<'a, W>
+    where
+        N: 'a + AsRef<[u8]> + ?Sized,
+    {
+        ElementWriter {
+            writer: self,
+            start_tag: BytesStart::borrowed_name(name.as_ref()),
+        }
+    }
+
+    /// Returns a [`TextWriter`] streaming escaped text content at the current position.
+    ///
+    /// This avoids building large texts in memory before writing them as a `Text` event.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use quick_xml::{Result, Writer};
+    /// # fn main() -> Result<()> {
+    /// use std::io::Write;
+    ///
+    /// let mut writer = Writer::new(Vec::new());
+    /// writer.create_element("log").write_inner_content(|writer| {
+    ///     let mut text = writer.text_writer();
+    ///     for i in 0..3 {
+    ///         write!(text, "<{}>", i)?;
+    ///     }
+    ///     Ok(())
+    /// })?;
+    /// assert_eq!(
+    ///     writer.into_inner(),
+    ///     b"<log>&lt;0&gt;&lt;1&gt;&lt;2&gt;</log>".as_ref()
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`TextWriter`]: struct.TextWriter.html
+    pub fn text_writer(&mut self) -> TextWriter<'_, W> {
+        TextWriter {
+            writer: self,
+            written: false,
+            #[cfg(feature = "encoding")]
+            incomplete: Vec::new(),
+        }
+    }
+
+    /// Streams all the content of `reader` as escaped text content at the current position,
+    /// returning the number of bytes read.
+    ///
+    /// The content is read and escaped in chunks with a [`TextWriter`], so that it never needs
+    /// to be held in memory at once. Like a `Text` event, it is written inline.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use quick_xml::{Result, Writer};
+    /// # fn main() -> Result<()> {
+    /// let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    /// writer.create_element("log").write_inner_content(|writer| {
+    ///     assert_eq!(writer.write_text_from_reader(&b"a < b"[..])?, 5);
+    ///     Ok(())
+    /// })?;
+    /// assert_eq!(writer.into_inner(), b"<log>a &lt; b</log>".as_ref());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`TextWriter`]: struct.TextWriter.html
+    pub fn write_text_from_reader<R: Read>(&mut self, mut reader: R) -> Result<u64> {
+        self.write_pending_start()?;
+        io::copy(&mut reader, &mut self.text_writer()).map_err(Error::Io)
+    }
+}
+
+#[cfg(feature = "async-tokio")]
+impl<W: AsyncWrite + Unpin> Writer<W> {
+    /// Creates a Writer from a tokio `AsyncWrite`, to write events with [`write_event_async`].
+    ///
+    /// This is the same as [`new`]: use [`new_with_indent`] to indent the written events.
+    ///
+    /// [`write_event_async`]: #method.write_event_async
+    /// [`new`]: #method.new
+    /// [`new_with_indent`]: #method.new_with_indent
+    pub fn from_async_writer(inner: W) -> Writer<W> {
+        Writer::new(inner)
+    }
+
+    /// Writes the given event to the underlying asynchronous writer.
+    ///
+    /// The event is validated and formatted like by `write_event` into a buffer kept by this
+    /// writer, which is then written by the returned future, resolving to the number of bytes of
+    /// the event. The underlying writer is not flushed. The bytes are counted by
+    /// [`bytes_written`] as soon as they are formatted.
+    ///
+    /// The state of this writer is updated as soon as the event is formatted. If the returned
+    /// future is dropped before completion or fails with an I/O error, the bytes which are not
+    /// written yet are kept, and written first by the next call to `write_event_async` or
+    /// [`flush_async`], so that the output still matches the state of this writer.
+    ///
+    /// [`bytes_written`]: #method.bytes_written
+    /// [`flush_async`]: #method.flush_async
+    pub fn write_event_async<'a, E: AsRef<Event<'a>>>(
+        &mut self,
+        event: E,
+    ) -> impl Future<Output = Result<usize>> + '_ {
+        let mut buffer = self.split_state(Vec::new());
+        let result = buffer.write_event(event);
+        self.push_unsent(buffer);
+        let mut result = Some(result);
+        future::poll_fn(move |cx| {
+            if let Some(Err(_)) = result {
+                return Poll::Ready(result.take().unwrap());
+            }
+            match self.poll_write_unsent(cx) {
+                Poll::Ready(Ok(())) => Poll::Ready(result.take().unwrap_or(Ok(0))),
+                Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+                Poll::Pending => Poll::Pending,
+            }
+        })
+    }
+
+    /// Writes the bytes not written yet by [`write_event_async`], and the `Start` event deferred
+    /// by [`collapse_empty_elements`], if any, then flushes the underlying asynchronous writer.
+    ///
+    /// [`write_event_async`]: #method.write_event_async
+    /// [`collapse_empty_elements`]: #method.collapse_empty_elements
+    pub fn flush_async(&mut self) -> impl Future<Output = Result<()>> + '_ {
+        let mut buffer = self.split_state(Vec::new());
+        let result = buffer.write_pending_start();
+        self.push_unsent(buffer);
+        let mut result = Some(result);
+        future::poll_fn(move |cx| {
+            if let Some(Err(_)) = result {
+                return Poll::Ready(result.take().unwrap());
+            }
+            match self.poll_write_unsent(cx) {
+                Poll::Ready(Ok(())) => Pin::new(&mut self.writer).poll_flush(cx).map_err(Error::Io),
+                Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+                Poll::Pending => Poll::Pending,
+            }
+        })
+    }
+
+    /// Gets back the state moved into `buffer` and keeps its bytes to be written
+    fn push_unsent(&mut self, buffer: Writer<Vec<u8>>) {
+        let bytes = self.join_state(buffer);
+        if self.unsent.is_empty() {
+            self.unsent = bytes;
+        } else {
+            self.unsent.extend_from_slice(&bytes);
+        }
+    }
+
+    /// Writes all the bytes kept by `push_unsent` to the underlying writer
+    fn poll_write_unsent(&mut self, cx: &mut Context) -> Poll<Result<()>> {
+        while !self.unsent.is_empty() {
+            match Pin::new(&mut self.writer).poll_write(cx, &self.unsent) {
+                Poll::Ready(Ok(0)) => {
+                    return Poll::Ready(Err(Error::Io(io::ErrorKind::WriteZero.into())))
+                }
+                Poll::Ready(Ok(n)) => {
+                    self.unsent.drain(..n);
+                }
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(Error::Io(e))),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Writer<Vec<u8>> {
+    /// Consumes this `Writer`, returning the written XML as a `String`, without copying it.
+    ///
+    /// A `Start` event deferred by [`collapse_empty_elements`] is written first. Returns
+    /// `Error::Utf8` if some event contained bytes which are not valid UTF-8.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use quick_xml::Writer;
+    ///
+    /// let mut writer = Writer::new(Vec::new());
+    /// writer
+    ///     .create_element("tag")
+    ///     .write_text_content("text")
+    ///     .unwrap();
+    /// assert_eq!(writer.into_string().unwrap(), "<tag>text</tag>");
+    /// ```
+    ///
+    /// [`collapse_empty_elements`]: #method.collapse_empty_elements
+    pub fn into_string(self) -> Result<String> {
+        let writer = self.try_into_inner()?;
+        String::from_utf8(writer).map_err(|e| Error::Utf8(e.utf8_error()))
+    }
+}
+
+impl<W: Write> Writer<BufWriter<W>> {
+    /// Creates a Writer which buffers events in memory before writing them to a generic Write.
+    ///
+    /// Bytes reach `inner` only when the buffer is full or when [`flush`] is called, which
+    /// reduces the number of system calls when writing many small events. Errors happening
+    /// while the buffer is flushed on drop are ignored, so call `flush` once done writing.
+    ///
+    /// [`flush`]: #method.flush
+    pub fn new_buffered(inner: W) -> Writer<BufWriter<W>> {
+        Writer::new(BufWriter::new(inner))
+    }
+}
+
+impl<F: fmt::Write> Writer<FmtWriter<F>> {
+    /// Creates a Writer writing into a `fmt::Write` implementor, e.g. a `String`.
+    ///
+    /// All the written content must be valid UTF-8, or writing fails with an `io::Error` of
+    /// kind `InvalidData`. Get `inner` back with [`into_fmt_inner`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use quick_xml::Writer;
+    ///
+    /// let mut writer = Writer::from_fmt_writer(String::new());
+    /// writer.create_element("tag").write_text_content("text")?;
+    /// assert_eq!(writer.into_fmt_inner()?, "<tag>text</tag>");
+    /// # Ok::<(), quick_xml::Error>(())
+    /// ```
+    ///
+    /// [`into_fmt_inner`]: #method.into_fmt_inner
+    pub fn from_fmt_writer(inner: F) -> Writer<FmtWriter<F>> {
+        Writer::new(FmtWriter {
+            writer: inner,
+            incomplete: Vec::new(),
+        })
+    }
+
+    /// Consumes this `Writer`, returning the underlying `fmt::Write` implementor once the `Start`
+    /// event deferred by [`collapse_empty_elements`], if any, is written.
+    ///
+    /// Returns `Error::Utf8` if the written content ends with an incomplete UTF-8 character.
+    ///
+    /// [`collapse_empty_elements`]: #method.collapse_empty_elements
+    pub fn into_fmt_inner(self) -> Result<F> {
+        let writer = self.try_into_inner()?;
+        match from_utf8(&writer.incomplete) {
+            Err(e) => Err(Error::Utf8(e)),
+            Ok(_) => Ok(writer.writer),
+        }
+    }
+}
+
+/// The options of a [`Writer`], to create several writers with the same settings.
+///
+/// Each option has the same meaning and default value as the `Writer` method of the same name.
+///
+/// # Example
+///
+/// ```rust
+/// use quick_xml::events::{BytesStart, Event};
+/// use quick_xml::WriterConfig;
+///
+/// let mut writer = WriterConfig::new()
+///     .indent(b' ', 2)
+///     .validate(true)
+///     .attribute_quote(b'\'')
+///     .build(Vec::new());
+/// let mut start = BytesStart::borrowed_name(b"root");
+/// start.push_attribute(("a", "1"));
+/// writer.write_event(Event::Start(start))?;
+/// writer.write_event(Event::Empty(BytesStart::borrowed_name(b"child")))?;
+/// writer.write_end()?;
+/// assert_eq!(writer.into_string()?, "<root a='1'>\n  <child/>\n</root>");
+/// # Ok::<(), quick_xml::Error>(())
+/// ```
+///
+/// [`Writer`]: struct.Writer.html
+#[derive(Clone, Debug)]
+pub struct WriterConfig {
+    indent: Option<(u8, usize)>,
+    newline: Vec<u8>,
+    wrap_attributes: Option<usize>,
+    wrapped_tag_end: WrappedTagEnd,
+    check_escaped_text: bool,
+    split_cdata: bool,
+    check_declaration: bool,
+    check_end_names: bool,
+    attribute_quote: Option<QuoteStyle>,
+    canonical_attributes: bool,
+    validate: bool,
+    canonical: bool,
+    collapse_empty_elements: bool,
+    #[cfg(feature = "encoding")]
+    encoding: Option<&'static Encoding>,
+}
+
+impl Default for WriterConfig {
+    fn default() -> Self {
+        WriterConfig {
+            indent: None,
+            newline: b"\n".to_vec(),
+            wrap_attributes: None,
+            wrapped_tag_end: WrappedTagEnd::AfterLastAttribute,
+            check_escaped_text: false,
+            split_cdata: false,
+            check_declaration: false,
+            check_end_names: false,
+            attribute_quote: None,
+            canonical_attributes: false,
+            validate: false,
+            canonical: false,
+            collapse_empty_elements: false,
+            #[cfg(feature = "encoding")]
+            encoding: None,
+        }
+    }
+}
+
+impl WriterConfig {
+    /// Creates the default configuration, the one of `Writer::new`.
+    pub fn new() -> WriterConfig {
+        WriterConfig::default()
+    }
+
+    /// Creates a `Writer` with this configuration, writing into `inner`.
+    pub fn build<W>(&self, inner: W) -> Writer<W> {
+        let mut writer = Writer::new(inner);
+        writer.indent = self
+            .indent
+            .map(|(indent_char, indent_size)| Indentation::new(indent_char, indent_size));
+        writer
+            .newline(&self.newline)
+            .wrap_attributes(self.wrap_attributes)
+            .wrapped_tag_end(self.wrapped_tag_end)
+            .check_escaped_text(self.check_escaped_text)
+            .split_cdata(self.split_cdata)
+            .check_declaration(self.check_declaration)
+            .check_end_names(self.check_end_names)
+            .canonical_attributes(self.canonical_attributes)
+            .validate(self.validate)
+            .canonical(self.canonical)
+            .collapse_empty_elements(self.collapse_empty_elements);
+        writer.attribute_quote = self.attribute_quote;
+        #[cfg(feature = "encoding")]
+        {
+            writer.encoding = self.encoding.map(|encoding| encoding.output_encoding());
+        }
+        writer
+    }
+
+    /// Indents events with `indent_size` times `indent_char`, like `Writer::new_with_indent`.
+    pub fn indent(&mut self, indent_char: u8, indent_size: usize) -> &mut WriterConfig {
+        self.indent = Some((indent_char, indent_size));
+        self
+    }
+
+    /// Changes the line break written before indented events (`\n` by default).
+    pub fn newline(&mut self, newline: &[u8]) -> &mut WriterConfig {
+        self.newline = newline.to_vec();
+        self
+    }
+
+    /// See `Writer::wrap_attributes`.
+    pub fn wrap_attributes(&mut self, max_len: Option<usize>) -> &mut WriterConfig {
+        self.wrap_attributes = max_len;
+        self
+    }
+
+    /// See `Writer::wrapped_tag_end`.
+    pub fn wrapped_tag_end(&mut self, style: WrappedTagEnd) -> &mut WriterConfig {
+        self.wrapped_tag_end = style;
+        self
+    }
+
+    /// See `Writer::check_escaped_text`.
+    pub fn check_escaped_text(&mut self, val: bool) -> &mut WriterConfig {
+        self.check_escaped_text = val;
+        self
+    }
+
+    /// See `Writer::split_cdata`.
+    pub fn split_cdata(&mut self, val: bool) -> &mut WriterConfig {
+        self.split_cdata = val;
+        self
+    }
+
+    /// See `Writer::check_declaration`.
+    pub fn check_declaration(&mut self, val: bool) -> &mut WriterConfig {
+        self.check_declaration = val;
+        self
+    }
+
+    /// See `Writer::check_end_names`.
+    pub fn check_end_names(&mut self, val: bool) -> &mut WriterConfig {
+        self.check_end_names = val;
+        self
+    }
+
+    /// See `Writer::attribute_quote`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `quote` is not `b'"'` or `b'\''`.
+    pub fn attribute_quote(&mut self, quote: u8) -> &mut WriterConfig {
+        let style = match quote {
+            b'"' => QuoteStyle::Double,
+            b'\'' => QuoteStyle::Single,
+            _ => panic!("attribute quote must be `\"` or `'`"),
+        };
+        self.attribute_quote_style(style)
+    }
+
+    /// See `Writer::attribute_quote_style`.
+    pub fn attribute_quote_style(&mut self, style: QuoteStyle) -> &mut WriterConfig {
+        self.attribute_quote = Some(style);
+        self
+    }
+
+    /// See `Writer::canonical_attributes`.
+    pub fn canonical_attributes(&mut self, val: bool) -> &mut WriterConfig {
+        self.canonical_attributes = val;
+        self
+    }
+
+    /// See `Writer::validate`.
+    pub fn validate(&mut self, val: bool) -> &mut WriterConfig {
+        self.validate = val;
+        self
+    }
+
+    /// See `Writer::canonical`.
+    pub fn canonical(&mut self, val: bool) -> &mut WriterConfig {
+        self.canonical = val;
+        self
+    }
+
+    /// See `Writer::collapse_empty_elements`.
+    pub fn collapse_empty_elements(&mut self, val: bool) -> &mut WriterConfig {
+        self.collapse_empty_elements = val;
+        self
+    }
+
+    /// Transcodes events into `encoding`, like `Writer::with_encoding`.
+    #[cfg(feature = "encoding")]
+    pub fn encoding(&mut self, encoding: &'static Encoding) -> &mut WriterConfig {
+        self.encoding = Some(encoding);
+        self
+    }
+}
+
+/// Copies all events from `reader` to `writer`, until `Event::Eof`.
+///
+/// Each event is passed to `filter` first, which returns the event to write, possibly
+/// transformed, or `None` to drop it. `Event::Eof` itself is not passed to `filter`.
+///
+/// The content of `CData` events, escaped by the `Reader`, is unescaped before being passed to
+/// `filter`, so that CDATA sections are written back unchanged.
+///
+/// # Examples
+///
+/// ```rust
+/// use quick_xml::events::{BytesStart, Event};
+/// use quick_xml::{copy_all, Reader, Writer};
+///
+/// let mut reader = Reader::from_str("<a><!--skipped--><b/></a>");
+/// let mut writer = Writer::new(Vec::new());
+/// copy_all(&mut reader, &mut writer, |event| match event {
+///     Event::Comment(_) => None,
+///     Event::Empty(_) => Some(Event::Empty(BytesStart::borrowed_name(b"c"))),
+///     e => Some(e),
+/// })
+/// .unwrap();
+/// assert_eq!(writer.into_inner(), b"<a><c/></a>");
+/// ```
+pub fn copy_all<R, W, F>(
+    reader: &mut Reader<R>,
+    writer: &mut Writer<W>,
+    mut filter: F,
+) -> Result<()>
+where
+    R: BufRead,
+    W: Write,
+    F: for<'e> FnMut(Event<'e>) -> Option<Event<'e>>,
+{
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::Eof => return Ok(()),
+            Event::CData(e) => {
+                if let Some(event) = filter(raw_cdata(&e)?) {
+                    writer.write_event(event)?;
+                }
+            }
+            event => {
+                if let Some(event) = filter(event) {
+                    writer.write_event(event)?;
+                }
+            }
+        }
+        buf.clear();
+    }
+}
+
+/// Copies the document read from `reader` into `writer`, indented with `indent` (the character
+/// and size of one indentation level) or minified if `indent` is `None`.
+///
+/// Events are streamed through a [`Writer`], so elements with mixed content (containing
+/// non-whitespace text or CDATA) are not indented from their first text on, like with
+/// [`Writer::new_with_indent`]. Whitespace-only text is dropped, except from that point on
+/// inside of these elements. All other events, including the attributes of elements, are kept
+/// unchanged.
+///
+/// # Examples
+///
+/// ```rust
+/// let xml = "<a>\n<b>x</b><c>1 <d>2</d></c></a>";
+/// let mut indented = Vec::new();
+/// quick_xml::reformat(xml.as_bytes(), &mut indented, Some((b' ', 2))).unwrap();
+/// assert_eq!(indented, b"<a>\n  <b>x</b>\n  <c>1 <d>2</d></c>\n</a>".as_ref());
+///
+/// let mut minified = Vec::new();
+/// quick_xml::reformat(&*indented, &mut minified, None).unwrap();
+/// assert_eq!(minified, b"<a><b>x</b><c>1 <d>2</d></c></a>".as_ref());
+/// ```
+///
+/// [`Writer`]: struct.Writer.html
+/// [`Writer::new_with_indent`]: struct.Writer.html#method.new_with_indent
+pub fn reformat<R, W>(reader: R, writer: W, indent: Option<(u8, usize)>) -> Result<()>
+where
+    R: BufRead,
+    W: Write,
+{
+    let mut reader = Reader::from_reader(reader);
+    // minifying is indenting with nothing, which still tracks mixed content
+    let mut writer = match indent {
+        Some((indent_char, indent_size)) => {
+            Writer::new_with_indent(writer, indent_char, indent_size)
+        }
+        None => {
+            let mut writer = Writer::new_with_indent(writer, b' ', 0);
+            writer.newline(b"");
+            writer
+        }
+    };
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::Eof => return Ok(()),
+            Event::Text(ref e)
+                if !writer.in_mixed_content() && e.iter().all(|&b| is_whitespace(b)) => {}
+            Event::CData(e) => {
+                writer.write_event(raw_cdata(&e)?)?;
+            }
+            e => {
+                writer.write_event(e)?;
+            }
+        }
+        buf.clear();
+    }
+}
+
+/// Converts the content of a `CData` event read by a `Reader`, which escapes it, to the content
+/// written as is by a `Writer`
+fn raw_cdata(content: &BytesText) -> Result<Event<'static>> {
+    let content = content.unescaped()?.into_owned();
+    Ok(Event::CData(BytesText::from_escaped(content)))
+}
+
+/// Rewrites the `content` of a start tag with each attribute on its own line, aligned after the
+/// name of `name_len` bytes, or returns `None` if the attributes cannot be parsed
+fn wrap_attributes(content: &[u8], name_len: usize, indent: &Indentation) -> Option<Vec<u8>> {
+    let mut wrapped = content[..name_len].to_vec();
+    let mut attributes = Attributes::new(content, name_len);
+    attributes.with_checks(false);
+    let mut start = name_len;
+    let mut first = true;
+    while let Some(attribute) = attributes.next() {
+        attribute.ok()?;
+        let end = attributes.position;
+        let attribute = &content[start..end];
+        let attribute = match attribute.iter().position(|&b| !is_whitespace(b)) {
+            Some(i) => &attribute[i..],
+            None => attribute,
+        };
+        if first {
+            wrapped.push(b' ');
+            first = false;
+        } else {
+            wrapped.extend_from_slice(&indent.newline);
+            wrapped.extend_from_slice(&indent.indents[..indent.indents_len]);
+            // `<` and the space following the name
+            wrapped.resize(wrapped.len() + name_len + 2, b' ');
+        }
+        wrapped.extend_from_slice(attribute);
+        start = end;
+    }
+    // an attribute was not parsed, e.g. one without value at the end
+    if !content[start..].iter().all(|&b| is_whitespace(b)) {
+        return None;
+    }
+    if indent.wrapped_tag_end == WrappedTagEnd::OwnLine {
+        wrapped.extend_from_slice(&indent.newline);
+        wrapped.extend_from_slice(&indent.indents[..indent.indents_len]);
+    }
+    Some(wrapped)
+}
+
+/// An `io::Write` implementor escaping all bytes written to it as text content, created by
+/// [`Writer::text_writer`].
+///
+/// Escaping is done byte by byte, so the content can be split anywhere between writes, even
+/// inside of a multi-byte character. The state of the `Writer` is updated when this value is
+/// dropped, as if a single `Text` event had been written.
+///
+/// With [`Writer::with_encoding`], the content is transcoded like the content of `Text` events:
+/// bytes which are not valid UTF-8 are rejected with an `io::Error` of kind `InvalidData`, and a
+/// character split between writes is transcoded once complete (or lost if this value is dropped
+/// before).
+///
+/// [`Writer::text_writer`]: struct.Writer.html#method.text_writer
+/// [`Writer::with_encoding`]: struct.Writer.html#method.with_encoding
+pub struct TextWriter<'a, W: Write> {
+    writer: &'a mut Writer<W>,
+    /// whether some non-empty content has been written
+    written: bool,
+    /// start of a character split by the last write, if transcoding
+    #[cfg(feature = "encoding")]
+    incomplete: Vec<u8>,
 }
 
-impl<W: Write> Writer<W> {
-    /// Creates a Writer from a generic Write
-    pub fn new(inner: W) -> Writer<W> {
-        Writer {
-            writer: inner,
-            indent: None,
+impl<'a, W: Write> Write for TextWriter<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !buf.is_empty() {
+            self.writer.write_pending_start().map_err(|e| match e {
+                Error::Io(e) => e,
+                e => io::Error::new(io::ErrorKind::InvalidData, e),
+            })?;
+            self.written = true;
+            let escaped = escape(buf);
+            #[cfg(feature = "encoding")]
+            let escaped = match self.writer.encoding {
+                Some(encoding) if encoding != UTF_8 => {
+                    let (valid, rest) = split_incomplete(&self.incomplete, &escaped)?;
+                    let encoded = encode_with_references(valid.as_bytes(), encoding)
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                    self.incomplete = rest;
+                    Cow::Owned(encoded)
+                }
+                _ => escaped,
+            };
+            self.writer.writer.write_all(&escaped)?;
+            self.writer.bytes_written += escaped.len() as u64;
         }
+        Ok(buf.len())
     }
 
-    /// Creates a Writer with configured whitespace indents from a generic Write
-    pub fn new_with_indent(inner: W, indent_char: u8, indent_size: usize) -> Writer<W> {
-        Writer {
-            writer: inner,
-            indent: Some(Indentation::new(indent_char, indent_size)),
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.writer.flush()
+    }
+}
+
+impl<'a, W: Write> Drop for TextWriter<'a, W> {
+    fn drop(&mut self) {
+        // like for `Text` events, empty content does not break the layout
+        if self.written {
+            self.writer.written = true;
+            self.writer.start_mixed_content();
+            if let Some(i) = self.writer.indent.as_mut() {
+                i.should_line_break = false;
+            }
         }
     }
+}
 
-    /// Consumes this `Writer`, returning the underlying writer.
-    pub fn into_inner(self) -> W {
+/// An `io::Write` implementor writing UTF-8 bytes into a `fmt::Write` implementor, created by
+/// [`Writer::from_fmt_writer`].
+///
+/// Bytes which are not valid UTF-8 are rejected with an `io::Error` of kind `InvalidData`. A
+/// character split between writes is written once complete.
+///
+/// The `Writer` only writes bytes, as events hold their content as bytes in any encoding, and
+/// this crate uses no unsafe code to turn them into `str` unchecked: each chunk is validated
+/// once here instead, which costs a scan of bytes already in the cache, before being passed to
+/// `fmt::Write::write_str` without copy.
+///
+/// [`Writer::from_fmt_writer`]: struct.Writer.html#method.from_fmt_writer
+pub struct FmtWriter<F> {
+    writer: F,
+    /// start of a character split by the last write
+    incomplete: Vec<u8>,
+}
+
+impl<F: fmt::Write> Write for FmtWriter<F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let (valid, rest) = split_incomplete(&self.incomplete, buf)?;
         self.writer
+            .write_str(&valid)
+            .map_err(|_| io::Error::from(io::ErrorKind::Other))?;
+        self.incomplete = rest;
+        Ok(buf.len())
     }
 
-    /// Get inner writer, keeping ownership
-    pub fn inner(&mut self) -> &mut W {
-        &mut self.writer
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
     }
+}
 
-    /// Writes the given event to the underlying writer.
-    pub fn write_event<'a, E: AsRef<Event<'a>>>(&mut self, event: E) -> Result<()> {
-        let mut next_should_line_break = true;
-        let result = match *event.as_ref() {
-            Event::Start(ref e) => {
-                let result = self.write_wrapped(b"<", e, b">");
-                if let Some(i) = self.indent.as_mut() {
-                    i.grow();
-                }
-                result
+/// A struct to write an element. Contains methods to add attributes and inner
+/// elements to the element, created by [`Writer::create_element`].
+///
+/// [`Writer::create_element`]: struct.Writer.html#method.create_element
+pub struct ElementWriter<'a, W: Write> {
+    writer: &'a mut Writer<W>,
+    start_tag: BytesStart<'a>,
+}
+
+impl<'a, W: Write> ElementWriter<'a, W> {
+    /// Adds an attribute to this element.
+    pub fn with_attribute<'b, I>(mut self, attr: I) -> Self
+    where
+        I: Into<Attribute<'b>>,
+    {
+        self.start_tag.push_attribute(attr);
+        self
+    }
+
+    /// Adds several attributes to this element.
+    pub fn with_attributes<'b, I>(mut self, attributes: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<Attribute<'b>>,
+    {
+        self.start_tag.extend_attributes(attributes);
+        self
+    }
+
+    /// Writes an empty element (`<name/>`).
+    pub fn write_empty(self) -> Result<&'a mut Writer<W>> {
+        self.writer.write_event(Event::Empty(self.start_tag))?;
+        Ok(self.writer)
+    }
+
+    /// Writes the element with a text content (`<name>text</name>`).
+    ///
+    /// `text` is escaped before being written.
+    pub fn write_text_content(self, text: &str) -> Result<&'a mut Writer<W>> {
+        self.writer
+            .write_event(Event::Start(self.start_tag.to_borrowed()))?;
+        self.writer
+            .write_event(Event::Text(BytesText::from_plain_str(text)))?;
+        self.writer
+            .write_event(Event::End(self.start_tag.to_end()))?;
+        Ok(self.writer)
+    }
+
+    /// Writes the element with a CDATA content (`<name><![CDATA[text]]></name>`).
+    ///
+    /// The content is written as is, so it should be created with `BytesText::from_escaped`.
+    pub fn write_cdata_content(self, text: BytesText) -> Result<&'a mut Writer<W>> {
+        self.writer
+            .write_event(Event::Start(self.start_tag.to_borrowed()))?;
+        self.writer.write_event(Event::CData(text))?;
+        self.writer
+            .write_event(Event::End(self.start_tag.to_end()))?;
+        Ok(self.writer)
+    }
+
+    /// Writes the element, with `value` serialized inside using `tag_name` as its root tag.
+    ///
+    /// The serialized fragment is indented at the nesting level of its hand-written siblings.
+    /// Errors of the underlying writer are returned as `DeError::Xml`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # extern crate serde;
+    /// # use quick_xml::Writer;
+    /// # use serde::Serialize;
+    /// #[derive(Serialize)]
+    /// struct Item {
+    ///     name: &'static str,
+    /// }
+    ///
+    /// let mut writer = Writer::new(Vec::new());
+    /// writer
+    ///     .create_element("Body")
+    ///     .write_serializable("item", &Item { name: "apple" })
+    ///     .unwrap();
+    ///
+    /// assert_eq!(writer.into_inner(), br#"<Body><item name="apple"/></Body>"#.to_vec());
+    /// ```
+    #[cfg(feature = "serialize")]
+    pub fn write_serializable<T: Serialize>(
+        self,
+        tag_name: &str,
+        value: &T,
+    ) -> std::result::Result<&'a mut Writer<W>, DeError> {
+        self.writer
+            .write_event(Event::Start(self.start_tag.to_borrowed()))?;
+        self.writer.write_serializable(tag_name, value)?;
+        self.writer
+            .write_event(Event::End(self.start_tag.to_end()))?;
+        Ok(self.writer)
+    }
+
+    /// Writes the element, with the content written by `closure` inside.
+    pub fn write_inner_content<F>(self, closure: F) -> Result<&'a mut Writer<W>>
+    where
+        F: FnOnce(&mut Writer<W>) -> Result<()>,
+    {
+        self.writer
+            .write_event(Event::Start(self.start_tag.to_borrowed()))?;
+        closure(self.writer)?;
+        self.writer
+            .write_event(Event::End(self.start_tag.to_end()))?;
+        Ok(self.writer)
+    }
+}
+
+/// Returns the content of `e` with all attribute values quoted according to `style` (or with
+/// their original quotes), sorted by `canonical_order` with the namespaces in scope if any
+fn rewrite_attributes(
+    e: &BytesStart,
+    style: Option<QuoteStyle>,
+    sort: Option<&Namespaces>,
+) -> Result<Vec<u8>> {
+    let mut attributes = Vec::new();
+    for attribute in e.attributes().with_positions() {
+        let (attribute, spans) = attribute?;
+        let quote = match style {
+            Some(style) => style.quote_for(&attribute.value),
+            None => e[spans.value.start - 1],
+        };
+        attributes.push((attribute, quote));
+    }
+    if let Some(namespaces) = sort {
+        attributes.sort_by_cached_key(|(attribute, _)| canonical_order(attribute.key, namespaces));
+    }
+    let mut content = e.name().to_vec();
+    for (attribute, quote) in attributes {
+        let escaped_quote: &[u8] = if quote == b'"' { b"&quot;" } else { b"&apos;" };
+        content.push(b' ');
+        content.extend_from_slice(attribute.key);
+        content.push(b'=');
+        content.push(quote);
+        for &b in attribute.value.iter() {
+            if b == quote {
+                content.extend_from_slice(escaped_quote);
+            } else {
+                content.push(b);
             }
-            Event::End(ref e) => {
-                if let Some(i) = self.indent.as_mut() {
-                    i.shrink();
-                }
-                self.write_wrapped(b"</", e, b">")
+        }
+        content.push(quote);
+    }
+    Ok(content)
+}
+
+/// Returns the key sorting an attribute in the order of Canonical XML, as documented by
+/// `Writer::canonical_attributes`, with its prefix resolved in `namespaces`
+fn canonical_order(key: &[u8], namespaces: &Namespaces) -> (u8, Vec<u8>, Vec<u8>) {
+    let qname = QName(key);
+    let local_name = qname.local_name().to_vec();
+    if qname.is_xmlns() {
+        // the default namespace, without prefix, comes first
+        return (0, qname.prefix().map_or(Vec::new(), |_| local_name), Vec::new());
+    }
+    match qname.prefix() {
+        None => (1, Vec::new(), local_name),
+        Some(b"xml") => (1, XML_NAMESPACE.to_vec(), local_name),
+        Some(prefix) => match namespaces.resolve(prefix) {
+            Some(uri) => (1, uri.to_vec(), local_name),
+            None => (2, key.to_vec(), Vec::new()),
+        },
+    }
+}
+
+/// The namespace bound to the `xml` prefix
+const XML_NAMESPACE: &[u8] = b"http://www.w3.org/XML/1998/namespace";
+
+/// Namespace declarations in scope while writing canonical XML or sorting attributes
+#[derive(Clone, Default)]
+struct Namespaces {
+    /// prefixes (empty for the default namespace) and URIs of all declarations in scope
+    bindings: Vec<(Vec<u8>, Vec<u8>)>,
+    /// index in `bindings` of the first declaration of each opened element
+    starts: Vec<usize>,
+}
+
+impl Namespaces {
+    /// Opens the scope of an element declaring `(prefix, uri)` namespaces
+    fn open(&mut self, declarations: Vec<(Vec<u8>, Vec<u8>)>) {
+        self.starts.push(self.bindings.len());
+        self.bindings.extend(declarations);
+    }
+
+    /// Closes the scope of the last opened element, removing its declarations
+    fn close(&mut self) {
+        if let Some(start) = self.starts.pop() {
+            self.bindings.truncate(start);
+        }
+    }
+
+    /// Returns the URI bound to `prefix`, if any
+    fn resolve(&self, prefix: &[u8]) -> Option<&[u8]> {
+        self.bindings
+            .iter()
+            .rev()
+            .find(|(p, _)| p == prefix)
+            .map(|(_, uri)| &**uri)
+    }
+}
+
+/// Namespace declarations written by `Writer::write_start_ns`
+#[derive(Clone)]
+struct NamespaceScopes {
+    /// declarations in scope, with the index of the first one of each element declaring some
+    namespaces: Namespaces,
+    /// depth of each element in `namespaces.starts`
+    depths: Vec<usize>,
+    /// namespace to declare as default on the next element
+    default: Option<Vec<u8>>,
+    /// generates a prefix for a namespace, from its URI and the number of rejected prefixes
+    generate_prefix: fn(&str, usize) -> String,
+}
+
+impl Default for NamespaceScopes {
+    fn default() -> Self {
+        NamespaceScopes {
+            namespaces: Namespaces::default(),
+            depths: Vec::new(),
+            default: None,
+            generate_prefix: |_, n| format!("ns{}", n),
+        }
+    }
+}
+
+impl NamespaceScopes {
+    /// Opens the scope of an element at `depth`, with the pending default namespace, returning
+    /// the index of its first declaration
+    fn open_scope(&mut self, depth: usize) -> usize {
+        let start = self.namespaces.bindings.len();
+        self.namespaces.starts.push(start);
+        self.depths.push(depth);
+        if let Some(default) = self.default.take() {
+            self.namespaces.bindings.push((Vec::new(), default));
+        }
+        start
+    }
+
+    /// Closes the scopes of the elements deeper than `depth`
+    fn close_scopes(&mut self, depth: usize) {
+        while matches!(self.depths.last(), Some(&d) if d > depth) {
+            self.depths.pop();
+            if let Some(start) = self.namespaces.starts.pop() {
+                self.namespaces.bindings.truncate(start);
             }
-            Event::Empty(ref e) => self.write_wrapped(b"<", e, b"/>"),
-            Event::Text(ref e) => {
-                next_should_line_break = false;
-                self.write(&e.escaped())
+        }
+    }
+
+    /// Returns the qualified name of `local_name` in `namespace`, declaring it in the current
+    /// scope if needed
+    fn qualify(&mut self, namespace: &str, local_name: &str, is_element: bool) -> Result<Vec<u8>> {
+        let uri = namespace.as_bytes();
+        let default = self.namespaces.resolve(b"").unwrap_or(b"");
+        if uri.is_empty() {
+            if is_element && !default.is_empty() {
+                self.namespaces.bindings.push((Vec::new(), Vec::new()));
+            }
+            return Ok(local_name.as_bytes().to_vec());
+        }
+        if is_element && default == uri {
+            return Ok(local_name.as_bytes().to_vec());
+        }
+        let namespaces = &self.namespaces;
+        let prefix = match namespaces.bindings.iter().rev().find(|(prefix, bound)| {
+            !prefix.is_empty() && bound == uri && namespaces.resolve(prefix) == Some(uri)
+        }) {
+            Some((prefix, _)) => prefix.clone(),
+            None if uri == XML_NAMESPACE => b"xml".to_vec(),
+            None => {
+                // a generator returning a different prefix for each number finds a free one
+                // once the prefixes in scope, the empty one and the reserved ones are rejected
+                let attempts = self.namespaces.bindings.len() + 4;
+                let prefix = (0..attempts)
+                    .map(|n| (self.generate_prefix)(namespace, n).into_bytes())
+                    .find(|prefix| {
+                        !prefix.is_empty()
+                            && prefix != b"xml"
+                            && prefix != b"xmlns"
+                            && self.namespaces.resolve(prefix).is_none()
+                    })
+                    .ok_or_else(|| {
+                        Error::NotWellFormed(format!(
+                            "no free prefix generated for namespace {}",
+                            namespace
+                        ))
+                    })?;
+                self.namespaces.bindings.push((prefix.clone(), uri.to_vec()));
+                prefix
             }
-            Event::Comment(ref e) => self.write_wrapped(b"<!--", e, b"-->"),
-            Event::CData(ref e) => self.write_wrapped(b"<![CDATA[", e, b"]]>"),
-            Event::Decl(ref e) => self.write_wrapped(b"<?", e, b"?>"),
-            Event::PI(ref e) => self.write_wrapped(b"<?", e, b"?>"),
-            Event::DocType(ref e) => self.write_wrapped(b"<!DOCTYPE", e, b">"),
-            Event::Eof => Ok(()),
         };
-        if let Some(i) = self.indent.as_mut() {
-            i.should_line_break = next_should_line_break;
+        let mut qname = prefix;
+        qname.push(b':');
+        qname.extend_from_slice(local_name.as_bytes());
+        Ok(qname)
+    }
+}
+
+/// Replaces `\r\n` and `\r` line endings with `\n`
+fn normalize_newlines(raw: &[u8]) -> Cow<'_, [u8]> {
+    if memchr::memchr(b'\r', raw).is_none() {
+        return Cow::Borrowed(raw);
+    }
+    let mut normalized = Vec::with_capacity(raw.len());
+    let mut bytes = raw.iter().peekable();
+    while let Some(&b) = bytes.next() {
+        if b == b'\r' {
+            bytes.next_if_eq(&&b'\n');
+            normalized.push(b'\n');
+        } else {
+            normalized.push(b);
         }
-        result
     }
+    Cow::Owned(normalized)
+}
 
-    /// Writes bytes
-    #[inline]
-    pub fn write(&mut self, value: &[u8]) -> Result<()> {
-        self.writer.write_all(value).map_err(Error::Io)
+/// Escapes an unescaped text or attribute value as required by Canonical XML
+fn escape_canonical(raw: &[u8], in_attribute: bool) -> Vec<u8> {
+    let mut escaped = Vec::with_capacity(raw.len());
+    for &b in raw {
+        match b {
+            b'&' => escaped.extend_from_slice(b"&amp;"),
+            b'<' => escaped.extend_from_slice(b"&lt;"),
+            b'>' if !in_attribute => escaped.extend_from_slice(b"&gt;"),
+            b'"' if in_attribute => escaped.extend_from_slice(b"&quot;"),
+            b'\t' if in_attribute => escaped.extend_from_slice(b"&#x9;"),
+            b'\n' if in_attribute => escaped.extend_from_slice(b"&#xA;"),
+            b'\r' => escaped.extend_from_slice(b"&#xD;"),
+            b => escaped.push(b),
+        }
     }
+    escaped
+}
 
-    #[inline]
-    fn write_wrapped(&mut self, before: &[u8], value: &[u8], after: &[u8]) -> Result<()> {
-        if let Some(ref i) = self.indent {
-            if i.should_line_break {
-                self.writer.write_all(b"\n").map_err(Error::Io)?;
-                self.writer
-                    .write_all(&i.indents[..i.indents_len])
-                    .map_err(Error::Io)?;
+/// Transcodes the content of a UTF-8 `event` into `encoding`
+#[cfg(feature = "encoding")]
+fn encode_event(event: &Event, encoding: &'static Encoding) -> Result<Event<'static>> {
+    let strict = |content: &[u8]| encode_strict(content, encoding).map(BytesText::from_escaped);
+    Ok(match *event {
+        Event::Start(ref e) => Event::Start(encode_start(e, encoding)?),
+        Event::Empty(ref e) => Event::Empty(encode_start(e, encoding)?),
+        Event::End(ref e) => Event::End(BytesEnd::owned(encode_strict(e.name(), encoding)?)),
+        Event::Text(ref e) => {
+            let text = encode_with_references(e, encoding)?;
+            Event::Text(BytesText::from_escaped(text))
+        }
+        Event::Comment(ref e) => Event::Comment(strict(e)?),
+        Event::CData(ref e) => Event::CData(strict(e)?),
+        Event::Decl(ref e) => {
+            let start = BytesStart::owned(encode_strict(e, encoding)?, 3);
+            Event::Decl(BytesDecl::from_start(start))
+        }
+        Event::PI(ref e) => Event::PI(strict(e)?),
+        Event::DocType(ref e) => Event::DocType(strict(e)?),
+        Event::Eof => Event::Eof,
+    })
+}
+
+/// Transcodes a start tag, with character references in attribute values only
+#[cfg(feature = "encoding")]
+fn encode_start(e: &BytesStart, encoding: &'static Encoding) -> Result<BytesStart<'static>> {
+    let name_len = encode_strict(e.name(), encoding)?.len();
+    let mut attributes = e.attributes();
+    for attribute in attributes.with_checks(false).flatten() {
+        encode_strict(attribute.key, encoding)?;
+    }
+    let content = encode_with_references(e, encoding)?;
+    Ok(BytesStart::owned(content, name_len))
+}
+
+/// Transcodes UTF-8 `content`, writing unmappable characters as character references
+#[cfg(feature = "encoding")]
+fn encode_with_references(content: &[u8], encoding: &'static Encoding) -> Result<Vec<u8>> {
+    let (encoded, _, _) = encoding.encode(from_utf8(content)?);
+    Ok(encoded.into_owned())
+}
+
+/// Transcodes UTF-8 `content`, returning `Error::UnencodableCharacter` for unmappable characters
+#[cfg(feature = "encoding")]
+fn encode_strict(content: &[u8], encoding: &'static Encoding) -> Result<Vec<u8>> {
+    let mut content = from_utf8(content)?;
+    let mut encoder = encoding.new_encoder();
+    let mut encoded = Vec::with_capacity(content.len());
+    loop {
+        let (result, read) =
+            encoder.encode_from_utf8_to_vec_without_replacement(content, &mut encoded, true);
+        content = &content[read..];
+        match result {
+            EncoderResult::InputEmpty => return Ok(encoded),
+            EncoderResult::OutputFull => encoded.reserve(content.len().max(16)),
+            EncoderResult::Unmappable(c) => return Err(Error::UnencodableCharacter(c)),
+        }
+    }
+}
+
+/// Splits the bytes `incomplete` then `buf` into their longest valid UTF-8 prefix and an
+/// incomplete character at their end, rejecting invalid bytes
+fn split_incomplete<'b>(incomplete: &[u8], buf: &'b [u8]) -> io::Result<(Cow<'b, str>, Vec<u8>)> {
+    let bytes: Cow<[u8]> = if incomplete.is_empty() {
+        Cow::Borrowed(buf)
+    } else {
+        Cow::Owned([incomplete, buf].concat())
+    };
+    let valid_up_to = match from_utf8(&bytes) {
+        Ok(_) => bytes.len(),
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+    };
+    let rest = bytes[valid_up_to..].to_vec();
+    let valid = match bytes {
+        Cow::Borrowed(b) => Cow::Borrowed(from_utf8(&b[..valid_up_to]).unwrap_or_default()),
+        Cow::Owned(mut b) => {
+            b.truncate(valid_up_to);
+            Cow::Owned(String::from_utf8(b).unwrap_or_default())
+        }
+    };
+    Ok((valid, rest))
+}
+
+/// Returns the position of the first `]]>` in `content`
+fn find_cdata_end(content: &[u8]) -> Option<usize> {
+    content.windows(3).position(|w| w == b"]]>")
+}
+
+/// Returns the error naming the first duplicated attribute key of `e`
+fn duplicated_attribute(e: &BytesStart) -> Error {
+    let mut keys = Vec::new();
+    let mut attributes = e.attributes();
+    attributes.with_checks(false);
+    for attribute in attributes.flatten() {
+        if keys.contains(&attribute.key) {
+            return Error::NotWellFormed(format!(
+                "duplicate attribute `{}`",
+                String::from_utf8_lossy(attribute.key)
+            ));
+        }
+        keys.push(attribute.key);
+    }
+    Error::NotWellFormed("duplicate attribute".to_string())
+}
+
+/// Checks that comment `content` contains no `--` and does not end with `-`
+fn check_comment(content: &[u8]) -> Result<()> {
+    for i in memchr::memchr_iter(b'-', content) {
+        match content.get(i + 1) {
+            Some(b'-') => {
+                return Err(Error::NotWellFormed(format!(
+                    "comment contains `--` at position {}",
+                    i
+                )))
             }
+            None => {
+                return Err(Error::NotWellFormed(format!(
+                    "comment ends with `-` at position {}",
+                    i
+                )))
+            }
+            _ => (),
         }
-        self.write(before)?;
-        self.write(value)?;
-        self.write(after)?;
-        Ok(())
     }
+    Ok(())
+}
 
-    /// Manually write a newline and indentation at the proper level.
-    ///
-    /// This can be used when the heuristic to line break and indent after any [Event] apart
-    /// from [Text] fails such as when a [Start] occurs directly after [Text].
-    /// This method will do nothing if `Writer` was not constructed with `new_with_indent`.
-    ///
-    /// [Event]: events/enum.Event.html
-    /// [Text]: events/enum.Event.html#variant.Text
-    /// [Start]: events/enum.Event.html#variant.Start
-    pub fn write_indent(&mut self) -> Result<()> {
-        if let Some(ref i) = self.indent {
-            self.writer.write_all(b"\n").map_err(Error::Io)?;
-            self.writer
-                .write_all(&i.indents[..i.indents_len])
-                .map_err(Error::Io)?;
+/// Checks that escaped `text` contains no `<` and that every `&` starts a reference
+fn check_escaped(text: &[u8]) -> Result<()> {
+    if let Some(i) = memchr::memchr(b'<', text) {
+        return Err(Error::NotWellFormed(format!(
+            "unescaped `<` at position {}",
+            i
+        )));
+    }
+    for i in memchr::memchr_iter(b'&', text) {
+        let reference = text[i + 1..]
+            .iter()
+            .position(|&b| b == b';')
+            .map(|end| &text[i + 1..i + 1 + end]);
+        let valid = match reference {
+            Some(r) if r.starts_with(b"#x") => {
+                r.len() > 2 && r[2..].iter().all(|b| b.is_ascii_hexdigit())
+            }
+            Some(r) if r.starts_with(b"#") => {
+                r.len() > 1 && r[1..].iter().all(|b| b.is_ascii_digit())
+            }
+            Some(r) => check_name(r).is_ok(),
+            None => false,
+        };
+        if !valid {
+            return Err(Error::NotWellFormed(format!(
+                "`&` at position {} does not start a reference",
+                i
+            )));
         }
-        Ok(())
     }
+    Ok(())
+}
+
+/// Where the `>` or `/>` of a tag wrapped by [`Writer::wrap_attributes`] is written.
+///
+/// [`Writer::wrap_attributes`]: struct.Writer.html#method.wrap_attributes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WrappedTagEnd {
+    /// Right after the last attribute: `b="2"/>`
+    AfterLastAttribute,
+    /// On its own line, at the indentation of the element
+    OwnLine,
 }
 
 #[derive(Clone)]
 struct Indentation {
     should_line_break: bool,
+    /// depth of the outermost element with text content, inside of which nothing is indented
+    mixed_depth: Option<usize>,
+    /// length above which tags are written with one attribute per line
+    wrap_attributes: Option<usize>,
+    wrapped_tag_end: WrappedTagEnd,
+    newline: Vec<u8>,
     indent_char: u8,
     indent_size: usize,
     indents: Vec<u8>,
@@ -180,6 +2478,10 @@ impl Indentation {
     fn new(indent_char: u8, indent_size: usize) -> Indentation {
         Indentation {
             should_line_break: false,
+            mixed_depth: None,
+            wrap_attributes: None,
+            wrapped_tag_end: WrappedTagEnd::AfterLastAttribute,
+            newline: b"\n".to_vec(),
             indent_char,
             indent_size,
             indents: vec![indent_char; 128],
@@ -300,8 +2602,7 @@ mod indentation {
         writer.write_event(Event::Empty(inner)).expect("write inner tag failed");
         writer.write_event(Event::End(end)).expect("write end tag failed");
 
-        assert_eq!(buffer, br#"<paired attr1="value1" attr2="value2">text<inner/>
-</paired>"#.as_ref());
+        assert_eq!(buffer, br#"<paired attr1="value1" attr2="value2">text<inner/></paired>"#.as_ref());
     }
 
     #[test]
@@ -329,4 +2630,221 @@ mod indentation {
     </paired>
 </paired>"#.as_ref());
     }
+
+    #[test]
+    fn crlf() {
+        let mut buffer = Vec::new();
+        let mut writer = Writer::new_with_indent(&mut buffer, b' ', 2);
+        writer.newline(b"\r\n");
+
+        let start = BytesStart::borrowed_name(b"paired");
+        let end = BytesEnd::borrowed(b"paired");
+        let inner = BytesStart::borrowed_name(b"inner");
+
+        writer.write_event(Event::Start(start)).expect("write start tag failed");
+        writer.write_event(Event::Empty(inner)).expect("write inner tag failed");
+        writer.write_event(Event::End(end)).expect("write end tag failed");
+
+        assert_eq!(buffer, b"<paired>\r\n  <inner/>\r\n</paired>".as_ref());
+    }
+
+    #[test]
+    fn manual_indent() {
+        let mut buffer = Vec::new();
+        let mut writer = Writer::new_with_indent(&mut buffer, b' ', 2);
+        writer.newline(b"\r\n");
+
+        let start = BytesStart::borrowed_name(b"paired");
+        let end = BytesEnd::borrowed(b"paired");
+        let text = BytesText::from_plain(b"text");
+        let inner = BytesStart::borrowed_name(b"inner");
+
+        writer.write_event(Event::Start(start)).expect("write start tag failed");
+        writer.write_event(Event::Text(text)).expect("write text failed");
+        writer.write_indent().expect("write indent failed");
+        writer.write_event(Event::Empty(inner.clone())).expect("write inner tag failed");
+        writer.write_indent().expect("write indent failed");
+        writer.write_event(Event::Empty(inner)).expect("write inner tag failed");
+        writer.write_event(Event::End(end)).expect("write end tag failed");
+
+        assert_eq!(
+            buffer,
+            b"<paired>text\r\n  <inner/>\r\n  <inner/></paired>".as_ref()
+        );
+    }
+
+    #[test]
+    fn element_writer_empty() {
+        let mut buffer = Vec::new();
+        let mut writer = Writer::new_with_indent(&mut buffer, b' ', 4);
+
+        writer
+            .create_element(b"empty")
+            .with_attribute(("attr1", "value1"))
+            .with_attribute(("attr2", "value2"))
+            .write_empty()
+            .expect("failure");
+
+        assert_eq!(buffer, br#"<empty attr1="value1" attr2="value2"/>"#.as_ref());
+    }
+
+    #[test]
+    fn element_writer_attributes() {
+        use std::collections::BTreeMap;
+
+        let mut attributes = BTreeMap::new();
+        attributes.insert("b", "1 < 2");
+        attributes.insert("a", "\"quoted\" & more");
+        attributes.insert("c", "");
+        let mut buffer = Vec::new();
+        let mut writer = Writer::new(&mut buffer);
+
+        writer
+            .create_element("attributes")
+            .with_attributes(attributes)
+            .write_empty()
+            .expect("failure");
+
+        assert_eq!(
+            buffer,
+            br#"<attributes a="&quot;quoted&quot; &amp; more" b="1 &lt; 2" c=""/>"#.as_ref()
+        );
+    }
+
+    #[test]
+    fn element_writer_text() {
+        let mut buffer = Vec::new();
+        let mut writer = Writer::new_with_indent(&mut buffer, b' ', 4);
+
+        writer
+            .create_element("paired")
+            .with_attribute(("attr1", "value1"))
+            .with_attribute(("attr2", "value2"))
+            .write_text_content("text & more")
+            .expect("failure");
+
+        assert_eq!(
+            buffer,
+            br#"<paired attr1="value1" attr2="value2">text &amp; more</paired>"#.as_ref()
+        );
+    }
+
+    #[test]
+    fn element_writer_nested() {
+        let mut buffer = Vec::new();
+        let mut writer = Writer::new_with_indent(&mut buffer, b' ', 4);
+
+        writer
+            .create_element("outer")
+            .with_attributes(vec![("attr1", "value1"), ("attr2", "value2")])
+            .write_inner_content(|writer| {
+                let fruits = ["apple", "orange", "banana"];
+                for (quant, item) in fruits.iter().enumerate() {
+                    writer
+                        .create_element("fruit")
+                        .with_attribute(("quantity", quant.to_string().as_str()))
+                        .write_text_content(item)?;
+                }
+                writer
+                    .create_element("inner")
+                    .write_inner_content(|writer| {
+                        writer.create_element("empty").write_empty().map(|_| ())
+                    })?
+                    .create_element("data")
+                    .write_cdata_content(BytesText::from_escaped_str("<raw>"))?;
+                Ok(())
+            })
+            .expect("failure");
+
+        assert_eq!(
+            std::str::from_utf8(&buffer).unwrap(),
+            r#"<outer attr1="value1" attr2="value2">
+    <fruit quantity="0">apple</fruit>
+    <fruit quantity="1">orange</fruit>
+    <fruit quantity="2">banana</fruit>
+    <inner>
+        <empty/>
+    </inner>
+    <data><![CDATA[<raw>]]></data>
+</outer>"#
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serialize")]
+    fn element_writer_serializable() {
+        #[derive(Serialize)]
+        struct Fruit {
+            name: &'static str,
+            color: &'static str,
+        }
+
+        let mut buffer = Vec::new();
+        let mut writer = Writer::new_with_indent(&mut buffer, b' ', 4);
+
+        let fruit = Fruit {
+            name: "apple",
+            color: "red",
+        };
+        writer
+            .create_element("Envelope")
+            .write_inner_content(|writer| {
+                writer.create_element("Header").write_empty()?;
+                writer
+                    .create_element("Body")
+                    .write_serializable("fruit", &fruit)
+                    .map_err(|e| match e {
+                        DeError::Xml(e) => e,
+                        e => panic!("{}", e),
+                    })?
+                    .create_element("Footer")
+                    .write_empty()?;
+                Ok(())
+            })
+            .expect("failure");
+
+        assert_eq!(
+            std::str::from_utf8(&buffer).unwrap(),
+            r#"<Envelope>
+    <Header/>
+    <Body>
+        <fruit name="apple" color="red"/>
+    </Body>
+    <Footer/>
+</Envelope>"#
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serialize")]
+    fn element_writer_serializable_collapsed() {
+        #[derive(Serialize)]
+        struct Item {
+            name: &'static str,
+        }
+
+        let mut writer = Writer::new(Vec::new());
+        writer.collapse_empty_elements(true).check_end_names(true);
+        writer
+            .write_event(Event::Start(BytesStart::borrowed_name(b"Envelope")))
+            .unwrap();
+        writer
+            .write_event(Event::Start(BytesStart::borrowed_name(b"Body")))
+            .unwrap();
+        writer
+            .write_serializable("item", &Item { name: "a" })
+            .unwrap();
+        writer
+            .write_event(Event::End(BytesEnd::borrowed(b"Body")))
+            .unwrap();
+        writer
+            .write_event(Event::Start(BytesStart::borrowed_name(b"Empty")))
+            .unwrap();
+        writer.write_end().unwrap();
+        writer.write_end().unwrap();
+        assert_eq!(
+            writer.into_string().unwrap(),
+            r#"<Envelope><Body><item name="a"/></Body><Empty/></Envelope>"#
+        );
+    }
 }
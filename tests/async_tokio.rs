@@ -0,0 +1,145 @@
+#![cfg(feature = "async-tokio")]
+
+extern crate quick_xml;
+extern crate tokio;
+
+use std::future::{self, Future};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::{Error, Reader, Writer};
+use tokio::io::{AsyncRead, AsyncWrite, DuplexStream, ReadBuf};
+use tokio::runtime::Builder;
+
+/// Reads `stream` until it is closed
+fn read_to_string(mut stream: DuplexStream) -> impl Future<Output = String> {
+    let mut xml = Vec::new();
+    future::poll_fn(move |cx| loop {
+        let mut chunk = [0; 8];
+        let mut buf = ReadBuf::new(&mut chunk);
+        match Pin::new(&mut stream).poll_read(cx, &mut buf) {
+            Poll::Ready(Ok(())) if buf.filled().is_empty() => {
+                return Poll::Ready(String::from_utf8(xml.clone()).unwrap())
+            }
+            Poll::Ready(Ok(())) => xml.extend_from_slice(buf.filled()),
+            Poll::Ready(Err(e)) => panic!("{}", e),
+            Poll::Pending => return Poll::Pending,
+        }
+    })
+}
+
+#[test]
+fn write_event_async() {
+    let runtime = Builder::new_current_thread().build().unwrap();
+    // a small buffer to await the writes several times
+    let (client, server) = tokio::io::duplex(8);
+    let reading = runtime.spawn(read_to_string(server));
+
+    let mut writer = Writer::new_with_indent(client, b' ', 2);
+    writer.check_end_names(true);
+    let mut root = BytesStart::borrowed_name(b"root");
+    root.push_attribute(("attr", "a\"b"));
+    let events = vec![
+        Event::Start(root),
+        Event::Empty(BytesStart::borrowed_name(b"empty")),
+        Event::Start(BytesStart::borrowed_name(b"text")),
+        Event::Text(BytesText::from_plain_str("a < b")),
+        Event::End(BytesEnd::borrowed(b"text")),
+    ];
+    let mut written = 0;
+    for event in events {
+        written += runtime.block_on(writer.write_event_async(event)).unwrap();
+    }
+    match runtime.block_on(writer.write_event_async(Event::End(BytesEnd::borrowed(b"a")))) {
+        Err(Error::EndEventMismatch { .. }) => (),
+        e => panic!("expecting EndEventMismatch error, found {:?}", e),
+    }
+    runtime
+        .block_on(writer.write_event_async(Event::End(BytesEnd::borrowed(b"root"))))
+        .unwrap();
+    assert_eq!(written, 57);
+    assert_eq!(writer.bytes_written(), 65);
+    drop(writer);
+
+    let xml = runtime.block_on(reading).unwrap();
+    assert_eq!(xml.len(), 65);
+    assert_eq!(
+        xml,
+        "<root attr=\"a&quot;b\">\n  <empty/>\n  <text>a &lt; b</text>\n</root>"
+    );
+
+    let mut reader = Reader::from_str(&xml);
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    let mut texts = Vec::new();
+    loop {
+        match reader.read_event(&mut buf).unwrap() {
+            Event::Text(e) => texts.push(e.unescape_and_decode(&reader).unwrap()),
+            Event::Eof => break,
+            _ => (),
+        }
+        buf.clear();
+    }
+    assert_eq!(texts, vec!["a < b"]);
+}
+
+/// Writes at most 4 bytes at once, and fails once when `fail_at` bytes are written
+struct Flaky {
+    written: Vec<u8>,
+    fail_at: Option<usize>,
+}
+
+impl AsyncWrite for Flaky {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if self.fail_at == Some(self.written.len()) {
+            self.fail_at = None;
+            return Poll::Ready(Err(io::ErrorKind::Interrupted.into()));
+        }
+        let len = buf.len().min(4);
+        self.written.extend_from_slice(&buf[..len]);
+        Poll::Ready(Ok(len))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[test]
+fn write_event_async_after_error() {
+    let runtime = Builder::new_current_thread().build().unwrap();
+    let mut writer = Writer::from_async_writer(Flaky {
+        written: Vec::new(),
+        fail_at: Some(4),
+    });
+    writer.collapse_empty_elements(true);
+    let start = Event::Start(BytesStart::borrowed_name(b"root"));
+    assert_eq!(runtime.block_on(writer.write_event_async(&start)).unwrap(), 0);
+    // the deferred start tag is written with the text, until the failure
+    let text = Event::Text(BytesText::from_plain_str("text"));
+    match runtime.block_on(writer.write_event_async(&text)) {
+        Err(Error::Io(ref e)) if e.kind() == io::ErrorKind::Interrupted => (),
+        e => panic!("expecting Io error, found {:?}", e),
+    }
+    assert_eq!(writer.get_ref().written, b"<roo");
+    // the rest is written before the next event
+    let end = Event::End(BytesEnd::borrowed(b"root"));
+    assert_eq!(runtime.block_on(writer.write_event_async(&end)).unwrap(), 7);
+    assert_eq!(writer.get_ref().written, b"<root>text</root>");
+
+    // a deferred start tag is written by flush_async
+    runtime.block_on(writer.write_event_async(&start)).unwrap();
+    runtime.block_on(writer.flush_async()).unwrap();
+    assert_eq!(writer.get_ref().written, b"<root>text</root><root>");
+    assert_eq!(writer.bytes_written(), 23);
+}
@@ -163,3 +163,34 @@ fn escapes_in_cdata() {
         r#"{37.3k,268,msb}<-1,1|1,-1>(T=1,(7,-6,3,D:4,1:1,T:1,1:2,0:8,F:8,15:4,C:4,-79m,T=0)+){C =(D:4+4*T+9+F:4+F:4:4+15)&15} [D:0..15,F:0..255]"#
     );
 }
+
+#[test]
+fn optional_fields() {
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Optional {
+        note: Option<String>,
+        item: Option<Item>,
+    }
+
+    let none = Optional {
+        note: None,
+        item: None,
+    };
+    let serialized = to_string(&none).unwrap();
+    assert_eq!(serialized, "<Optional/>");
+    assert_eq!(from_str::<Optional>(&serialized).unwrap(), none);
+
+    let some = Optional {
+        note: Some("text".to_string()),
+        item: Some(Item {
+            name: "Banana".to_string(),
+            source: "Store".to_string(),
+        }),
+    };
+    let serialized = to_string(&some).unwrap();
+    assert_eq!(
+        serialized,
+        r#"<Optional note="text"><item name="Banana" source="Store"/></Optional>"#
+    );
+    assert_eq!(from_str::<Optional>(&serialized).unwrap(), some);
+}
@@ -1,11 +1,15 @@
 extern crate quick_xml;
+#[cfg(feature = "encoding")]
+extern crate encoding_rs;
 
-use std::io::Cursor;
+use std::io::{self, Cursor, Read, Write};
 use std::str::from_utf8;
 
+use quick_xml::events::attributes::QuoteStyle;
 use quick_xml::events::Event::*;
 use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
-use quick_xml::{Reader, Result, Writer};
+use quick_xml::name::QName;
+use quick_xml::{copy_all, Error, Parser, Reader, Result, WrappedTagEnd, Writer, WriterConfig};
 
 macro_rules! next_eq_name {
     ($r:expr, $t:tt, $bytes:expr) => {
@@ -101,6 +105,29 @@ fn test_empty_attr() {
     next_eq!(r, Empty, b"a");
 }
 
+#[test]
+fn test_attr_value_with_gt() {
+    let mut r = Reader::from_str(r#"<a b="x>y"><c d='>'/></a>"#);
+    r.trim_text(true).expand_empty_elements(false);
+    let mut buf = Vec::new();
+    match r.read_event(&mut buf) {
+        Ok(Start(ref e)) => {
+            let attr = e.attributes().next().unwrap().unwrap();
+            assert_eq!(attr.key, b"b");
+            assert_eq!(&*attr.value, b"x>y");
+        }
+        e => panic!("expecting Start, found {:?}", e),
+    }
+    match r.read_event(&mut buf) {
+        Ok(Empty(ref e)) => {
+            let attr = e.attributes().next().unwrap().unwrap();
+            assert_eq!(&*attr.value, b">");
+        }
+        e => panic!("expecting Empty, found {:?}", e),
+    }
+    next_eq!(r, End, b"a");
+}
+
 #[test]
 fn test_start_end_comment() {
     let mut r = Reader::from_str("<b><a b=\"test\" c=\"test\"/> <a  /><!--t--></b>");
@@ -180,70 +207,1889 @@ fn test_cdata() {
 }
 
 #[test]
-fn test_cdata_open_close() {
-    let mut r = Reader::from_str("<![CDATA[test <> test]]>");
-    r.trim_text(true);
-    next_eq!(r, CData, b"test &lt;&gt; test");
+fn test_cdata_open_close() {
+    let mut r = Reader::from_str("<![CDATA[test <> test]]>");
+    r.trim_text(true);
+    next_eq!(r, CData, b"test &lt;&gt; test");
+}
+
+#[test]
+fn test_start_attr() {
+    let mut r = Reader::from_str("<a b=\"c\">");
+    r.trim_text(true);
+    next_eq!(r, Start, b"a");
+}
+
+#[test]
+fn test_nested() {
+    let mut r = Reader::from_str("<a><b>test</b><c/></a>");
+    r.trim_text(true).expand_empty_elements(false);
+    next_eq!(r, Start, b"a", Start, b"b", Text, b"test", End, b"b", Empty, b"c", End, b"a");
+}
+
+#[test]
+fn test_ignore_comments() {
+    let mut r = Reader::from_str("<a><!--x--><b/><!--y-->text<!--z--></a>");
+    r.trim_text(true).ignore_comments(true);
+    next_eq!(r, Start, b"a", Empty, b"b", Text, b"text", End, b"a");
+    assert_eq!(r.buffer_position(), 39);
+}
+
+#[test]
+fn test_ignore_comments_read_text() {
+    let mut r = Reader::from_str("<a><!--x-->text<!--y--></a>");
+    r.trim_text(true).ignore_comments(true);
+    next_eq!(r, Start, b"a");
+    assert_eq!(r.read_text(b"a", &mut Vec::new()).unwrap(), "text");
+}
+
+#[test]
+fn test_ignore_comments_many() {
+    let xml = format!("<a>{}</a>", "<!--x--> ".repeat(100_000));
+    let mut r = Reader::from_str(&xml);
+    r.trim_text(true).ignore_comments(true);
+    next_eq!(r, Start, b"a", End, b"a");
+}
+
+#[test]
+fn test_ignore_pi() {
+    let mut r = Reader::from_str("<?xml version='1.0'?><?pi data?><!DOCTYPE a><a><?pi?></a>");
+    r.trim_text(true).ignore_pi(true);
+    let mut buf = Vec::new();
+    match r.read_event(&mut buf) {
+        Ok(DocType(_)) => (),
+        e => panic!("expecting DocType, found {:?}", e),
+    }
+    next_eq!(r, Start, b"a", End, b"a");
+}
+
+#[test]
+fn test_ignore_doctype() {
+    let mut r = Reader::from_str("<!DOCTYPE a [<!ENTITY b 'c'>]><!--x--><a/>");
+    r.trim_text(true).ignore_doctype(true);
+    next_eq!(r, Comment, b"x", Empty, b"a");
+}
+
+#[test]
+fn test_max_depth() {
+    let depth = 100_000;
+    let xml = format!("{}{}", "<a>".repeat(depth), "</a>".repeat(depth));
+
+    let mut r = Reader::from_str(&xml);
+    let mut buf = Vec::new();
+    let mut count = 0;
+    loop {
+        match r.read_event(&mut buf) {
+            Ok(Start(_)) => count += 1,
+            Ok(Eof) => break,
+            Ok(_) => (),
+            Err(e) => panic!("unexpected error at {}: {:?}", r.buffer_position(), e),
+        }
+        buf.clear();
+    }
+    assert_eq!(count, depth);
+
+    let mut r = Reader::from_str(&xml);
+    r.trim_text(true).max_depth(1000);
+    let mut count = 0;
+    loop {
+        match r.read_event(&mut buf) {
+            Ok(Start(_)) => count += 1,
+            Err(Error::MaxDepthExceeded(1000)) => break,
+            e => panic!("expecting MaxDepthExceeded error, found {:?}", e),
+        }
+        buf.clear();
+    }
+    assert_eq!(count, 1000);
+    assert_eq!(r.buffer_position(), 3000);
+}
+
+#[test]
+fn test_max_depth_expanded_empty() {
+    let mut r = Reader::from_str("<a><b/></a>");
+    r.trim_text(true).expand_empty_elements(true).max_depth(1);
+    next_eq!(r, Start, b"a");
+    match r.read_event(&mut Vec::new()) {
+        Err(Error::MaxDepthExceeded(1)) => (),
+        e => panic!("expecting MaxDepthExceeded error, found {:?}", e),
+    }
+
+    let mut r = Reader::from_str("<a><b/></a>");
+    r.trim_text(true).max_depth(1);
+    next_eq!(r, Start, b"a", Empty, b"b", End, b"a");
+}
+
+#[test]
+fn test_fragments() {
+    let mut r = Reader::from_str("<a><b/></a>\n<a>text</a>\n<c/>");
+    r.trim_text(true).check_end_names(true).max_depth(2);
+    next_eq!(
+        r, Start, b"a", Empty, b"b", End, b"a", Start, b"a", Text, b"text", End, b"a", Empty,
+        b"c"
+    );
+    match r.read_event(&mut Vec::new()) {
+        Ok(Eof) => (),
+        e => panic!("expecting Eof, found {:?}", e),
+    }
+}
+
+#[test]
+fn test_read_event_with_span() {
+    let xml = r#"<?xml version="1.0"?>
+<!DOCTYPE a><a b=">"><!--c-->text<![CDATA[<d>]]><?e f?><g/></a>"#;
+    let mut reader = Reader::from_str(xml);
+    let mut buf = Vec::new();
+    let mut end = 0;
+    loop {
+        let (event, span) = reader.read_event_with_span(&mut buf).unwrap();
+        // spans of an untrimmed document cover all of it
+        assert_eq!(span.start, end);
+        end = span.end;
+        assert_eq!(end, reader.buffer_position());
+        match event {
+            Eof => break,
+            CData(_) => assert_eq!(&xml[span], "<![CDATA[<d>]]>"),
+            PI(_) => assert_eq!(&xml[span], "<?e f?>"),
+            Start(_) => assert_eq!(&xml[span], r#"<a b=">">"#),
+            _ => (),
+        }
+        buf.clear();
+    }
+    assert_eq!(end, xml.len());
+
+    let mut reader = Reader::from_str("<a>\n  <b/>\n</a>");
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    reader.peek_event(&mut buf).unwrap();
+    assert_eq!(reader.read_event_with_span(&mut buf).unwrap().1, 0..3);
+    assert_eq!(reader.read_event_with_span(&mut buf).unwrap().1, 6..10);
+    assert_eq!(reader.read_event_with_span(&mut buf).unwrap().1, 11..15);
+}
+
+#[test]
+fn test_on_raw() {
+    let xml = r#"<?xml version="1.0"?>
+<!DOCTYPE a [<!ENTITY e "> ">]><a b=">"><!-- > --> text <![CDATA[<d>]]><?e f?><g/>
+  <h>&amp;</h>
+  <i>j</i><k xmlns="urn:k"><l/> </k></a>
+"#;
+    for &(trim, expand, ignore) in &[(false, false, false), (true, true, false), (true, false, true)] {
+        let mut reader = Reader::from_str(xml);
+        reader
+            .trim_text(trim)
+            .expand_empty_elements(expand)
+            .ignore_comments(ignore)
+            .ignore_pi(ignore)
+            .ignore_doctype(ignore);
+        let mut sources = Vec::new();
+        let mut events = Vec::new();
+        {
+            let mut observed = reader.on_raw(|raw| sources.extend_from_slice(raw));
+            let mut buf = Vec::new();
+            loop {
+                let (event, span) = observed.read_event_with_span(&mut buf).unwrap();
+                if let Eof = event {
+                    break;
+                }
+                events.push((format!("{:?}", event), span));
+                buf.clear();
+            }
+        }
+        assert_eq!(from_utf8(&sources).unwrap(), xml);
+
+        // the events are the same as without `on_raw`
+        let mut reader = Reader::from_str(xml);
+        reader
+            .trim_text(trim)
+            .expand_empty_elements(expand)
+            .ignore_comments(ignore)
+            .ignore_pi(ignore)
+            .ignore_doctype(ignore);
+        let mut buf = Vec::new();
+        for (event, span) in events {
+            let (e, s) = reader.read_event_with_span(&mut buf).unwrap();
+            assert_eq!((format!("{:?}", e), s), (event, span));
+            buf.clear();
+        }
+    }
+
+    // the other reading methods pass the input data too
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+    let mut sources = Vec::new();
+    {
+        let mut observed = reader.on_raw(|raw| sources.extend_from_slice(raw));
+        let mut buf = Vec::new();
+        let mut ns_buf = Vec::new();
+        loop {
+            match observed.read_namespaced_event(&mut buf, &mut ns_buf).unwrap() {
+                (_, Start(ref e)) if e.name() == b"h" => {
+                    observed.read_to_end(b"h", &mut Vec::new()).unwrap();
+                }
+                (_, Start(ref e)) if e.name() == b"i" => {
+                    assert_eq!(observed.read_text(b"i", &mut Vec::new()).unwrap(), "j");
+                }
+                (ns, Empty(ref e)) if e.name() == b"l" => assert_eq!(ns, Some(&b"urn:k"[..])),
+                (_, Eof) => break,
+                _ => (),
+            }
+            buf.clear();
+        }
+    }
+    assert_eq!(from_utf8(&sources).unwrap(), xml);
+}
+
+#[test]
+fn test_html_attributes() {
+    let mut r = Reader::from_str(
+        r#"<form><input type=checkbox name="agree" checked disabled>
+<img src=/img/logo.png alt='Company logo' width=100 height=50/></form>"#,
+    );
+    // `input` is a void element, without end tag
+    r.trim_text(true).check_end_names(false);
+    let mut buf = Vec::new();
+    let mut tags = Vec::new();
+    loop {
+        match r.read_event(&mut buf).unwrap() {
+            Eof => break,
+            Start(ref e) | Empty(ref e) => {
+                let attributes = e
+                    .html_attributes()
+                    .map(|a| {
+                        let a = a.unwrap();
+                        (
+                            a.key_str().unwrap().to_string(),
+                            a.value_str().unwrap().to_string(),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                tags.push((from_utf8(e.name()).unwrap().to_string(), attributes));
+            }
+            _ => (),
+        }
+        buf.clear();
+    }
+    let owned = |attributes: &[(&str, &str)]| {
+        attributes
+            .iter()
+            .map(|&(k, v)| (k.to_string(), v.to_string()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        tags,
+        vec![
+            ("form".to_string(), vec![]),
+            (
+                "input".to_string(),
+                owned(&[
+                    ("type", "checkbox"),
+                    ("name", "agree"),
+                    ("checked", ""),
+                    ("disabled", "")
+                ])
+            ),
+            (
+                "img".to_string(),
+                owned(&[
+                    ("src", "/img/logo.png"),
+                    ("alt", "Company logo"),
+                    ("width", "100"),
+                    ("height", "50")
+                ])
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_read_event_unbuffered() {
+    let mut r = Reader::from_str("<a x='1'>text</a>");
+    r.trim_text(true);
+    let start = match r.read_event_unbuffered() {
+        Ok(Start(e)) => e,
+        e => panic!("expecting Start, found {:?}", e),
+    };
+    match r.read_event_unbuffered() {
+        Ok(Text(ref e)) => assert_eq!(&**e, b"text"),
+        e => panic!("expecting Text, found {:?}", e),
+    }
+    // the events outlive the following reads
+    assert_eq!(start.name(), b"a");
+    assert_eq!(start.get_attribute("x").unwrap().value, &b"1"[..]);
+    match r.read_event_unbuffered() {
+        Ok(End(ref e)) => assert_eq!(e.name(), b"a"),
+        e => panic!("expecting End, found {:?}", e),
+    }
+    match r.read_event_unbuffered() {
+        Ok(Eof) => (),
+        e => panic!("expecting Eof, found {:?}", e),
+    }
+}
+
+#[test]
+fn test_check_document_structure() {
+    fn read_all(xml: &str) -> Result<usize> {
+        let mut r = Reader::from_str(xml);
+        r.trim_text(true).check_document_structure(true);
+        let mut buf = Vec::new();
+        let mut count = 0;
+        loop {
+            match r.read_event(&mut buf)? {
+                Eof => return Ok(count),
+                _ => count += 1,
+            }
+        }
+    }
+    let xml = "<?xml version='1.0'?>\n<!DOCTYPE a>\n<!-- c -->\n<a>text<![CDATA[x]]></a>\n<?pi?>\n";
+    assert_eq!(read_all(xml).unwrap(), 8);
+    assert_eq!(read_all("<a/>").unwrap(), 1);
+
+    match read_all("<a/> text") {
+        Err(Error::TextOutsideRoot(5)) => (),
+        e => panic!("expecting TextOutsideRoot(5), found {:?}", e),
+    }
+    match read_all("text<a/>") {
+        Err(Error::TextOutsideRoot(0)) => (),
+        e => panic!("expecting TextOutsideRoot(0), found {:?}", e),
+    }
+    match read_all("<![CDATA[x]]><a/>") {
+        Err(Error::TextOutsideRoot(0)) => (),
+        e => panic!("expecting TextOutsideRoot(0), found {:?}", e),
+    }
+    match read_all("<a></a><b/>") {
+        Err(Error::MultipleRoots(7)) => (),
+        e => panic!("expecting MultipleRoots(7), found {:?}", e),
+    }
+    match read_all("<!-- c -->  ") {
+        Err(Error::MissingRoot) => (),
+        e => panic!("expecting MissingRoot, found {:?}", e),
+    }
+
+    // fragments are accepted by default
+    let mut r = Reader::from_str("text<a/><b/>");
+    r.trim_text(true);
+    next_eq!(r, Text, b"text", Empty, b"a", Empty, b"b");
+}
+
+#[test]
+fn test_read_to_end_with_span() {
+    let xml = "<a><a><b>x</b></a><c/></a><a></a><a/><d>y</d>";
+    let mut r = Reader::from_str(xml);
+    r.trim_text(true).expand_empty_elements(true);
+    let mut buf = Vec::new();
+    next_eq!(r, Start, b"a");
+    let span = r.read_to_end_with_span(b"a", &mut buf).unwrap();
+    assert_eq!(&xml[span], "<a><b>x</b></a><c/>");
+    next_eq!(r, Start, b"a");
+    let span = r.read_to_end_with_span(b"a", &mut buf).unwrap();
+    assert_eq!(span, 29..29);
+    // the end of an expanded empty element is at the end of the tag
+    next_eq!(r, Start, b"a");
+    let span = r.read_to_end_with_span(b"a", &mut buf).unwrap();
+    assert_eq!(span, 37..37);
+    // a peeked event is part of the content
+    next_eq!(r, Start, b"d");
+    match r.peek_event(&mut buf) {
+        Ok(&Text(_)) => (),
+        e => panic!("expecting Text, found {:?}", e),
+    }
+    let span = r.read_to_end_with_span(b"d", &mut buf).unwrap();
+    assert_eq!(&xml[span], "y");
+    match r.read_to_end_with_span(b"d", &mut buf) {
+        Err(Error::UnexpectedEof(_)) => (),
+        e => panic!("expecting UnexpectedEof, found {:?}", e),
+    }
+}
+
+#[test]
+fn test_unknown_bang() {
+    for &(xml, content) in &[("<a><!foo bar></a>", "foo bar"), ("<a><!-x></a>", "-x")] {
+        let mut r = Reader::from_str(xml);
+        r.trim_text(true);
+        next_eq!(r, Start, b"a");
+        match r.read_event(&mut Vec::new()) {
+            Err(Error::UnknownBang(ref e)) if e == content => (),
+            e => panic!("expecting UnknownBang({:?}), found {:?}", content, e),
+        }
+        assert_eq!(r.buffer_position(), 3);
+    }
+
+    let xml = "<a><!foo bar><!x>text</a>";
+    let mut r = Reader::from_str(xml);
+    r.trim_text(true).unknown_bang_as_text(true);
+    next_eq!(
+        r,
+        Start,
+        b"a",
+        Text,
+        b"&lt;!foo bar&gt;",
+        Text,
+        b"&lt;!x&gt;",
+        Text,
+        b"text"
+    );
+    next_eq!(r, End, b"a");
+
+    let mut r = Reader::from_str(xml);
+    r.unknown_bang_as_text(true);
+    let mut writer = Writer::new(Vec::new());
+    copy_all(&mut r, &mut writer, |e| Some(e)).unwrap();
+    assert_eq!(
+        writer.into_string().unwrap(),
+        "<a>&lt;!foo bar&gt;&lt;!x&gt;text</a>"
+    );
+}
+
+#[test]
+fn test_whitespace_predicate() {
+    let mut r = Reader::from_str("<a>\u{A0} x \u{A0}</a><b>\u{A0}</b><c>\u{A0}-</c>");
+    r.trim_text(true)
+        .whitespace_predicate(|c| c.is_whitespace() || c == '-');
+    next_eq!(r, Start, b"a", Text, b"x", End, b"a", Start, b"b", End, b"b", Start, b"c", End, b"c");
+
+    // the XML whitespace only by default
+    let mut r = Reader::from_str("<a>\u{A0} x \u{A0}</a>");
+    r.trim_text(true);
+    next_eq!(
+        r,
+        Start,
+        b"a",
+        Text,
+        "\u{A0} x \u{A0}".as_bytes(),
+        End,
+        b"a"
+    );
+
+    // attributes are still separated by XML whitespace only
+    let mut r = Reader::from_str("<a x='1'\u{A0}y='2'/>");
+    r.trim_text(true).whitespace_predicate(char::is_whitespace);
+    match r.read_event(&mut Vec::new()) {
+        Ok(Event::Empty(e)) => {
+            let keys = e.attributes().map(|a| a.unwrap().key).collect::<Vec<_>>();
+            assert_eq!(keys, vec![&b"x"[..]]);
+        }
+        e => panic!("expecting Empty, found {:?}", e),
+    }
+}
+
+#[test]
+fn test_reader_config() {
+    let mut reader = Reader::from_str("<a> <b/> </a>");
+    let default = reader.config().clone();
+    reader.trim_text(true).expand_empty_elements(true);
+    next_eq!(reader, Start, b"a");
+
+    // the settings are copied to another reader
+    let mut other = Reader::from_str("<a> <b/> </a>");
+    other.set_config(reader.config().clone());
+    next_eq!(other, Start, b"a", Start, b"b", End, b"b", End, b"a");
+
+    // and restored on the first reader
+    reader.set_config(default);
+    next_eq!(reader, Text, b" ", Empty, b"b");
+}
+
+#[test]
+fn test_check_names() {
+    let mut r = Reader::from_str("<a.1 b-2='1'><_x/></a.1>");
+    r.trim_text(true).check_names(true);
+    next_eq!(r, Start, b"a.1", Empty, b"_x", End, b"a.1");
+
+    let mut buf = Vec::new();
+    for &(xml, position) in &[
+        ("<1badName/>", 1),
+        ("<a><b-></b-><-c>", 13),
+        ("<a></1a>", 5),
+        ("<\u{e9}t\u{e9}></\u{e9}t\u{d7}>", 12),
+    ] {
+        let mut r = Reader::from_str(xml);
+        r.trim_text(true).check_names(true).check_end_names(false);
+        loop {
+            match r.read_event(&mut buf) {
+                Err(Error::InvalidName(p)) => {
+                    assert_eq!(p, position, "{}", xml);
+                    break;
+                }
+                Ok(Eof) | Err(_) => panic!("expecting InvalidName in {}", xml),
+                Ok(_) => (),
+            }
+        }
+    }
+
+    // not checked by default
+    let mut r = Reader::from_str("<1a></1a>");
+    r.trim_text(true);
+    next_eq!(r, Start, b"1a", End, b"1a");
+}
+
+#[test]
+fn test_parser_chunks() {
+    let xml = r#"<?xml version="1.0"?><!DOCTYPE r [<!ENTITY e "x">]><r a="1>2"><!-- a > b -->text<![CDATA[ c]]>d<b/></r>"#;
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    let mut expected = Vec::new();
+    loop {
+        match reader.read_event(&mut buf).unwrap() {
+            Eof => break,
+            e => expected.push(format!("{:?}", e)),
+        }
+    }
+
+    // every chunk size, down to one byte at a time
+    for size in 1..xml.len() + 1 {
+        let mut parser = Parser::new();
+        parser.reader_mut().trim_text(true);
+        let mut events = Vec::new();
+        for chunk in xml.as_bytes().chunks(size) {
+            parser.feed(chunk);
+            while let Some(e) = parser.next_event().unwrap() {
+                events.push(format!("{:?}", e));
+            }
+        }
+        parser.finish();
+        loop {
+            match parser.next_event().unwrap() {
+                Some(Eof) => break,
+                Some(e) => events.push(format!("{:?}", e)),
+                None => panic!("expecting Eof once finished"),
+            }
+        }
+        assert_eq!(events, expected, "chunks of {} bytes", size);
+        assert_eq!(parser.reader().buffer_position(), xml.len());
+    }
+}
+
+#[test]
+fn test_parser_finish() {
+    // text is only complete once followed by a tag or the end of the input
+    let mut parser = Parser::new();
+    parser.reader_mut().trim_text(true);
+    parser.feed(b"<a>te");
+    match parser.next_event() {
+        Ok(Some(Start(ref e))) => assert_eq!(e.name(), b"a"),
+        e => panic!("expecting Start, found {:?}", e),
+    }
+    assert!(parser.next_event().unwrap().is_none());
+    parser.feed(b"xt");
+    assert!(parser.next_event().unwrap().is_none());
+    parser.finish();
+    match parser.next_event() {
+        Ok(Some(Text(ref e))) => assert_eq!(&**e, b"text"),
+        e => panic!("expecting Text, found {:?}", e),
+    }
+    assert!(matches!(parser.next_event(), Ok(Some(Eof))));
+
+    for xml in &["<a><b", "<a x='1", "text<a", "<!-- a", "<![CDATA["] {
+        let mut parser = Parser::new();
+        parser.reader_mut().trim_text(true);
+        parser.feed(xml.as_bytes());
+        while parser.next_event().unwrap().is_some() {}
+        parser.finish();
+        loop {
+            match parser.next_event() {
+                Err(Error::UnexpectedEof(_)) => break,
+                Ok(Some(Eof)) | Ok(None) => panic!("expecting UnexpectedEof for {}", xml),
+                Ok(Some(_)) => (),
+                Err(e) => panic!("expecting UnexpectedEof for {}, found {:?}", xml, e),
+            }
+        }
+        assert!(matches!(parser.next_event(), Ok(Some(Eof))));
+    }
+}
+
+#[test]
+fn test_attribute_error_positions() {
+    let xml = "<root>\n  <a x='1' x='2'/>\n  <b y=2/>\n</root>";
+    let mut r = Reader::from_str(xml);
+    r.trim_text(true);
+    let mut buf = Vec::new();
+    let mut errors = Vec::new();
+    loop {
+        match r.read_event(&mut buf).unwrap() {
+            Start(ref e) | Empty(ref e) => {
+                errors.extend(e.attributes().filter_map(|a| a.err()));
+            }
+            Eof => break,
+            _ => (),
+        }
+        buf.clear();
+    }
+    match errors[..] {
+        [Error::DuplicatedAttribute(dup, first), Error::UnquotedValue(unquoted)] => {
+            assert_eq!(&xml[first..first + 5], "x='1'");
+            assert_eq!(&xml[dup..dup + 5], "x='2'");
+            assert_eq!(&xml[unquoted..unquoted + 1], "2");
+        }
+        _ => panic!("unexpected errors {:?}", errors),
+    }
+}
+
+#[test]
+fn test_peek_event() {
+    let mut r = Reader::from_str("<a>text</a>");
+    r.trim_text(true);
+    let mut buf = Vec::new();
+    match r.peek_event(&mut buf) {
+        Ok(&Start(ref e)) => assert_eq!(e.name(), b"a"),
+        e => panic!("expecting Start, found {:?}", e),
+    }
+    match r.peek_event(&mut buf) {
+        Ok(&Start(ref e)) => assert_eq!(e.name(), b"a"),
+        e => panic!("expecting Start, found {:?}", e),
+    }
+    next_eq!(r, Start, b"a");
+    match r.peek_event(&mut buf) {
+        Ok(&Text(ref e)) => assert_eq!(&**e, b"text"),
+        e => panic!("expecting Text, found {:?}", e),
+    }
+    next_eq!(r, Text, b"text", End, b"a");
+    match r.peek_event(&mut buf) {
+        Ok(&Eof) => (),
+        e => panic!("expecting Eof, found {:?}", e),
+    }
+    match r.read_event(&mut buf) {
+        Ok(Eof) => (),
+        e => panic!("expecting Eof, found {:?}", e),
+    }
+}
+
+#[test]
+fn test_eof_is_terminal() {
+    let mut r = Reader::from_str("<a>text</a>");
+    r.trim_text(true);
+    next_eq!(r, Start, b"a", Text, b"text", End, b"a");
+    let mut buf = Vec::new();
+    for _ in 0..3 {
+        match r.read_event_with_span(&mut buf) {
+            Ok((Eof, span)) => assert_eq!(span, 11..11),
+            e => panic!("expecting Eof, found {:?}", e),
+        }
+    }
+
+    let mut r = Reader::from_str("<a/>");
+    r.trim_text(true).expand_empty_elements(true);
+    next_eq!(r, Start, b"a", End, b"a");
+    let mut ns_buf = Vec::new();
+    for _ in 0..3 {
+        match r.read_namespaced_event(&mut buf, &mut ns_buf) {
+            Ok((None, Eof)) => (),
+            e => panic!("expecting Eof, found {:?}", e),
+        }
+    }
+
+    let mut r = Reader::from_str("<a></b>");
+    r.trim_text(true);
+    next_eq!(r, Start, b"a");
+    assert!(r.read_event(&mut buf).is_err());
+    for _ in 0..3 {
+        match r.read_event(&mut buf) {
+            Ok(Eof) => (),
+            e => panic!("expecting Eof, found {:?}", e),
+        }
+    }
+}
+
+#[test]
+fn test_xml_space() {
+    let mut r = Reader::from_str(
+        "<a> 1 <b xml:space='preserve'> 2 <c> 3 </c><d xml:space=\"default\"> 4 </d> </b> 5 </a>",
+    );
+    r.trim_text(true);
+    next_eq!(
+        r, Start, b"a", Text, b"1", Start, b"b", Text, b" 2 ", Start, b"c", Text, b" 3 ", End,
+        b"c", Start, b"d", Text, b"4", End, b"d", Text, b" ", End, b"b", Text, b"5", End, b"a"
+    );
+}
+
+#[test]
+fn test_comment_with_double_dash() {
+    let mut r = Reader::from_str("<a><!-- b -- c --></a>");
+    r.trim_text(true);
+    next_eq!(r, Start, b"a", Comment, b" b -- c ", End, b"a");
+
+    let mut r = Reader::from_str("<a><!-- b -- c --></a>");
+    r.trim_text(true).check_comments(true);
+    next_eq!(r, Start, b"a");
+    match r.read_event(&mut Vec::new()) {
+        Err(Error::UnexpectedToken(ref t)) if t == "--" => (),
+        e => panic!("expecting UnexpectedToken error, found {:?}", e),
+    }
+    assert_eq!(r.buffer_position(), 10);
+}
+
+#[test]
+fn test_writer() {
+    let txt = include_str!("../tests/documents/test_writer.xml").trim();
+    let mut reader = Reader::from_str(txt);
+    reader.trim_text(true);
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Eof) => break,
+            Ok(e) => assert!(writer.write_event(e).is_ok()),
+            Err(e) => panic!(e),
+        }
+    }
+
+    let result = writer.into_inner().into_inner();
+    assert_eq!(result, txt.as_bytes());
+}
+
+#[test]
+fn test_writer_borrow() {
+    let txt = include_str!("../tests/documents/test_writer.xml").trim();
+    let mut reader = Reader::from_str(txt);
+    reader.trim_text(true);
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Eof) => break,
+            Ok(e) => assert!(writer.write_event(&e).is_ok()), // either `e` or `&e`
+            Err(e) => panic!(e),
+        }
+    }
+
+    let result = writer.into_inner().into_inner();
+    assert_eq!(result, txt.as_bytes());
+}
+
+#[test]
+fn test_writer_indent() {
+    let txt = include_str!("../tests/documents/test_writer_indent.xml");
+    let mut reader = Reader::from_str(txt);
+    reader.trim_text(true);
+    let mut writer = Writer::new_with_indent(Cursor::new(Vec::new()), b' ', 4);
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Eof) => break,
+            Ok(e) => assert!(writer.write_event(e).is_ok()),
+            Err(e) => panic!(e),
+        }
+    }
+
+    let result = writer.into_inner().into_inner();
+    // println!("{:?}", String::from_utf8_lossy(&result));
+    assert_eq!(result, txt.as_bytes());
+}
+
+#[test]
+fn test_writer_indent_mixed_content() {
+    fn texts(xml: &[u8]) -> Vec<String> {
+        let mut reader = Reader::from_reader(xml);
+        let mut buf = Vec::new();
+        let mut texts = Vec::new();
+        loop {
+            match reader.read_event(&mut buf).unwrap() {
+                Eof => return texts,
+                Text(ref e) if e.iter().all(|b| b.is_ascii_whitespace()) => (),
+                Text(e) => texts.push(e.unescape_and_decode(&reader).unwrap()),
+                _ => (),
+            }
+            buf.clear();
+        }
+    }
+
+    let xml = "<html><body><p>Hello <b>world</b>, <i>again <br/>and</i>!</p>\
+               <ul><li>one</li><li>two <em>2</em></li></ul></body></html>";
+    let mut reader = Reader::from_str(xml);
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event(&mut buf).unwrap() {
+            Eof => break,
+            e => assert!(writer.write_event(e).is_ok()),
+        }
+        buf.clear();
+    }
+    let result = writer.into_inner();
+    assert_eq!(
+        from_utf8(&result).unwrap(),
+        "<html>\
+         \n  <body>\
+         \n    <p>Hello <b>world</b>, <i>again <br/>and</i>!</p>\
+         \n    <ul>\
+         \n      <li>one</li>\
+         \n      <li>two <em>2</em></li>\
+         \n    </ul>\
+         \n  </body>\
+         \n</html>"
+    );
+    assert_eq!(texts(&result), texts(xml.as_bytes()));
+}
+
+#[test]
+fn test_writer_wrap_attributes() {
+    let write = |writer: &mut Writer<Vec<u8>>| {
+        let mut root = BytesStart::borrowed_name(b"config");
+        root.push_attribute(("version", "1"));
+        writer.write_event(Start(root)).unwrap();
+        let mut service = BytesStart::borrowed(br#"service name="a&amp;b"  port='80'"#, 7);
+        service.push_attribute(("enabled", "true"));
+        writer.write_event(Start(service)).unwrap();
+        let mut option = BytesStart::borrowed_name(b"option");
+        option.extend_attributes(vec![("key", "timeout"), ("value", "30")]);
+        writer.write_event(Empty(option)).unwrap();
+        writer.write_end().unwrap();
+        writer.write_end().unwrap();
+    };
+
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    writer.wrap_attributes(Some(34));
+    write(&mut writer);
+    assert_eq!(
+        writer.into_string().unwrap(),
+        r#"<config version="1">
+  <service name="a&amp;b"
+           port='80'
+           enabled="true">
+    <option key="timeout" value="30"/>
+  </service>
+</config>"#
+    );
+
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    writer
+        .wrap_attributes(Some(20))
+        .wrapped_tag_end(WrappedTagEnd::OwnLine);
+    write(&mut writer);
+    assert_eq!(
+        writer.into_string().unwrap(),
+        r#"<config version="1">
+  <service name="a&amp;b"
+           port='80'
+           enabled="true"
+  >
+    <option key="timeout"
+            value="30"
+    />
+  </service>
+</config>"#
+    );
+
+    // without indentation, tags are never wrapped
+    let mut writer = Writer::new(Vec::new());
+    writer.wrap_attributes(Some(20));
+    write(&mut writer);
+    assert_eq!(
+        writer.into_string().unwrap(),
+        r#"<config version="1"><service name="a&amp;b"  port='80' enabled="true"><option key="timeout" value="30"/></service></config>"#
+    );
+}
+
+#[test]
+fn test_rename_elements() {
+    let input = r#"<old a="1"><child b='&amp;'>text<old/></child><old c="2">more</old></old>"#;
+    let mut reader = Reader::from_str(input);
+    let mut writer = Writer::new(Vec::new());
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event(&mut buf).unwrap() {
+            Start(ref mut e) if e.name() == b"old" => {
+                e.set_name(b"new");
+                assert_eq!(e.to_end().name(), b"new");
+                writer.write_event(Start(e.clone()))
+            }
+            Empty(ref mut e) if e.name() == b"old" => {
+                writer.write_event(Empty(e.set_name(b"new").clone()))
+            }
+            End(ref e) if e.name() == b"old" => writer.write_event(End(BytesEnd::borrowed(b"new"))),
+            Eof => break,
+            e => writer.write_event(e),
+        }
+        .unwrap();
+        buf.clear();
+    }
+    assert_eq!(
+        writer.into_string().unwrap(),
+        r#"<new a="1"><child b='&amp;'>text<new/></child><new c="2">more</new></new>"#
+    );
+}
+
+#[test]
+fn test_write_with_to_end() {
+    let starts = vec![
+        BytesStart::owned(b"root xmlns:a=\"urn:a\"".to_vec(), 4),
+        BytesStart::borrowed(b"a:child id='1'", 7),
+        BytesStart::borrowed_name(b"leaf"),
+    ];
+    let mut writer = Writer::new(Vec::new());
+    for start in &starts {
+        writer.write_event(Event::Start(start.to_borrowed())).unwrap();
+    }
+    for start in starts.iter().rev() {
+        writer.write_event(Event::End(start.to_end())).unwrap();
+    }
+    let result = writer.into_inner();
+    assert_eq!(
+        result,
+        &b"<root xmlns:a=\"urn:a\"><a:child id='1'><leaf></leaf></a:child></root>"[..]
+    );
+
+    let mut reader = Reader::from_reader(&result[..]);
+    reader.trim_text(true).check_end_names(true);
+    let mut buf = Vec::new();
+    let mut depth = 0;
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(_)) => depth += 1,
+            Ok(Event::End(_)) => depth -= 1,
+            Ok(Event::Eof) => break,
+            e => panic!("expecting Start or End, found {:?}", e),
+        }
+        buf.clear();
+    }
+    assert_eq!(depth, 0);
+}
+
+#[test]
+fn test_collect_owned_events() {
+    let input = r#"<?xml version="1.0"?><root a="1" b='&lt;'><!-- c --><x/>text &amp; more<![CDATA[cdata]]><?pi x?></root>"#;
+    let events = {
+        let mut reader = Reader::from_str(input);
+        let mut buf = Vec::new();
+        let mut events: Vec<Event<'static>> = Vec::new();
+        loop {
+            match reader.read_event(&mut buf).unwrap() {
+                Event::Eof => break,
+                e => events.push(e.into_owned()),
+            }
+            buf.clear();
+        }
+        events
+    };
+
+    let events = std::thread::spawn(move || events).join().unwrap();
+    match events.iter().find(|e| matches!(e, Event::Start(_))) {
+        Some(Event::Start(e)) => {
+            assert_eq!(e.name(), b"root");
+            assert_eq!(e.attributes().count(), 2);
+        }
+        e => panic!("expecting Start, found {:?}", e),
+    }
+    let mut writer = Writer::new(Vec::new());
+    for event in &events {
+        writer.write_event(event).unwrap();
+    }
+    assert_eq!(writer.into_inner(), input.as_bytes());
+}
+
+#[test]
+fn test_plain_text_round_trip() {
+    let text = "a & b < c ]]> d → ü";
+    let mut writer = Writer::new(Vec::new());
+    writer
+        .write_event(Start(BytesStart::borrowed_name(b"a")))
+        .unwrap();
+    writer
+        .write_event(Text(BytesText::from_plain_str(text)))
+        .unwrap();
+    writer.write_event(End(BytesEnd::borrowed(b"a"))).unwrap();
+    let written = writer.into_inner();
+    assert_eq!(
+        from_utf8(&written).unwrap(),
+        "<a>a &amp; b &lt; c ]]&gt; d → ü</a>"
+    );
+
+    let mut reader = Reader::from_reader(&written[..]);
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    reader.read_event(&mut buf).unwrap();
+    match reader.read_event(&mut buf) {
+        Ok(Text(e)) => {
+            assert_eq!(&*e.unescaped().unwrap(), text.as_bytes());
+            assert_eq!(e.unescape_and_decode(&reader).unwrap(), text);
+            // the escaped content is written as is, without escaping it twice
+            let mut writer = Writer::new(Vec::new());
+            writer
+                .write_event(Text(BytesText::from_escaped(e.escaped())))
+                .unwrap();
+            assert_eq!(writer.into_inner(), &written[3..written.len() - 4]);
+        }
+        e => panic!("expecting Text, found {:?}", e),
+    }
+}
+
+#[test]
+fn test_writer_into_string() {
+    let mut writer = Writer::new(Vec::new());
+    writer
+        .write_event(Empty(BytesStart::borrowed_name("café".as_bytes())))
+        .unwrap();
+    assert_eq!(writer.into_string().unwrap(), "<café/>");
+
+    let mut writer = Writer::new(Vec::new());
+    writer
+        .write_event(Text(BytesText::from_escaped(&b"\xff"[..])))
+        .unwrap();
+    match writer.into_string() {
+        Err(Error::Utf8(_)) => (),
+        e => panic!("expecting Utf8 error, found {:?}", e),
+    }
+}
+
+#[test]
+fn test_writer_escape_text() {
+    let mut writer = Writer::new(Vec::new());
+    writer
+        .write_event(Text(BytesText::from_plain_str("a & b < c > d")))
+        .unwrap();
+    writer
+        .write_event(Text(BytesText::from_escaped_str(" &amp; &lt;")))
+        .unwrap();
+    writer
+        .create_element("e")
+        .write_text_content("<&>")
+        .unwrap();
+    assert_eq!(
+        writer.into_string().unwrap(),
+        "a &amp; b &lt; c &gt; d &amp; &lt;<e>&lt;&amp;&gt;</e>"
+    );
+}
+
+#[test]
+fn test_writer_check_escaped_text() {
+    let mut writer = Writer::new(Vec::new());
+    writer.check_escaped_text(true);
+    writer
+        .write_event(Text(BytesText::from_escaped_str("&lt;a&gt;")))
+        .unwrap();
+    match writer.write_event(Text(BytesText::from_escaped_str("a <b>"))) {
+        Err(Error::UnescapedText(2)) => (),
+        e => panic!("expecting UnescapedText error, found {:?}", e),
+    }
+    assert_eq!(writer.into_string().unwrap(), "&lt;a&gt;");
+}
+
+#[test]
+fn test_writer_split_cdata() {
+    let mut writer = Writer::new(Vec::new());
+    match writer.write_event(CData(BytesText::from_escaped_str("a]]>b"))) {
+        Err(Error::UnescapedCData(1)) => (),
+        e => panic!("expecting UnescapedCData error, found {:?}", e),
+    }
+    writer.split_cdata(true);
+    writer
+        .write_event(CData(BytesText::from_escaped_str("a]]>b")))
+        .unwrap();
+    let xml = writer.into_string().unwrap();
+    assert_eq!(xml, "<![CDATA[a]]]]><![CDATA[>b]]>");
+
+    let mut reader = Reader::from_str(&xml);
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    let mut content = Vec::new();
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(CData(e)) => content.extend_from_slice(&e.unescaped().unwrap()),
+            Ok(Eof) => break,
+            e => panic!("expecting CData event, found {:?}", e),
+        }
+        buf.clear();
+    }
+    assert_eq!(content, b"a]]>b");
+}
+
+#[test]
+fn test_writer_write_raw() {
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    writer
+        .write_event(Start(BytesStart::borrowed_name(b"root")))
+        .unwrap();
+    writer
+        .write_event(Empty(BytesStart::borrowed_name(b"first")))
+        .unwrap();
+    writer
+        .write_raw(b"<raw a=\"&lt;\"><b>x</b><c/></raw>")
+        .unwrap();
+    writer
+        .write_event(Empty(BytesStart::borrowed_name(b"last")))
+        .unwrap();
+    writer
+        .write_event(End(BytesEnd::borrowed(b"root")))
+        .unwrap();
+    assert_eq!(
+        writer.into_string().unwrap(),
+        "<root>\n  <first/>\n  <raw a=\"&lt;\"><b>x</b><c/></raw>\n  <last/>\n</root>"
+    );
+}
+
+#[test]
+fn test_writer_comment_pi() {
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    writer.write_pi(b"xml-stylesheet", b"href=\"a.xsl\"").unwrap();
+    writer
+        .write_event(Start(BytesStart::borrowed_name(b"root")))
+        .unwrap();
+    writer.write_comment(b" a - comment ").unwrap();
+    writer.write_pi(b"empty", b"").unwrap();
+    writer
+        .write_event(End(BytesEnd::borrowed(b"root")))
+        .unwrap();
+    assert_eq!(
+        writer.into_string().unwrap(),
+        "<?xml-stylesheet href=\"a.xsl\"?>\n<root>\n  <!-- a - comment -->\n  <?empty?>\n</root>"
+    );
+
+    let mut writer = Writer::new(Vec::new());
+    for text in [&b"a--b"[..], b"a-"].iter() {
+        match writer.write_comment(text) {
+            Err(Error::NotWellFormed(_)) => (),
+            e => panic!("Expecting NotWellFormed for {:?}, found {:?}", text, e),
+        }
+    }
+    for &(target, data) in [(&b"XmL"[..], &b""[..]), (b"1pi", b""), (b"pi", b"a?>b")].iter() {
+        match writer.write_pi(target, data) {
+            Err(Error::NotWellFormed(_)) => (),
+            e => panic!("Expecting NotWellFormed for {:?}, found {:?}", target, e),
+        }
+    }
+    assert!(writer.into_inner().is_empty());
+}
+
+#[test]
+fn test_writer_collapse_empty_elements() {
+    let mut reader = Reader::from_str(
+        r#"<root><a x="1"></a><b>text</b><c><d></d></c><e/><f>
+</f></root>"#,
+    );
+    reader.expand_empty_elements(true);
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    writer.collapse_empty_elements(true);
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event(&mut buf).unwrap() {
+            Eof => break,
+            e => assert!(writer.write_event(e).is_ok()),
+        }
+        buf.clear();
+    }
+    assert_eq!(
+        writer.into_string().unwrap(),
+        "<root>\n  <a x=\"1\"/>\n  <b>text</b>\n  <c>\n    <d/>\n  </c>\n  <e/>\n  <f>\n</f>\n</root>"
+    );
+
+    let mut writer = Writer::new(Vec::new());
+    writer.collapse_empty_elements(true);
+    writer
+        .write_event(Start(BytesStart::borrowed_name(b"a")))
+        .unwrap();
+    writer
+        .write_event(Start(BytesStart::borrowed_name(b"b")))
+        .unwrap();
+    writer.write_end().unwrap();
+    writer.write_end().unwrap();
+    writer
+        .write_event(Start(BytesStart::borrowed_name(b"c")))
+        .unwrap();
+    writer.flush().unwrap();
+    assert_eq!(writer.into_string().unwrap(), "<a><b/></a><c>");
+
+    // the pending start tag is written when the writer is consumed
+    let mut writer = Writer::new(Vec::new());
+    writer.collapse_empty_elements(true);
+    writer
+        .write_event(Start(BytesStart::borrowed_name(b"a")))
+        .unwrap();
+    assert_eq!(writer.into_string().unwrap(), "<a>");
+
+    let mut writer = Writer::new(Vec::new());
+    writer.collapse_empty_elements(true);
+    writer
+        .write_event(Start(BytesStart::borrowed_name(b"a")))
+        .unwrap();
+    assert_eq!(writer.try_into_inner().unwrap(), b"<a>");
+
+    // into_inner does not write a pending start tag
+    let mut writer = Writer::new(Vec::new());
+    writer.collapse_empty_elements(true);
+    writer
+        .write_event(Start(BytesStart::borrowed_name(b"a")))
+        .unwrap();
+    assert!(writer.into_inner().is_empty());
+
+    // a pending start tag is closed by close_all
+    let mut writer = Writer::new(Vec::new());
+    writer.collapse_empty_elements(true);
+    writer
+        .write_event(Start(BytesStart::borrowed_name(b"a")))
+        .unwrap();
+    writer
+        .write_event(Start(BytesStart::borrowed_name(b"b")))
+        .unwrap();
+    writer.close_all().unwrap();
+    assert_eq!(writer.into_string().unwrap(), "<a><b/></a>");
+}
+
+#[test]
+fn test_writer_start_ns() {
+    const SOAP: &str = "http://schemas.xmlsoap.org/soap/envelope/";
+    const APP: &str = "urn:example:app";
+    const XML: &str = "http://www.w3.org/XML/1998/namespace";
+
+    let mut writer = Writer::new(Vec::new());
+    writer.prefix_generator(|uri, n| {
+        if uri == SOAP && n == 0 {
+            "soap".to_string()
+        } else {
+            format!("ns{}", n)
+        }
+    });
+    writer
+        .write_start_ns(SOAP, "Envelope", vec![(SOAP, "encodingStyle", "urn:enc")])
+        .unwrap();
+    writer.write_start_ns(SOAP, "Body", None).unwrap();
+    writer.set_default_namespace(APP);
+    writer
+        .write_start_ns(APP, "order", vec![("", "id", "1 & 2"), (XML, "lang", "en")])
+        .unwrap();
+    writer
+        .write_start_ns(APP, "item", vec![(APP, "sku", "a")])
+        .unwrap();
+    writer.write_end().unwrap();
+    writer
+        .write_start_ns(APP, "item", vec![(APP, "sku", "b")])
+        .unwrap();
+    writer.write_end().unwrap();
+    writer.write_start_ns("", "note", None).unwrap();
+    writer.write_end().unwrap();
+    writer.close_all().unwrap();
+    let xml = writer.into_string().unwrap();
+    assert_eq!(
+        xml,
+        format!(
+            "<soap:Envelope xmlns:soap=\"{0}\" soap:encodingStyle=\"urn:enc\"><soap:Body>\
+             <order xmlns=\"{1}\" id=\"1 &amp; 2\" xml:lang=\"en\">\
+             <item xmlns:ns0=\"{1}\" ns0:sku=\"a\"></item>\
+             <item xmlns:ns0=\"{1}\" ns0:sku=\"b\"></item>\
+             <note xmlns=\"\"></note></order></soap:Body></soap:Envelope>",
+            SOAP, APP
+        )
+    );
+
+    let expected: &[(&str, &str)] = &[
+        (SOAP, "Envelope"),
+        (SOAP, "encodingStyle"),
+        (SOAP, "Body"),
+        (APP, "order"),
+        ("", "id"),
+        (XML, "lang"),
+        (APP, "item"),
+        (APP, "sku"),
+        (APP, "item"),
+        (APP, "sku"),
+        ("", "note"),
+    ];
+    let mut names = Vec::new();
+    let mut reader = Reader::from_str(&xml);
+    let mut buf = Vec::new();
+    let mut ns_buf = Vec::new();
+    loop {
+        match reader.read_namespaced_event(&mut buf, &mut ns_buf).unwrap() {
+            (ns, Start(e)) => {
+                names.push((ns.unwrap_or(b"").to_vec(), e.local_name().to_vec()));
+                for attribute in e.attributes() {
+                    let key = attribute.unwrap().key;
+                    if !key.starts_with(b"xmlns") {
+                        // the `xml` prefix is bound implicitly, so it is not resolved
+                        let ns = match reader.attribute_namespace(key, &ns_buf).0 {
+                            None if key.starts_with(b"xml:") => XML.as_bytes(),
+                            ns => ns.unwrap_or(b""),
+                        };
+                        names.push((ns.to_vec(), QName(key).local_name().to_vec()));
+                    }
+                }
+            }
+            (_, Eof) => break,
+            _ => (),
+        }
+        buf.clear();
+    }
+    let expected = expected
+        .iter()
+        .map(|&(ns, local_name)| (ns.as_bytes().to_vec(), local_name.as_bytes().to_vec()))
+        .collect::<Vec<_>>();
+    assert_eq!(names, expected);
+}
+
+#[test]
+fn test_writer_start_ns_generated_prefixes() {
+    // reserved prefixes are skipped
+    let mut writer = Writer::new(Vec::new());
+    writer.prefix_generator(|_, n| ["xml", "xmlns", "", "p"][n].to_string());
+    writer.write_start_ns("urn:a", "x", None).unwrap();
+    writer.close_all().unwrap();
+    assert_eq!(
+        writer.into_string().unwrap(),
+        r#"<p:x xmlns:p="urn:a"></p:x>"#
+    );
+
+    // a generator returning the same prefix does not loop forever
+    let mut writer = Writer::new(Vec::new());
+    writer.prefix_generator(|_, _| "p".to_string());
+    writer.write_start_ns("urn:a", "x", None).unwrap();
+    match writer.write_start_ns("urn:b", "x", None) {
+        Err(Error::NotWellFormed(_)) => (),
+        e => panic!("expecting NotWellFormed error, found {:?}", e),
+    }
+    writer.write_start_ns("urn:a", "y", None).unwrap();
+    writer.close_all().unwrap();
+    assert_eq!(
+        writer.into_string().unwrap(),
+        r#"<p:x xmlns:p="urn:a"><p:y></p:y></p:x>"#
+    );
+}
+
+#[test]
+fn test_writer_bytes_written() {
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 4);
+    writer.split_cdata(true).write_bom().unwrap();
+    let events = vec![
+        Decl(BytesDecl::new(b"1.0", Some(b"UTF-8"), None)),
+        DocType(BytesText::from_escaped_str(" root")),
+        PI(BytesText::from_escaped_str("pi data")),
+        Comment(BytesText::from_escaped_str(" comment ")),
+        Start(BytesStart::borrowed(b"root a=\"1\"", 4)),
+        Start(BytesStart::borrowed_name(b"text")),
+        Text(BytesText::from_plain_str("a < b")),
+        End(BytesEnd::borrowed(b"text")),
+        CData(BytesText::from_escaped_str("c]]>d")),
+        Empty(BytesStart::borrowed_name(b"empty")),
+        Text(BytesText::from_escaped_str("")),
+    ];
+    let mut counted = 3;
+    for event in events {
+        let before = writer.get_ref().len();
+        let written = writer.write_event(&event).unwrap();
+        assert_eq!(writer.get_ref().len() - before, written, "{:?}", event);
+        counted += written;
+    }
+    writer.write_raw(b"<raw/>").unwrap();
+    writer.write_indent().unwrap();
+    write!(writer.text_writer(), "x & y").unwrap();
+    writer.write_end().unwrap();
+    assert_eq!(writer.bytes_written(), writer.get_ref().len() as u64);
+    assert!(writer.bytes_written() > counted as u64);
+    let len = writer.bytes_written();
+    assert_eq!(writer.into_inner().len() as u64, len);
+
+    // a deferred start tag is counted with the event writing it
+    let mut writer = Writer::new(Vec::new());
+    writer.collapse_empty_elements(true);
+    let start = Start(BytesStart::borrowed_name(b"a"));
+    assert_eq!(writer.write_event(&start).unwrap(), 0);
+    assert_eq!(
+        writer.write_event(End(BytesEnd::borrowed(b"a"))).unwrap(),
+        4
+    );
+    assert_eq!(writer.write_event(&start).unwrap(), 0);
+    assert_eq!(writer.write_event(&start).unwrap(), 3);
+    assert_eq!(writer.bytes_written(), 7);
+    // the pending one is written when the writer is consumed
+    assert_eq!(writer.try_into_inner().unwrap(), b"<a/><a><a>");
+}
+
+#[test]
+fn test_writer_text_writer() {
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    writer
+        .write_event(Start(BytesStart::borrowed_name(b"a")))
+        .unwrap();
+    {
+        let mut text = writer.text_writer();
+        // split inside of the `é` character
+        text.write_all(&"<é".as_bytes()[..2]).unwrap();
+        text.write_all(&"<é".as_bytes()[2..]).unwrap();
+        let mut chunks = b"&quo".chain(&b"t;\""[..]);
+        io::copy(&mut chunks, &mut text).unwrap();
+    }
+    writer.write_event(End(BytesEnd::borrowed(b"a"))).unwrap();
+    assert_eq!(
+        writer.into_string().unwrap(),
+        "<a>&lt;é&amp;quot;&quot;</a>"
+    );
+}
+
+#[test]
+fn test_writer_text_from_reader() {
+    /// Reads its content 3 bytes at a time
+    struct Chunks<'a>(&'a [u8]);
+
+    impl<'a> Read for Chunks<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = self.0.len().min(buf.len()).min(3);
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(len)
+        }
+    }
+
+    let payload = "a&b<<c>>é&&".repeat(100);
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    writer
+        .write_event(Start(BytesStart::borrowed_name(b"root")))
+        .unwrap();
+    writer
+        .write_event(Start(BytesStart::borrowed_name(b"log")))
+        .unwrap();
+    let read = writer
+        .write_text_from_reader(Chunks(payload.as_bytes()))
+        .unwrap();
+    assert_eq!(read, payload.len() as u64);
+    writer.write_event(End(BytesEnd::borrowed(b"log"))).unwrap();
+    writer
+        .write_event(End(BytesEnd::borrowed(b"root")))
+        .unwrap();
+    assert_eq!(
+        writer.into_string().unwrap(),
+        format!(
+            "<root>\n  <log>{}</log>\n</root>",
+            "a&amp;b&lt;&lt;c&gt;&gt;é&amp;&amp;".repeat(100)
+        )
+    );
+}
+
+#[test]
+fn test_writer_fmt_writer() {
+    let mut writer = Writer::from_fmt_writer(String::new());
+    writer
+        .write_event(Start(BytesStart::borrowed("ré a=\"€\"".as_bytes(), 3)))
+        .unwrap();
+    {
+        let mut text = writer.text_writer();
+        // split inside of the `€` character
+        text.write_all(b"<\xE2\x82").unwrap();
+        text.write_all(b"\xAC").unwrap();
+    }
+    writer
+        .write_event(End(BytesEnd::borrowed("ré".as_bytes())))
+        .unwrap();
+    assert_eq!(writer.into_fmt_inner().unwrap(), "<ré a=\"€\">&lt;€</ré>");
+
+    let mut writer = Writer::from_fmt_writer(String::new());
+    match writer.write_event(Text(BytesText::from_escaped(&b"a\xFFb"[..]))) {
+        Err(Error::Io(ref e)) if e.kind() == io::ErrorKind::InvalidData => (),
+        e => panic!("Expecting InvalidData error, found {:?}", e),
+    }
+    let mut writer = Writer::from_fmt_writer(String::new());
+    writer.write(b"a\xE2\x82").unwrap();
+    match writer.into_fmt_inner() {
+        Err(Error::Utf8(_)) => (),
+        e => panic!("Expecting Utf8 error, found {:?}", e),
+    }
+}
+
+#[test]
+fn test_writer_config() {
+    let mut config = WriterConfig::new();
+    config
+        .indent(b'\t', 1)
+        .newline(b"\r\n")
+        .collapse_empty_elements(true)
+        .attribute_quote_style(QuoteStyle::Auto)
+        .check_end_names(true);
+    let events = vec![
+        Start(BytesStart::borrowed(br#"a b='"'"#, 1)),
+        Start(BytesStart::borrowed_name(b"c")),
+        End(BytesEnd::borrowed(b"c")),
+        End(BytesEnd::borrowed(b"a")),
+    ];
+    let mut outputs = Vec::new();
+    for config in vec![config.clone(), config] {
+        let mut writer = config.build(Vec::new());
+        for event in &events {
+            writer.write_event(event).unwrap();
+        }
+        match writer.write_event(End(BytesEnd::borrowed(b"a"))) {
+            Err(Error::EndEventMismatch { .. }) => (),
+            e => panic!("Expecting EndEventMismatch error, found {:?}", e),
+        }
+        outputs.push(writer.into_string().unwrap());
+    }
+    assert_eq!(outputs[0], "<a b='\"'>\r\n\t<c/>\r\n</a>");
+    assert_eq!(outputs[0], outputs[1]);
+}
+
+#[test]
+fn test_writer_attribute_quote() {
+    let mut reader = Reader::from_str(r#"<a b='x"y' c="z&apos;"/><d e="&lt;'"></d>"#);
+    let mut writer = Writer::new(Vec::new());
+    writer.attribute_quote(b'\'');
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event(&mut buf).unwrap() {
+            Eof => break,
+            e => assert!(writer.write_event(e).is_ok()),
+        }
+        buf.clear();
+    }
+    let mut start = BytesStart::borrowed_name(b"f");
+    start.push_attribute(("g", "'"));
+    writer.write_event(Empty(start)).unwrap();
+    assert_eq!(
+        writer.into_string().unwrap(),
+        "<a b='x\"y' c='z&apos;'/><d e='&lt;&apos;'></d><f g='&apos;'/>"
+    );
+}
+
+#[test]
+fn test_writer_attribute_quote_style_auto() {
+    let mut reader = Reader::from_str(r#"<a b='x"y' c="x'y" d='x"&apos;' e='z'/>"#);
+    let mut writer = Writer::new(Vec::new());
+    writer.attribute_quote_style(QuoteStyle::Auto);
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event(&mut buf).unwrap() {
+            Eof => break,
+            e => assert!(writer.write_event(e).is_ok()),
+        }
+        buf.clear();
+    }
+    let mut start = BytesStart::borrowed_name(b"f");
+    start.push_attribute_with_style("g", "x\"'", QuoteStyle::Auto);
+    writer.write_event(Empty(start)).unwrap();
+    assert_eq!(
+        writer.into_string().unwrap(),
+        r#"<a b='x"y' c="x'y" d='x"&apos;' e="z"/><f g="x&quot;'"/>"#
+    );
+}
+
+#[test]
+fn test_writer_canonical_attributes() {
+    let mut reader = Reader::from_str(concat!(
+        r#"<a z="1" b:y='2' xmlns:b="urn:b" a:x="3" xmlns="urn:default" xmlns:a="urn:z" "#,
+        r#"xml:lang="en" c:w="4" y='5'/><b  y="&lt;"   x='"'>"#,
+    ));
+    let mut writer = Writer::new(Vec::new());
+    writer.canonical_attributes(true);
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event(&mut buf).unwrap() {
+            Eof => break,
+            e => assert!(writer.write_event(e).is_ok()),
+        }
+        buf.clear();
+    }
+    assert_eq!(
+        writer.into_string().unwrap(),
+        concat!(
+            r#"<a xmlns="urn:default" xmlns:a="urn:z" xmlns:b="urn:b" y='5' z="1" "#,
+            r#"xml:lang="en" b:y='2' a:x="3" c:w="4"/><b x='"' y="&lt;">"#,
+        )
+    );
+
+    // the same attributes in any order are written the same way
+    let mut first = BytesStart::borrowed_name(b"e");
+    first.extend_attributes(vec![("b", "1"), ("a", "2"), ("c", "3")]);
+    let mut second = BytesStart::borrowed_name(b"e");
+    second.extend_attributes(vec![("c", "3"), ("b", "1"), ("a", "2")]);
+    let mut writer = Writer::new(Vec::new());
+    writer.canonical_attributes(true).attribute_quote(b'\'');
+    writer.write_event(Empty(first)).unwrap();
+    writer.write_event(Empty(second)).unwrap();
+    assert_eq!(
+        writer.into_string().unwrap(),
+        "<e a='2' b='1' c='3'/><e a='2' b='1' c='3'/>"
+    );
+
+    // prefixes declared on ancestors are resolved like in canonical XML
+    let root = r#"<r xmlns:z="urn:a" xmlns:a="urn:z"><e a:x="1" z:y="2" b="3"/></r>"#;
+    // the prefixes are undeclared once the root is closed
+    let sorted = format!(r#"{}<e a:x="1" z:y="2"/>"#, root);
+    let cases = [
+        (
+            &*sorted,
+            false,
+            r#"<r xmlns:a="urn:z" xmlns:z="urn:a"><e b="3" z:y="2" a:x="1"/></r><e a:x="1" z:y="2"/>"#,
+        ),
+        (
+            root,
+            true,
+            r#"<r xmlns:a="urn:z" xmlns:z="urn:a"><e b="3" z:y="2" a:x="1"></e></r>"#,
+        ),
+    ];
+    for &(xml, canonical, expected) in &cases {
+        let mut reader = Reader::from_str(xml);
+        let mut writer = Writer::new(Vec::new());
+        if canonical {
+            writer.canonical(true);
+        } else {
+            writer.canonical_attributes(true);
+        }
+        loop {
+            match reader.read_event(&mut buf).unwrap() {
+                Eof => break,
+                e => writer.write_event(e).unwrap(),
+            };
+            buf.clear();
+        }
+        assert_eq!(writer.into_string().unwrap(), expected);
+    }
+}
+
+#[test]
+#[should_panic]
+fn test_writer_attribute_quote_invalid() {
+    Writer::new(Vec::<u8>::new()).attribute_quote(b'`');
+}
+
+#[test]
+fn test_reformat() {
+    fn reformat(xml: &[u8], indent: Option<(u8, usize)>) -> String {
+        let mut result = Vec::new();
+        quick_xml::reformat(xml, &mut result, indent).unwrap();
+        String::from_utf8(result).unwrap()
+    }
+
+    let txt = include_str!("../tests/documents/test_writer_indent.xml");
+    let minified = reformat(txt.as_bytes(), None);
+    assert!(!minified.contains('\n'));
+    assert_eq!(reformat(minified.as_bytes(), Some((b' ', 4))), txt);
+
+    let documents = [
+        &include_bytes!("../tests/documents/sample_1.xml")[..],
+        &include_bytes!("../tests/documents/sample_ns.xml")[..],
+        &include_bytes!("../tests/documents/test_writer.xml")[..],
+        &include_bytes!("../tests/sample_rss.xml")[..],
+    ];
+    for xml in documents.iter() {
+        let indented = reformat(xml, Some((b'\t', 1)));
+        let minified = reformat(xml, None);
+        assert_eq!(reformat(indented.as_bytes(), None), minified);
+        assert_eq!(reformat(minified.as_bytes(), Some((b'\t', 1))), indented);
+    }
+
+    let xml = r#"<?xml version="1.0"?><!--c--><root b='1' a="2"><p>Hello <b>world</b> <i>!</i></p>
+        <c><![CDATA[<x> ]]></c><?pi x?><e>  </e></root>"#;
+    assert_eq!(
+        reformat(xml.as_bytes(), Some((b' ', 2))),
+        r#"<?xml version="1.0"?>
+<!--c-->
+<root b='1' a="2">
+  <p>Hello <b>world</b> <i>!</i></p>
+  <c><![CDATA[<x> ]]></c>
+  <?pi x?>
+  <e>
+  </e>
+</root>"#
+    );
+
+    // events are streamed, so mixed content is only detected at its first text
+    let xml = b"<p>\n<b>x</b> y <i>!</i>\n</p><q>a <b/> <i/></q>";
+    assert_eq!(
+        reformat(xml, Some((b' ', 2))),
+        "<p>\n  <b>x</b> y <i>!</i>\n</p>\n<q>a <b/> <i/></q>"
+    );
+    assert_eq!(reformat(xml, None), "<p><b>x</b> y <i>!</i>\n</p><q>a <b/> <i/></q>");
+}
+
+#[test]
+fn test_writer_accessors() {
+    let mut writer = Writer::new(Vec::new());
+    writer
+        .write_event(Empty(BytesStart::borrowed_name(b"a")))
+        .unwrap();
+    assert_eq!(writer.get_ref(), b"<a/>");
+    writer.get_mut().extend_from_slice(b"<b/>");
+    assert_eq!(writer.into_inner(), b"<a/><b/>");
+}
+
+#[test]
+fn test_copy_all() {
+    let mut reader = Reader::from_str("<a x='1'><!--c--><b>text</b><?pi?></a>");
+    let mut writer = Writer::new(Vec::new());
+    copy_all(&mut reader, &mut writer, |event| match event {
+        Comment(_) | PI(_) => None,
+        Text(ref e) if &**e == b"text" => Some(Text(BytesText::from_plain_str("changed"))),
+        e => Some(e),
+    })
+    .unwrap();
+    assert_eq!(writer.into_string().unwrap(), "<a x='1'><b>changed</b></a>");
+
+    let xml = "<a><![CDATA[x < y & z]]><![CDATA[&amp;]]></a>";
+    let mut reader = Reader::from_str(xml);
+    let mut writer = Writer::new(Vec::new());
+    copy_all(&mut reader, &mut writer, |event| Some(event)).unwrap();
+    assert_eq!(writer.into_string().unwrap(), xml);
 }
 
 #[test]
-fn test_start_attr() {
-    let mut r = Reader::from_str("<a b=\"c\">");
-    r.trim_text(true);
-    next_eq!(r, Start, b"a");
+fn test_writer_write_end() {
+    let mut writer = Writer::new(Vec::new());
+    writer
+        .write_event(Start(BytesStart::borrowed_name(b"a")))
+        .unwrap();
+    writer
+        .write_event(Start(BytesStart::borrowed(b"b x='1'", 1)))
+        .unwrap();
+    writer
+        .write_event(Empty(BytesStart::borrowed_name(b"c")))
+        .unwrap();
+    writer.write_end().unwrap();
+    writer
+        .write_event(Start(BytesStart::borrowed_name(b"d")))
+        .unwrap();
+    writer.write_event(End(BytesEnd::borrowed(b"d"))).unwrap();
+    writer
+        .write_event(Start(BytesStart::borrowed_name(b"e")))
+        .unwrap();
+    writer.close_all().unwrap();
+    match writer.write_end() {
+        Err(Error::EndEventMismatch { .. }) => (),
+        e => panic!("expecting EndEventMismatch error, found {:?}", e),
+    }
+    assert_eq!(
+        writer.into_string().unwrap(),
+        "<a><b x='1'><c/></b><d></d><e></e></a>"
+    );
 }
 
 #[test]
-fn test_nested() {
-    let mut r = Reader::from_str("<a><b>test</b><c/></a>");
-    r.trim_text(true).expand_empty_elements(false);
-    next_eq!(r, Start, b"a", Start, b"b", Text, b"test", End, b"b", Empty, b"c", End, b"a");
+fn test_writer_check_end_names() {
+    let mut writer = Writer::new(Vec::new());
+    writer.check_end_names(true);
+    writer
+        .write_event(Start(BytesStart::borrowed_name(b"a")))
+        .unwrap();
+    match writer.write_event(End(BytesEnd::borrowed(b"b"))) {
+        Err(Error::EndEventMismatch {
+            ref expected,
+            ref found,
+        }) if expected == "a" && found == "b" => (),
+        e => panic!("expecting EndEventMismatch error, found {:?}", e),
+    }
+    writer.write_event(End(BytesEnd::borrowed(b"a"))).unwrap();
+    match writer.write_event(End(BytesEnd::borrowed(b"a"))) {
+        Err(Error::EndEventMismatch { .. }) => (),
+        e => panic!("expecting EndEventMismatch error, found {:?}", e),
+    }
+    assert_eq!(writer.into_string().unwrap(), "<a></a>");
 }
 
 #[test]
-fn test_writer() {
-    let txt = include_str!("../tests/documents/test_writer.xml").trim();
-    let mut reader = Reader::from_str(txt);
-    reader.trim_text(true);
-    let mut writer = Writer::new(Cursor::new(Vec::new()));
-    let mut buf = Vec::new();
-    loop {
-        match reader.read_event(&mut buf) {
-            Ok(Eof) => break,
-            Ok(e) => assert!(writer.write_event(e).is_ok()),
-            Err(e) => panic!(e),
+fn test_writer_validate() {
+    fn invalid<'a>(prefix: &[Event<'a>], event: Event<'a>) {
+        let mut writer = Writer::new(Vec::new());
+        writer.validate(true);
+        for e in prefix {
+            writer.write_event(e).unwrap();
+        }
+        match writer.write_event(&event) {
+            Err(Error::NotWellFormed(_)) => (),
+            e => panic!("expecting NotWellFormed error for {:?}, found {:?}", event, e),
         }
     }
 
-    let result = writer.into_inner().into_inner();
-    assert_eq!(result, txt.as_bytes());
+    let a = Start(BytesStart::borrowed_name(b"a"));
+    invalid(&[], Start(BytesStart::borrowed_name(b"1a")));
+    invalid(&[], Empty(BytesStart::borrowed(b"a b<='1'", 1)));
+    invalid(&[], Empty(BytesStart::borrowed(b"a b='<'", 1)));
+    invalid(&[], Empty(BytesStart::borrowed(b"a b='&c'", 1)));
+    invalid(&[a.clone()], Text(BytesText::from_escaped_str("a < b")));
+    invalid(&[a.clone()], Text(BytesText::from_escaped_str("a & b")));
+    invalid(&[a.clone()], Text(BytesText::from_escaped_str("&#xZ;")));
+    invalid(&[a.clone()], End(BytesEnd::borrowed(b"b")));
+    invalid(&[], End(BytesEnd::borrowed(b"a")));
+    invalid(&[], Empty(BytesStart::borrowed(b"a b='1' c='2' b='3'", 1)));
+    invalid(&[], Comment(BytesText::from_escaped_str("a -- b")));
+    invalid(&[], Comment(BytesText::from_escaped_str("a -")));
+    invalid(
+        &[a.clone(), End(BytesEnd::borrowed(b"a"))],
+        Empty(BytesStart::borrowed_name(b"b")),
+    );
+
+    let mut writer = Writer::new(Vec::new());
+    writer.validate(true);
+    writer
+        .create_element("ns:a")
+        .with_attribute(("b", "<&>\""))
+        .write_inner_content(|writer| {
+            writer
+                .write_event(Text(BytesText::from_escaped_str("&lt;&#60;&#x3C;&custom;")))?;
+            writer.create_element("é.1").write_text_content("&")?;
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(
+        writer.into_string().unwrap(),
+        "<ns:a b=\"&lt;&amp;&gt;&quot;\">&lt;&#60;&#x3C;&custom;<é.1>&amp;</é.1></ns:a>"
+    );
+
+    let mut writer = Writer::new(Vec::new());
+    writer.validate(true);
+    writer
+        .write_event(Comment(BytesText::from_escaped_str("")))
+        .unwrap();
+    writer
+        .write_event(Comment(BytesText::from_escaped_str(" a - b ")))
+        .unwrap();
+    match writer.write_event(Empty(BytesStart::borrowed(b"e id='1' id='2'", 1))) {
+        Err(Error::NotWellFormed(ref msg)) if msg.contains("`id`") => (),
+        e => panic!("expecting NotWellFormed error naming `id`, found {:?}", e),
+    }
+    match writer.write_event(Comment(BytesText::from_escaped_str("a -- b"))) {
+        Err(Error::NotWellFormed(ref msg)) if msg.contains("position 2") => (),
+        e => panic!("expecting NotWellFormed error at position 2, found {:?}", e),
+    }
+    writer
+        .write_event(Comment(BytesText::from_comment_lossy(b"a -- b -")))
+        .unwrap();
+    assert_eq!(
+        writer.into_string().unwrap(),
+        "<!----><!-- a - b --><!--a - - b - -->"
+    );
+
+    let mut writer = Writer::new(Vec::new());
+    writer.validate(true);
+    let decl = Decl(BytesDecl::new(b"1.0", None, None));
+    writer.write_event(&decl).unwrap();
+    writer
+        .write_event(Empty(BytesStart::borrowed_name(b"a")))
+        .unwrap();
+    match writer.write_event(&decl) {
+        Err(Error::MisplacedDeclaration) => (),
+        e => panic!("expecting MisplacedDeclaration error, found {:?}", e),
+    }
+    assert_eq!(writer.into_string().unwrap(), "<?xml version=\"1.0\"?><a/>");
 }
 
 #[test]
-fn test_writer_borrow() {
-    let txt = include_str!("../tests/documents/test_writer.xml").trim();
-    let mut reader = Reader::from_str(txt);
-    reader.trim_text(true);
-    let mut writer = Writer::new(Cursor::new(Vec::new()));
-    let mut buf = Vec::new();
-    loop {
-        match reader.read_event(&mut buf) {
-            Ok(Eof) => break,
-            Ok(e) => assert!(writer.write_event(&e).is_ok()), // either `e` or `&e`
-            Err(e) => panic!(e),
-        }
-    }
+fn test_writer_buffered() {
+    let mut writer = Writer::new_buffered(Vec::new());
+    writer
+        .write_event(Start(BytesStart::borrowed_name(b"a")))
+        .unwrap();
+    writer.write_event(End(BytesEnd::borrowed(b"a"))).unwrap();
+    assert!(writer.inner().get_ref().is_empty());
 
-    let result = writer.into_inner().into_inner();
-    assert_eq!(result, txt.as_bytes());
+    writer.flush().unwrap();
+    assert_eq!(writer.inner().get_ref(), b"<a></a>");
 }
 
 #[test]
-fn test_writer_indent() {
-    let txt = include_str!("../tests/documents/test_writer_indent.xml");
-    let mut reader = Reader::from_str(txt);
-    reader.trim_text(true);
-    let mut writer = Writer::new_with_indent(Cursor::new(Vec::new()), b' ', 4);
+fn test_writer_indent_roundtrip() {
+    fn events(xml: &[u8]) -> Vec<String> {
+        let mut reader = Reader::from_reader(xml);
+        reader.trim_text(true);
+        let mut events = Vec::new();
+        let mut buf = Vec::new();
+        loop {
+            match reader.read_event(&mut buf) {
+                Ok(Eof) => break,
+                Ok(e) => events.push(format!("{:?}", e)),
+                Err(e) => panic!(e),
+            }
+            buf.clear();
+        }
+        events
+    }
+
+    let txt = br#"<?xml version="1.0"?><a x="1"><!--c--><b>text</b><c><d/><?pi?></c></a>"#;
+    let mut reader = Reader::from_reader(&txt[..]);
+    let mut writer = Writer::new_with_indent(Cursor::new(Vec::new()), b' ', 2);
     let mut buf = Vec::new();
     loop {
         match reader.read_event(&mut buf) {
@@ -251,11 +2097,23 @@ fn test_writer_indent() {
             Ok(e) => assert!(writer.write_event(e).is_ok()),
             Err(e) => panic!(e),
         }
+        buf.clear();
     }
 
     let result = writer.into_inner().into_inner();
-    // println!("{:?}", String::from_utf8_lossy(&result));
-    assert_eq!(result, txt.as_bytes());
+    assert_eq!(
+        String::from_utf8(result.clone()).unwrap(),
+        r#"<?xml version="1.0"?>
+<a x="1">
+  <!--c-->
+  <b>text</b>
+  <c>
+    <d/>
+    <?pi?>
+  </c>
+</a>"#
+    );
+    assert_eq!(events(&result), events(txt));
 }
 
 #[test]
@@ -308,6 +2166,74 @@ fn test_write_attrs() {
     assert_eq!(result, expected.as_bytes());
 }
 
+#[test]
+fn test_write_bom_and_declaration() {
+    let mut writer = Writer::new(Vec::new());
+    writer.check_declaration(true);
+    writer.write_bom().unwrap();
+    writer
+        .write_declaration(b"1.0", Some(b"UTF-8"), None)
+        .unwrap();
+    writer
+        .write_event(Empty(BytesStart::borrowed_name(b"root")))
+        .unwrap();
+    match writer.write_bom() {
+        Err(Error::MisplacedDeclaration) => (),
+        e => panic!("expecting MisplacedDeclaration error, found {:?}", e),
+    }
+    match writer.write_declaration(b"1.0", None, None) {
+        Err(Error::MisplacedDeclaration) => (),
+        e => panic!("expecting MisplacedDeclaration error, found {:?}", e),
+    }
+    assert_eq!(
+        writer.into_inner(),
+        b"\xEF\xBB\xBF<?xml version=\"1.0\" encoding=\"UTF-8\"?><root/>".to_vec()
+    );
+}
+
+#[test]
+fn test_write_doctype() {
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    writer.write_declaration(b"1.0", None, None).unwrap();
+    let doctype = BytesText::doctype(
+        "html",
+        Some("-//W3C//DTD XHTML 1.0 Strict//EN"),
+        Some("http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd"),
+        None,
+    )
+    .unwrap();
+    writer.write_event(DocType(doctype)).unwrap();
+    writer
+        .write_event(Empty(BytesStart::borrowed_name(b"html")))
+        .unwrap();
+    assert_eq!(
+        writer.into_string().unwrap(),
+        r#"<?xml version="1.0"?>
+<!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.0 Strict//EN" "http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd">
+<html/>"#
+    );
+
+    let doctype =
+        BytesText::doctype("a", None, Some(r#"a"b.dtd"#), Some("<!ENTITY c 'd'>")).unwrap();
+    assert_eq!(&*doctype, br#" a SYSTEM 'a"b.dtd' [<!ENTITY c 'd'>]"#.as_ref());
+    assert_eq!(&*BytesText::doctype("a", None, None, None).unwrap(), b" a");
+
+    // PUBLIC must be followed by a system literal
+    match BytesText::doctype("a", Some("pub"), None, None) {
+        Err(Error::NotWellFormed(_)) => (),
+        e => panic!("expecting NotWellFormed error, found {:?}", e),
+    }
+    // an identifier cannot contain both quotes
+    match BytesText::doctype("a", None, Some(r#"a"b'c"#), None) {
+        Err(Error::NotWellFormed(_)) => (),
+        e => panic!("expecting NotWellFormed error, found {:?}", e),
+    }
+    match BytesText::doctype("a", Some(r#"a"b'c"#), Some("a.dtd"), None) {
+        Err(Error::NotWellFormed(_)) => (),
+        e => panic!("expecting NotWellFormed error, found {:?}", e),
+    }
+}
+
 #[test]
 fn test_new_xml_decl_full() {
     let mut writer = Writer::new(Vec::new());
@@ -473,6 +2399,73 @@ fn test_buf_position_err_comment_trim_text() {
     }
 }
 
+#[test]
+fn test_attribute_err_position_is_absolute() {
+    let mut r = Reader::from_str("<root>\n  <a x='1'\n     y='2' x='3'/>\n</root>");
+    r.trim_text(true);
+
+    next_eq!(r, Start, b"root");
+
+    let mut buf = Vec::new();
+    match r.read_event(&mut buf) {
+        Ok(Empty(ref e)) => {
+            let mut attributes = e.attributes();
+            assert!(attributes.next().unwrap().is_ok());
+            assert!(attributes.next().unwrap().is_ok());
+            match attributes.next() {
+                Some(Err(Error::DuplicatedAttribute(29, 12))) => (),
+                a => panic!("expecting duplicated attribute at 29 and 12, found {:?}", a),
+            }
+
+            // positions are relative to the content once it is modified
+            let mut e = e.clone();
+            e.set_name(b"abc");
+            match e.attributes().nth(2) {
+                Some(Err(Error::DuplicatedAttribute(21, 4))) => (),
+                a => panic!("expecting duplicated attribute at 21 and 4, found {:?}", a),
+            }
+        }
+        e => panic!("expecting Empty, found {:?}", e),
+    }
+}
+
+#[test]
+fn test_attribute_unclosed_value() {
+    let mut r = Reader::from_str(r#"<a b="unterminated>"#);
+    r.trim_text(true);
+    let mut buf = Vec::new();
+    match r.read_event(&mut buf) {
+        Ok(Start(ref e)) => match e.attributes().next() {
+            Some(Err(Error::UnclosedAttributeValue(5))) => (),
+            a => panic!("expecting unclosed attribute value at 5, found {:?}", a),
+        },
+        e => panic!("expecting Start, found {:?}", e),
+    }
+}
+
+#[test]
+fn test_local_name() {
+    let mut r = Reader::from_str("<ns:a x='1'><b/><ns:c/></ns:a>");
+    r.trim_text(true);
+    let mut buf = Vec::new();
+    let mut names = Vec::new();
+    loop {
+        let (name, local_name) = match r.read_event(&mut buf).unwrap() {
+            Start(ref e) | Empty(ref e) => (e.name().to_vec(), e.local_name().to_vec()),
+            End(ref e) => (e.name().to_vec(), e.local_name().to_vec()),
+            Eof => break,
+            _ => continue,
+        };
+        names.push(format!(
+            "{} {}",
+            from_utf8(&name).unwrap(),
+            from_utf8(&local_name).unwrap()
+        ));
+        buf.clear();
+    }
+    assert_eq!(names, vec!["ns:a a", "b b", "ns:c c", "ns:a a"]);
+}
+
 #[test]
 fn test_namespace() {
     let mut r = Reader::from_str("<a xmlns:myns='www1'><myns:b>in namespace!</myns:b></a>");
@@ -496,6 +2489,42 @@ fn test_namespace() {
     }
 }
 
+#[test]
+fn test_resolve_namespace() {
+    let mut r = Reader::from_str("<a xmlns='d' xmlns:x='u1'><b xmlns:x='u2'/><c/></a>");
+    r.trim_text(true);
+
+    let mut buf = Vec::new();
+    let mut ns_buf = Vec::new();
+    assert_eq!(r.resolve_namespace(b"x", &ns_buf), None);
+
+    // <a>
+    r.read_namespaced_event(&mut buf, &mut ns_buf).unwrap();
+    assert_eq!(r.resolve_namespace(b"", &ns_buf), Some(&b"d"[..]));
+    assert_eq!(r.resolve_namespace(b"x", &ns_buf), Some(&b"u1"[..]));
+    assert_eq!(r.resolve_namespace(b"y", &ns_buf), None);
+
+    // <b/> shadows `x`
+    r.read_namespaced_event(&mut buf, &mut ns_buf).unwrap();
+    assert_eq!(r.resolve_namespace(b"x", &ns_buf), Some(&b"u2"[..]));
+    // </b>
+    r.read_namespaced_event(&mut buf, &mut ns_buf).unwrap();
+
+    // <c/> sees the declaration of <a> again
+    r.read_namespaced_event(&mut buf, &mut ns_buf).unwrap();
+    assert_eq!(r.resolve_namespace(b"x", &ns_buf), Some(&b"u1"[..]));
+    r.read_namespaced_event(&mut buf, &mut ns_buf).unwrap();
+
+    // </a>, then Eof
+    r.read_namespaced_event(&mut buf, &mut ns_buf).unwrap();
+    match r.read_namespaced_event(&mut buf, &mut ns_buf) {
+        Ok((None, Eof)) => (),
+        e => panic!("expecting Eof, found {:?}", e),
+    }
+    assert_eq!(r.resolve_namespace(b"", &ns_buf), None);
+    assert_eq!(r.resolve_namespace(b"x", &ns_buf), None);
+}
+
 #[test]
 fn test_default_namespace() {
     let mut r = Reader::from_str("<a ><b xmlns=\"www1\"></b></a>");
@@ -849,6 +2878,161 @@ fn test_unescape_and_decode_without_bom_removes_utf8_bom() {
     assert_eq!(txt, vec![""]);
 }
 
+#[test]
+fn test_from_file() {
+    let mut reader = Reader::from_file("./tests/documents/sample_1.xml").unwrap();
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    let mut count = 0;
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Start(_)) => count += 1,
+            Ok(Eof) => break,
+            Err(e) => panic!("{:?}", e),
+            _ => (),
+        }
+        buf.clear();
+    }
+    assert!(count > 0);
+
+    match Reader::from_file("./tests/documents/does_not_exist.xml") {
+        Err(Error::Io(_)) => (),
+        _ => panic!("expecting Io error"),
+    }
+}
+
+#[test]
+#[cfg(feature = "encoding")]
+fn test_writer_with_encoding() {
+    use encoding_rs::WINDOWS_1252;
+
+    let mut writer = Writer::with_encoding(Vec::new(), WINDOWS_1252);
+    writer.write_declaration(b"1.0", None, None).unwrap();
+    let mut start = BytesStart::borrowed_name("café".as_bytes());
+    start.push_attribute(("a", "é & ж"));
+    writer.write_event(Start(start)).unwrap();
+    writer
+        .write_event(Text(BytesText::from_plain_str("€ ж")))
+        .unwrap();
+    writer.write_comment(" é ".as_bytes()).unwrap();
+    match writer.write_comment(" ж ".as_bytes()) {
+        Err(Error::UnencodableCharacter('ж')) => (),
+        e => panic!("Expecting UnencodableCharacter, found {:?}", e),
+    }
+    match writer.write_event(Empty(BytesStart::borrowed_name("ж".as_bytes()))) {
+        Err(Error::UnencodableCharacter('ж')) => (),
+        e => panic!("Expecting UnencodableCharacter, found {:?}", e),
+    }
+    writer
+        .write_event(End(BytesEnd::borrowed("café".as_bytes())))
+        .unwrap();
+    let result = writer.into_inner();
+    assert_eq!(
+        result,
+        &b"<?xml version=\"1.0\" encoding=\"windows-1252\"?><caf\xE9 a=\"\xE9 &amp; &#1078;\">\x80 &#1078;<!-- \xE9 --></caf\xE9>"[..]
+    );
+
+    let mut reader = Reader::from_reader(&result[..]);
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    assert!(matches!(reader.read_event(&mut buf), Ok(Decl(_))));
+    match reader.read_event(&mut buf) {
+        Ok(Start(e)) => {
+            assert_eq!(reader.decode(e.name()), "café");
+            let attribute = e.attributes().next().unwrap().unwrap();
+            assert_eq!(
+                attribute.unescape_and_decode_value(&reader).unwrap(),
+                "é & ж"
+            );
+        }
+        e => panic!("Expecting Start, found {:?}", e),
+    }
+    match reader.read_event(&mut buf) {
+        Ok(Text(e)) => assert_eq!(e.unescape_and_decode(&reader).unwrap(), "€ ж"),
+        e => panic!("Expecting Text, found {:?}", e),
+    }
+}
+
+#[test]
+#[cfg(feature = "encoding")]
+fn test_writer_with_encoding_text_writer() {
+    use encoding_rs::WINDOWS_1252;
+
+    let mut writer = Writer::with_encoding(Vec::new(), WINDOWS_1252);
+    writer
+        .write_event(Start(BytesStart::borrowed_name(b"a")))
+        .unwrap();
+    {
+        let mut text = writer.text_writer();
+        // `é` split between two writes
+        text.write_all(b"caf\xC3").unwrap();
+        text.write_all(&[&b"\xA9 < "[..], "ж".as_bytes()].concat())
+            .unwrap();
+        assert_eq!(
+            text.write_all(b"\xFF").unwrap_err().kind(),
+            std::io::ErrorKind::InvalidData
+        );
+    }
+    writer.write_text_from_reader("€".as_bytes()).unwrap();
+    writer.write_raw(b"<b/>").unwrap();
+    writer.write_end().unwrap();
+    assert_eq!(
+        writer.into_inner(),
+        &b"<a>caf\xE9 &lt; &#1078;\x80<b/></a>"[..]
+    );
+}
+
+#[test]
+#[cfg(feature = "encoding")]
+fn test_writer_with_encoding_latin1_label() {
+    use encoding_rs::{Encoding, WINDOWS_1252};
+
+    // like browsers, encoding_rs maps the ISO-8859-1 label to windows-1252
+    let encoding = Encoding::for_label(b"ISO-8859-1").unwrap();
+    assert_eq!(encoding, WINDOWS_1252);
+    let mut writer = Writer::with_encoding(Vec::new(), encoding);
+    writer.write_declaration(b"1.0", None, None).unwrap();
+    writer
+        .write_event(Start(BytesStart::borrowed_name(b"a")))
+        .unwrap();
+    // U+0080 to U+009F are ISO-8859-1 bytes 0x80 to 0x9F, but windows-1252 only keeps the ones
+    // it leaves unassigned, and uses the other bytes for characters such as `€` and `Ÿ`
+    writer
+        .write_event(Text(BytesText::from_plain_str(
+            "\u{80}\u{81}\u{85}\u{8D}\u{9F} \u{a0}\u{ff} € Ÿ",
+        )))
+        .unwrap();
+    writer.write_end().unwrap();
+    assert_eq!(
+        writer.into_inner(),
+        &b"<?xml version=\"1.0\" encoding=\"windows-1252\"?>\
+           <a>&#128;\x81&#133;\x8D&#159; \xA0\xFF \x80 \x9F</a>"[..]
+    );
+}
+
+#[test]
+#[cfg(feature = "encoding")]
+fn test_decode_strict() {
+    let mut reader = Reader::from_reader(
+        &b"<?xml version=\"1.0\" encoding=\"Shift_JIS\"?><a>\x82\xa0 \x82</a>"[..],
+    );
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    assert!(matches!(reader.read_event(&mut buf), Ok(Decl(_))));
+    assert!(matches!(reader.read_event(&mut buf), Ok(Start(_))));
+    match reader.read_event(&mut buf) {
+        Ok(Text(e)) => {
+            assert_eq!(reader.decode(&e), "\u{3042} \u{FFFD}");
+            match reader.decode_strict(&e) {
+                Err(Error::UndecodableBytes(name)) => assert_eq!(name, "Shift_JIS"),
+                e => panic!("expecting UndecodableBytes, found {:?}", e),
+            }
+            assert_eq!(reader.decode_strict(&e[..3]).unwrap(), "\u{3042} ");
+        }
+        e => panic!("expecting text event, found {:?}", e),
+    }
+}
+
 #[test]
 #[cfg(feature = "encoding")]
 fn test_unescape_and_decode_without_bom_removes_utf16be_bom() {
@@ -907,3 +3091,105 @@ fn test_unescape_and_decode_without_bom_does_nothing_if_no_bom_exists() {
     }
     assert_eq!(txt.is_empty(), true);
 }
+
+#[test]
+fn test_doctype_entities() {
+    let mut reader = Reader::from_str(
+        r#"<!DOCTYPE doc [<!ENTITY me "Jane"><!ENTITY you 'John'>]><doc>&me;</doc>"#,
+    );
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    match reader.read_event(&mut buf) {
+        Ok(DocType(e)) => {
+            let entities = e.entities().unwrap();
+            assert_eq!(entities.len(), 2);
+            assert_eq!(entities[&b"me"[..]], b"Jane");
+            assert_eq!(entities[&b"you"[..]], b"John");
+        }
+        e => panic!("Expecting DocType, found {:?}", e),
+    }
+}
+
+#[test]
+fn test_qname() {
+    use quick_xml::name::QName;
+    use std::collections::HashMap;
+
+    let mut reader = Reader::from_str(r#"<a:x a:k="1" b:k="2" k="3"></a:x>"#);
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    match reader.read_event(&mut buf) {
+        Ok(Start(e)) => {
+            assert_eq!(e.qname(), QName::from("a:x"));
+            assert_eq!(e.qname(), e.to_end().qname());
+            let values: HashMap<_, _> = e
+                .attributes()
+                .map(|a| {
+                    let a = a.unwrap();
+                    (a.qname(), a.value.into_owned())
+                })
+                .collect();
+            assert_eq!(values.len(), 3);
+            assert_eq!(values[&QName::from("b:k")], b"2");
+            assert_eq!(values[&QName::from("k")], b"3");
+        }
+        e => panic!("Expecting Start, found {:?}", e),
+    }
+}
+
+#[test]
+fn test_writer_canonical() {
+    fn canonicalize(xml: &str) -> String {
+        let mut reader = Reader::from_str(xml);
+        let mut writer = Writer::new(Vec::new());
+        writer.canonical(true);
+        let mut buf = Vec::new();
+        loop {
+            match reader.read_event(&mut buf).unwrap() {
+                Eof => break,
+                e => assert!(writer.write_event(e).is_ok()),
+            }
+            buf.clear();
+        }
+        writer.into_string().unwrap()
+    }
+
+    let documents = [
+        (
+            include_str!("documents/c14n_pi_comments.xml"),
+            include_str!("documents/c14n_pi_comments_canonical.xml"),
+        ),
+        (
+            include_str!("documents/c14n_tags.xml"),
+            include_str!("documents/c14n_tags_canonical.xml"),
+        ),
+        (
+            include_str!("documents/c14n_characters.xml"),
+            include_str!("documents/c14n_characters_canonical.xml"),
+        ),
+    ];
+    for &(xml, canonical) in documents.iter() {
+        assert_eq!(canonicalize(xml), canonical);
+        assert_eq!(canonicalize(canonical), canonical);
+    }
+    assert_eq!(
+        canonicalize("<a>\r\n<b xml:lang='en' a:x='1' xmlns:a='urn:a'>x\ry</b></a>\r\n"),
+        "<a>\n<b xmlns:a=\"urn:a\" xml:lang=\"en\" a:x=\"1\">x\ny</b></a>"
+    );
+}
+
+#[test]
+fn test_lenient_entities() {
+    let mut reader = Reader::from_str("<a>&foo;&amp;&#x41;</a>");
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    assert!(matches!(reader.read_event(&mut buf), Ok(Start(_))));
+    match reader.read_event(&mut buf) {
+        Ok(Text(e)) => {
+            assert!(e.unescape_and_decode(&reader).is_err());
+            reader.lenient_entities(true);
+            assert_eq!(e.unescape_and_decode(&reader).unwrap(), "&foo;&A");
+        }
+        e => panic!("Expecting Text, found {:?}", e),
+    }
+}
@@ -155,7 +155,7 @@ fn issue_83_duplicate_attributes() {
         b"
             |StartElement(hello)
             |1:30 EmptyElement(some-tag, attr-error: error while parsing \
-                  attribute at position 16: Duplicate attribute at position 9 and 16)
+                  attribute at position 24: Duplicate attribute at position 17 and 24)
             |EndElement(hello)
             |EndDocument
         ",